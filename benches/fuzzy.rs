@@ -0,0 +1,107 @@
+//! Scan a large candidate set with the fuzzy engine, report the throughput and the allocations per
+//! item, e.g. `cargo bench --bench fuzzy`.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use skim::prelude::*;
+
+const NUM_ITEMS: usize = 200_000;
+
+/// count the allocations, to see the churn of matching
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// a line of `index<TAB>path`, only the path is matched (as `--nth=2`)
+struct Candidate {
+    line: String,
+    ranges: [(usize, usize); 1],
+}
+
+impl SkimItem for Candidate {
+    fn display(&self) -> Cow<'_, AnsiString<'_>> {
+        Cow::Owned(self.line.as_str().into())
+    }
+
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.line)
+    }
+
+    fn get_matching_ranges(&self) -> Cow<'_, [(usize, usize)]> {
+        Cow::Borrowed(&self.ranges)
+    }
+}
+
+fn candidates() -> Vec<Arc<ItemWrapper>> {
+    (0..NUM_ITEMS)
+        .map(|idx| {
+            let path = format!("src/module_{}/sub_{}/file_{}.rs", idx % 97, idx % 13, idx);
+            let prefix = format!("{}\t", idx);
+            let line = format!("{}{}", prefix, path);
+            let ranges = [(prefix.len(), line.len())];
+            let item: Arc<dyn SkimItem> = Arc::new(Candidate { line, ranges });
+            Arc::new(ItemWrapper::new(item, (0, idx as u32)))
+        })
+        .collect()
+}
+
+fn bench(items: &[Arc<ItemWrapper>], algorithm: FuzzyAlgorithm, word_match: bool, query: &str) {
+    let factory = ExactOrFuzzyEngineFactory::builder()
+        .fuzzy_algorithm(algorithm)
+        .word_match(word_match)
+        .build();
+    let engine = factory.create_engine(query);
+
+    // warm up, e.g. the scratch buffers of the matchers
+    let _ = items
+        .iter()
+        .take(1000)
+        .filter_map(|item| engine.match_item(item.clone()))
+        .count();
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let started_at = Instant::now();
+    let matched: Vec<_> = items
+        .iter()
+        .filter_map(|item| engine.match_item(item.clone()))
+        .collect();
+    let elapsed = started_at.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    // the matched items hold their matched indices, i.e. an allocation each at least
+    println!(
+        "{:<8} {:<10} {:<16} {:>7} matched  {:>8.0} items/ms  {:>5.2} allocations/matched  {:>5.2} allocations/item",
+        format!("{:?}", algorithm),
+        if word_match { "word-match" } else { "" },
+        query,
+        matched.len(),
+        items.len() as f64 / elapsed.as_secs_f64() / 1000.0,
+        allocations as f64 / matched.len().max(1) as f64,
+        allocations as f64 / items.len() as f64,
+    );
+}
+
+fn main() {
+    let items = candidates();
+    for &algorithm in &[FuzzyAlgorithm::SkimV2, FuzzyAlgorithm::SkimV1, FuzzyAlgorithm::Clangd] {
+        for query in &["mod1file", "sub_1/file_99"] {
+            bench(&items, algorithm, false, query);
+        }
+    }
+    bench(&items, FuzzyAlgorithm::SkimV2, true, "file_99");
+}
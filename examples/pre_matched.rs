@@ -0,0 +1,36 @@
+extern crate skim;
+use skim::prelude::*;
+
+/// show the results of an external search, nothing is matched by skim
+pub fn main() {
+    let options = SkimOptionsBuilder::default()
+        .multi(true)
+        .query(Some("lib"))
+        .build()
+        .unwrap();
+
+    let results = vec![("src/lib.rs", 0, 3), ("src/libfoo/mod.rs", 4, 7), ("lib/tree.rs", 0, 3)];
+    let items = results
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (path, start, end))| {
+            let item = Arc::new(ItemWrapper::new(Arc::new(path), (0, idx as u32)));
+            let rank = Rank {
+                index: idx as i64,
+                ..Rank::default()
+            };
+            MatchedItem::builder(item)
+                .rank(rank)
+                .matched_range(MatchedRange::ByteRange(start, end))
+                .build()
+        })
+        .collect();
+
+    let selected_items = Skim::run_with_matched(&options, items)
+        .map(|out| out.selected_items)
+        .unwrap_or_default();
+
+    for item in selected_items.iter() {
+        println!("{}", item.output());
+    }
+}
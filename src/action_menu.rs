@@ -0,0 +1,140 @@
+//! the overlay of the actions of the current item, shown by `action-menu`
+use crate::event::UpdateScreen;
+use crate::event::{Event, EventHandler};
+use crate::theme::ColorTheme;
+use crate::theme::DEFAULT_THEME;
+use crate::SkimItem;
+use std::cmp::{max, min};
+use std::sync::Arc;
+use tuikit::prelude::*;
+
+pub struct ActionMenu {
+    item: Arc<dyn SkimItem>,
+    actions: Vec<String>,
+    cursor: usize,
+    theme: Arc<ColorTheme>,
+}
+
+impl ActionMenu {
+    /// the menu of `SkimItem::actions`, `None` if the item has none
+    pub fn new(item: Arc<dyn SkimItem>) -> Option<Self> {
+        let actions: Vec<String> = item.actions().iter().map(|action| action.to_string()).collect();
+        if actions.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            item,
+            actions,
+            cursor: 0,
+            theme: Arc::new(*DEFAULT_THEME),
+        })
+    }
+
+    pub fn theme(mut self, theme: Arc<ColorTheme>) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// move the cursor down by `diff` actions, clamped to the first and the last
+    pub fn act_move_cursor(&mut self, diff: i32) {
+        let cursor = max(0, self.cursor as i32 + diff) as usize;
+        self.cursor = min(cursor, self.actions.len() - 1);
+    }
+
+    /// the item the actions are offered for
+    pub fn item(&self) -> &Arc<dyn SkimItem> {
+        &self.item
+    }
+
+    /// the item and the action under the cursor
+    pub fn into_chosen(self) -> (Arc<dyn SkimItem>, String) {
+        let action = self.actions[self.cursor].clone();
+        (self.item, action)
+    }
+}
+
+impl Draw for ActionMenu {
+    fn draw(&self, canvas: &mut dyn Canvas) -> Result<()> {
+        let (_screen_width, screen_height) = canvas.size()?;
+        canvas.clear()?;
+        let title = format!("Actions of {} (choose by up/down and accept)", self.item.text());
+        canvas.print_with_attr(0, 0, &title, self.theme.prompt())?;
+
+        // the first row is the title, keep the cursor on the screen
+        let height = max(1, screen_height.saturating_sub(1));
+        let offset = (self.cursor + 1).saturating_sub(height);
+        for (row, action) in self.actions.iter().enumerate().skip(offset).take(height) {
+            let (cursor, attr) = if row == self.cursor {
+                ("> ", self.theme.current())
+            } else {
+                ("  ", self.theme.normal())
+            };
+            canvas.print_with_attr(row - offset + 1, 0, cursor, self.theme.cursor())?;
+            canvas.print_with_attr(row - offset + 1, 2, action, attr)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Widget<Event> for ActionMenu {}
+
+impl EventHandler for ActionMenu {
+    fn handle(&mut self, event: &Event) -> UpdateScreen {
+        match event {
+            Event::EvActUp(diff) => self.act_move_cursor(-*diff),
+            Event::EvActDown(diff) => self.act_move_cursor(*diff),
+            _ => return UpdateScreen::DONT_REDRAW,
+        }
+
+        UpdateScreen::REDRAW
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::screen_row;
+    use crate::AnsiString;
+    use std::borrow::Cow;
+    use tuikit::screen::Screen;
+
+    struct File;
+
+    impl SkimItem for File {
+        fn display(&self) -> Cow<'_, AnsiString<'_>> {
+            Cow::Owned("a.rs".into())
+        }
+
+        fn text(&self) -> Cow<'_, str> {
+            Cow::Borrowed("a.rs")
+        }
+
+        fn actions(&self) -> Vec<&str> {
+            vec!["open", "edit", "delete"]
+        }
+    }
+
+    #[test]
+    fn test_choose_action() {
+        assert!(ActionMenu::new(Arc::new("a.rs")).is_none());
+
+        let mut menu = ActionMenu::new(Arc::new(File)).unwrap();
+        let mut screen = Screen::new(80, 3);
+        menu.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 1), "> open");
+        assert_eq!(screen_row(&screen, 2), "edit");
+
+        // clamped to the last, scrolled to keep the cursor shown
+        menu.handle(&Event::EvActDown(5));
+        menu.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 1), "edit");
+        assert_eq!(screen_row(&screen, 2), "> delete");
+
+        menu.handle(&Event::EvActUp(1));
+        let (item, action) = menu.into_chosen();
+        assert_eq!(item.text(), "a.rs");
+        assert_eq!(action, "edit");
+    }
+}
@@ -2,7 +2,6 @@ extern crate clap;
 extern crate env_logger;
 #[macro_use]
 extern crate log;
-extern crate shlex;
 extern crate skim;
 extern crate time;
 
@@ -30,30 +29,73 @@ Usage: sk [options]
     -t, --tiebreak [score,index,begin,end,-score,...]
                          comma seperated criteria
     -n, --nth 1,2..5     specify the fields to be matched
+    --match-prefix-chars=K
+                         only match the first K characters of each item
+    --match-skip=N|space Do not match the first N characters of each item
+                         (or the ones till the first space)
     --with-nth 1,2..5    specify the fields to be transformed
+    --accept-nth 1,2..5  specify the fields to be output
     -d, --delimiter \\t  specify the delimiter(in REGEX) for fields
     -e, --exact          start skim in exact mode
+    --word-match         only match the terms at word boundaries
     --regex              use regex instead of fuzzy match
     --algo=TYPE          Fuzzy matching algorithm:
                          [skim_v1|skim_v2|clangd] (default: skim_v2)
     --case [respect,ignore,smart] (default: smart)
                          case sensitive or not
+    --query-case [as-is,lower,upper] (default: as-is)
+                         fold the case of the query, not the items
+    --single-thread      Match and sort the items in one thread
+    --lazy-tail          Show the first screen of matches before the rest
+                         of the items are matched
+    --max-query-length=N Only match the first N chars of the query
+    --match-budget=MS    Show the matches found in MS milliseconds, then
+                         match the rest
 
   Interface
     -b, --bind KEYBINDS  comma seperated keybindings, in KEY:ACTION
                          such as 'ctrl-j:accept,ctrl-k:kill-line'
     -m, --multi          Enable Multiple Selection
     --no-multi           Disable Multiple Selection
+    --select-all-on-start
+                         Select all the initially matched items (implies -m)
+    --output-select-order
+                         Output the selections in the order they are selected
+    --no-retain-filtered-selection
+                         Deselect the items no longer matched by the query
     --no-mouse           Disable mouse events
+    --mouse-scroll-lines=N[,M]
+                         Scroll the list by N (the preview by M) rows per
+                         wheel tick (default: 1)
     -c, --cmd ag         command to invoke dynamically
+    --no-expand          Do not expand `~` and $VAR in commands before
+                         invoking them (preview, execute, cmd)
+    --restrict-env=VARS  Pass only the env vars VARS (comma separated) to
+                         the preview and execute commands
     -I replstr           replace `replstr` with the selected item
     -i, --interactive    Start skim in interactive(command) mode
+    --show-cmd           Show the command run for the query in interactive
+                         mode
     --color [BASE][,COLOR:ANSI]
                          change color theme
+    --color-scheme=SCHEME
+                         Base colors for the background: [dark|light|custom]
     --no-hscroll         Disable horizontal scroll
+    --show-trailing-whitespace
+                         Highlight the trailing whitespace of items
+    --line-numbers       Show the line number of items in the input
+    --pin-top            Keep the best match on the first row when scrolled
+    --follow             Follow the best matches while the cursor is on the top
+    --scrollbar-heatmap  Draw a scrollbar colored by where the best matches are
 
   Layout
     --layout=LAYOUT      Choose layout: [default|reverse|reverse-list]
+    --display-direction=DIR
+                         Draw the first item [from-prompt|toward-prompt]
+    --grid               Pack the items into columns (for short items)
+    --max-rendered-rows=N
+                         Draw at most N rows of items (for slow terminals)
+    --startup-delay=MS   Draw the UI only after MS milliseconds (default: 0)
     --height=HEIGHT      Height of skim's window (--height 40%)
     --no-height          Disable height feature
     --min-height=HEIGHT  Minimum height when --height is given by percent
@@ -61,12 +103,14 @@ Usage: sk [options]
     --margin=MARGIN      Screen Margin (TRBL / TB,RL / T,RL,B / T,R,B,L)
                          e.g. (sk --margin 1,10%)
     -p, --prompt '> '    prompt string for query mode
-    --cmd-prompt '> '    prompt string for command mode
+    --cmd-prompt 'c> '   prompt string for command mode
+    --placeholder=STR    Display STR (dimmed) after the prompt while the query is empty
 
   Display
     --ansi               parse ANSI color codes for input strings
     --tabstop=SPACES     Number of spaces for a tab character (default: 8)
     --inline-info        Display info next to query
+    --show-query-debug   Display how the query is parsed in the info
     --header=STR         Display STR next to info
     --header-lines=N     The first N lines of the input are treated as header
 
@@ -81,17 +125,46 @@ Usage: sk [options]
                          We can specify the fields. e.g. ({1}, {..3}, {0..})
     --preview-window=OPT Preview window layout (default: right:50%)
                          [up|down|left|right][:SIZE[%]][:hidden]
+    --preview-only       Show only the preview of the current item (full screen)
+    --preview-separator=CHAR
+                         Separate the list and the preview by a line of CHAR
+    --preview-cache-size=N
+                         Keep the outputs of the last N preview commands
+    --preview-stream     Show the output of the preview command as it is read
+    --preview-line-cmd   Preview the items by running their lines as the commands
+    --preview-inline=N   Show N lines of the preview under the current item
 
   Scripting
     -q, --query \"\"       specify the initial query
     --cmd-query \"\"       specify the initial query for interactive mode
     --expect KEYS        comma seperated keys that can be used to complete skim
+    --expect-exit-codes=KEY:CODE[,..]
+                         exit with CODE if completed by KEY
+    --confirm-accept=PROMPT
+                         ask for confirmation (y) before accepting
+    --auto-accept-single Accept once the query narrows the matches to one
     --read0              Read input delimited by ASCII NUL(\\0) characters
+    --crlf [strip,keep] (default: strip)
+                         strip the \\r before the \\n ending the lines or not
+    --trim               Strip the leading and trailing whitespace of items
+    --dedup              Show the items of the same text only once
+    --memory-budget=BYTES
+                         Keep the lines read beyond BYTES in a temp file
     --print0             Print output delimited by ASCII NUL(\\0) characters
+    --no-trailing-newline
+                         Do not end the last line of the output
+    --record-events=FILE Record the events (actions) of the input to FILE
+    --replay-events=FILE Replay the events recorded in FILE, then read the live ones
     --print-query        Print query as the first line
     --print-cmd          Print command query as the first line (after --print-query)
-    --print-score        Print matching score in filter output (with --filter)
+    --print-score        Print matching score before each output line
+    --print-score-after  Print the score after the line instead (with --print-score)
     -f, --filter=STR     Filter mode. Do not start interactive finder.
+    --filter-batch       Read queries from stdin (one per line) and filter
+                         the items of the command for each of them
+    --batch-delimiter=STR
+                         Line printed after the matches of each query
+                         (with --filter-batch, default: empty line)
 
   Environment variables
     SKIM_DEFAULT_COMMAND Default command to use when input is tty
@@ -141,10 +214,8 @@ fn real_main() -> Result<i32, std::io::Error> {
     let mut args = Vec::new();
 
     args.push(env::args().next().expect("there should be at least one arg: the application name"));
-    args.extend(env::var("SKIM_DEFAULT_OPTIONS")
-        .ok()
-        .and_then(|val| shlex::split(&val))
-        .unwrap_or_default());
+    // the explicit args override the default ones
+    args.extend(default_options_args());
     for arg in env::args().skip(1) {
         args.push(arg);
     }
@@ -159,34 +230,69 @@ fn real_main() -> Result<i32, std::io::Error> {
         .arg(Arg::with_name("bind").long("bind").short("b").multiple(true).takes_value(true))
         .arg(Arg::with_name("multi").long("multi").short("m").multiple(true))
         .arg(Arg::with_name("no-multi").long("no-multi").multiple(true))
+        .arg(Arg::with_name("select-all-on-start").long("select-all-on-start").multiple(true))
+        .arg(Arg::with_name("output-select-order").long("output-select-order").multiple(true))
+        .arg(Arg::with_name("no-retain-filtered-selection").long("no-retain-filtered-selection").multiple(true))
         .arg(Arg::with_name("prompt").long("prompt").short("p").multiple(true).takes_value(true).default_value("> "))
         .arg(Arg::with_name("cmd-prompt").long("cmd-prompt").multiple(true).takes_value(true).default_value("c> "))
+        .arg(Arg::with_name("placeholder").long("placeholder").multiple(true).takes_value(true))
         .arg(Arg::with_name("expect").long("expect").multiple(true).takes_value(true))
+        .arg(Arg::with_name("expect-exit-codes").long("expect-exit-codes").multiple(true).takes_value(true))
+        .arg(Arg::with_name("confirm-accept").long("confirm-accept").multiple(true).takes_value(true))
+        .arg(Arg::with_name("auto-accept-single").long("auto-accept-single").multiple(true))
+        .arg(Arg::with_name("record-events").long("record-events").multiple(true).takes_value(true))
+        .arg(Arg::with_name("replay-events").long("replay-events").multiple(true).takes_value(true))
         .arg(Arg::with_name("tac").long("tac").multiple(true))
         .arg(Arg::with_name("tiebreak").long("tiebreak").short("t").multiple(true).takes_value(true))
         .arg(Arg::with_name("ansi").long("ansi").multiple(true))
         .arg(Arg::with_name("exact").long("exact").short("e").multiple(true))
+        .arg(Arg::with_name("word-match").long("word-match").multiple(true))
         .arg(Arg::with_name("cmd").long("cmd").short("c").multiple(true).takes_value(true))
         .arg(Arg::with_name("interactive").long("interactive").short("i").multiple(true))
+        .arg(Arg::with_name("show-cmd").long("show-cmd").multiple(true))
         .arg(Arg::with_name("query").long("query").short("q").multiple(true).takes_value(true))
         .arg(Arg::with_name("cmd-query").long("cmd-query").multiple(true).takes_value(true))
         .arg(Arg::with_name("regex").long("regex").multiple(true))
         .arg(Arg::with_name("delimiter").long("delimiter").short("d").multiple(true).takes_value(true))
         .arg(Arg::with_name("nth").long("nth").short("n").multiple(true).takes_value(true))
         .arg(Arg::with_name("with-nth").long("with-nth").multiple(true).takes_value(true))
+        .arg(Arg::with_name("accept-nth").long("accept-nth").multiple(true).takes_value(true))
         .arg(Arg::with_name("replstr").short("I").multiple(true).takes_value(true))
         .arg(Arg::with_name("color").long("color").multiple(true).takes_value(true))
+        .arg(Arg::with_name("color-scheme").long("color-scheme").multiple(true).takes_value(true))
         .arg(Arg::with_name("margin").long("margin").multiple(true).takes_value(true).default_value("0,0,0,0"))
         .arg(Arg::with_name("min-height").long("min-height").multiple(true).takes_value(true).default_value("10"))
         .arg(Arg::with_name("height").long("height").multiple(true).takes_value(true).default_value("100%"))
         .arg(Arg::with_name("no-height").long("no-height").multiple(true))
         .arg(Arg::with_name("no-mouse").long("no-mouse").multiple(true))
+        .arg(Arg::with_name("mouse-scroll-lines").long("mouse-scroll-lines").multiple(true).takes_value(true))
+        .arg(Arg::with_name("no-expand").long("no-expand").multiple(true))
+        .arg(Arg::with_name("restrict-env").long("restrict-env").multiple(true).takes_value(true))
         .arg(Arg::with_name("preview").long("preview").multiple(true).takes_value(true))
         .arg(Arg::with_name("preview-window").long("preview-window").multiple(true).takes_value(true).default_value("right:50%"))
+        .arg(Arg::with_name("preview-only").long("preview-only").multiple(true))
+        .arg(Arg::with_name("preview-stream").long("preview-stream").multiple(true))
+        .arg(Arg::with_name("preview-line-cmd").long("preview-line-cmd").multiple(true))
+        .arg(Arg::with_name("preview-inline").long("preview-inline").multiple(true).takes_value(true))
+        .arg(Arg::with_name("preview-separator").long("preview-separator").multiple(true).takes_value(true))
+        .arg(Arg::with_name("preview-cache-size").long("preview-cache-size").multiple(true).takes_value(true).default_value("0"))
         .arg(Arg::with_name("reverse").long("reverse").multiple(true))
+        .arg(Arg::with_name("grid").long("grid").multiple(true))
+        .arg(Arg::with_name("show-trailing-whitespace").long("show-trailing-whitespace").multiple(true))
+        .arg(Arg::with_name("line-numbers").long("line-numbers").multiple(true))
+        .arg(Arg::with_name("pin-top").long("pin-top").multiple(true))
+        .arg(Arg::with_name("follow").long("follow").multiple(true))
+        .arg(Arg::with_name("scrollbar-heatmap").long("scrollbar-heatmap").multiple(true))
+        .arg(Arg::with_name("max-rendered-rows").long("max-rendered-rows").multiple(true).takes_value(true))
+        .arg(Arg::with_name("startup-delay").long("startup-delay").multiple(true).takes_value(true).default_value("0"))
 
         .arg(Arg::with_name("algorithm").long("algo").multiple(true).takes_value(true).default_value("skim_v2"))
         .arg(Arg::with_name("case").long("case").multiple(true).takes_value(true).default_value("smart"))
+        .arg(Arg::with_name("query-case").long("query-case").multiple(true).takes_value(true).default_value("as-is"))
+        .arg(Arg::with_name("single-thread").long("single-thread").multiple(true))
+        .arg(Arg::with_name("lazy-tail").long("lazy-tail").multiple(true))
+        .arg(Arg::with_name("max-query-length").long("max-query-length").multiple(true).takes_value(true))
+        .arg(Arg::with_name("match-budget").long("match-budget").multiple(true).takes_value(true))
         .arg(Arg::with_name("literal").long("literal").multiple(true))
         .arg(Arg::with_name("cycle").long("cycle").multiple(true))
         .arg(Arg::with_name("no-hscroll").long("no-hscroll").multiple(true))
@@ -195,8 +301,11 @@ fn real_main() -> Result<i32, std::io::Error> {
         .arg(Arg::with_name("jump-labels").long("jump-labels").multiple(true).takes_value(true).default_value("abcdefghijklmnopqrstuvwxyz"))
         .arg(Arg::with_name("border").long("border").multiple(true))
         .arg(Arg::with_name("inline-info").long("inline-info").multiple(true))
+        .arg(Arg::with_name("show-query-debug").long("show-query-debug").multiple(true))
         .arg(Arg::with_name("header").long("header").multiple(true).takes_value(true).default_value(""))
         .arg(Arg::with_name("header-lines").long("header-lines").multiple(true).takes_value(true).default_value("0"))
+        .arg(Arg::with_name("match-prefix-chars").long("match-prefix-chars").multiple(true).takes_value(true))
+        .arg(Arg::with_name("match-skip").long("match-skip").multiple(true).takes_value(true))
         .arg(Arg::with_name("tabstop").long("tabstop").multiple(true).takes_value(true).default_value("8"))
         .arg(Arg::with_name("no-bold").long("no-bold").multiple(true))
         .arg(Arg::with_name("history").long("history").multiple(true).takes_value(true))
@@ -206,8 +315,16 @@ fn real_main() -> Result<i32, std::io::Error> {
         .arg(Arg::with_name("print-query").long("print-query").multiple(true))
         .arg(Arg::with_name("print-cmd").long("print-cmd").multiple(true))
         .arg(Arg::with_name("print-score").long("print-score").multiple(true))
+        .arg(Arg::with_name("filter-batch").long("filter-batch").multiple(true))
+        .arg(Arg::with_name("batch-delimiter").long("batch-delimiter").multiple(true).takes_value(true))
+        .arg(Arg::with_name("print-score-after").long("print-score-after").multiple(true))
         .arg(Arg::with_name("read0").long("read0").multiple(true))
+        .arg(Arg::with_name("crlf").long("crlf").multiple(true).takes_value(true).default_value("strip"))
+        .arg(Arg::with_name("trim").long("trim").multiple(true))
+        .arg(Arg::with_name("dedup").long("dedup").multiple(true))
+        .arg(Arg::with_name("memory-budget").long("memory-budget").multiple(true).takes_value(true))
         .arg(Arg::with_name("print0").long("print0").multiple(true))
+        .arg(Arg::with_name("no-trailing-newline").long("no-trailing-newline").multiple(true))
         .arg(Arg::with_name("sync").long("sync").multiple(true))
         .arg(Arg::with_name("extended").long("extended").short("x").multiple(true))
         .arg(Arg::with_name("no-sort").long("no-sort").multiple(true))
@@ -215,6 +332,7 @@ fn real_main() -> Result<i32, std::io::Error> {
         .arg(Arg::with_name("exit-0").long("exit-0").short("0").multiple(true))
         .arg(Arg::with_name("filter").long("filter").short("f").takes_value(true).multiple(true))
         .arg(Arg::with_name("layout").long("layout").multiple(true).takes_value(true).default_value("default"))
+        .arg(Arg::with_name("display-direction").long("display-direction").multiple(true).takes_value(true))
         .get_matches_from(args);
 
     if opts.is_present("help") {
@@ -248,6 +366,13 @@ fn real_main() -> Result<i32, std::io::Error> {
 
     let options = options;
 
+    //------------------------------------------------------------------------------
+    // batch filter mode: the queries are read from stdin, so the items are read from command
+    if opts.is_present("filter-batch") {
+        let delimiter = opts.values_of("batch-delimiter").and_then(|mut vals| vals.next_back()).unwrap_or("");
+        return filter_batch(&options, delimiter);
+    }
+
     //------------------------------------------------------------------------------
     // read from pipe or command
     let stdin = std::io::stdin();
@@ -270,7 +395,13 @@ fn real_main() -> Result<i32, std::io::Error> {
     //------------------------------------------------------------------------------
     let output_ending = if options.print0 { "\0" } else { "\n" };
 
-    let output = Skim::run_with(&options, rx_item);
+    let output = match Skim::try_run_with(&options, rx_item) {
+        Ok(output) => output,
+        Err(err) => {
+            eprintln!("sk: {}", err);
+            return Ok(2);
+        }
+    };
     if output.is_none() {
         return Ok(130);
     }
@@ -278,22 +409,36 @@ fn real_main() -> Result<i32, std::io::Error> {
     //------------------------------------------------------------------------------
     // output
     let output = output.unwrap();
+    let mut writer = OutputWriter::new(&mut stdout, output_ending, !options.no_trailing_newline);
 
     // output query
     if options.print_query {
-        write!(stdout, "{}{}", output.query, output_ending)?;
+        writer.write_line(&output.query)?;
     }
 
     if options.print_cmd {
-        write!(stdout, "{}{}", output.cmd, output_ending)?;
+        writer.write_line(&output.cmd)?;
     }
 
-    if let Some(key) = output.accept_key {
-        write!(stdout, "{}{}", key, output_ending)?;
+    if let Some(ref key) = output.accept_key {
+        writer.write_line(key)?;
     }
 
-    for item in output.selected_items.iter() {
-        write!(stdout, "{}{}", item.output(), output_ending)?;
+    for (item, score) in output.selected_items.iter().zip(output.selected_scores.iter()) {
+        if options.print_score {
+            let score = score.map(|s| s.to_string()).unwrap_or_default();
+            writer.write_line(&with_score(&item.accepted_output(), &score, &options))?;
+        } else {
+            writer.write_line(&item.accepted_output())?;
+        }
+    }
+
+    let exit_codes = opts.values_of("expect-exit-codes").and_then(|mut vals| vals.next_back()).unwrap_or("");
+    let exit_code = exit_code_of_output(&output, exit_codes);
+
+    // aborted, though the current item is printed
+    if output.is_abort {
+        return Ok(exit_code);
     }
 
     //------------------------------------------------------------------------------
@@ -312,30 +457,107 @@ fn real_main() -> Result<i32, std::io::Error> {
         write_history_to_file(&cmd_history, &output.cmd, limit, file)?;
     }
 
-    Ok(if output.selected_items.is_empty() { 1 } else { 0 })
+    Ok(exit_code)
+}
+
+/// 130 if aborted, else the exit code given to the accept key in `exit_codes` (see
+/// `--expect-exit-codes`), else 0 if some items are accepted, 1 if none
+fn exit_code_of_output(output: &SkimOutput, exit_codes: &str) -> i32 {
+    if output.is_abort {
+        return 130;
+    }
+
+    output
+        .accept_key
+        .as_ref()
+        .and_then(|key| exit_code_of_key(exit_codes, key))
+        .unwrap_or(if output.selected_items.is_empty() { 1 } else { 0 })
+}
+
+/// the exit code of `key` in `exit_codes`, e.g. `ctrl-d:2,ctrl-e:3`, the malformed ones are ignored
+fn exit_code_of_key(exit_codes: &str, key: &str) -> Option<i32> {
+    exit_codes
+        .split(',')
+        .filter_map(|key_code| {
+            let mut parts = key_code.rsplitn(2, ':');
+            let code = parts.next()?.trim().parse::<i32>().ok()?;
+            Some((parts.next()?.trim(), code))
+        })
+        .find(|&(expected, _)| expected == key)
+        .map(|(_, code)| code)
 }
 
 fn parse_options<'a>(options: &'a ArgMatches) -> SkimOptions<'a> {
     SkimOptionsBuilder::default()
         .color(options.values_of("color").and_then(|vals| vals.last()))
+        .color_scheme(
+            options
+                .values_of("color-scheme")
+                .and_then(|mut vals| vals.next_back())
+                .map(ColorScheme::of)
+                .unwrap_or_default(),
+        )
         .min_height(options.values_of("min-height").and_then(|vals| vals.last()))
         .no_height(options.is_present("no-height"))
         .height(options.values_of("height").and_then(|vals| vals.last()))
         .margin(options.values_of("margin").and_then(|vals| vals.last()))
         .preview(options.values_of("preview").and_then(|vals| vals.last()))
         .preview_window(options.values_of("preview-window").and_then(|vals| vals.last()))
+        .preview_only(options.is_present("preview-only"))
+        .preview_stream(options.is_present("preview-stream"))
+        .preview_line_cmd(options.is_present("preview-line-cmd"))
+        .preview_inline(
+            options
+                .values_of("preview-inline")
+                .and_then(|mut vals| vals.next_back())
+                .and_then(|s| s.parse::<usize>().ok()),
+        )
+        .preview_separator(
+            options
+                .values_of("preview-separator")
+                .and_then(|mut vals| vals.next_back()),
+        )
+        .preview_cache_size(
+            options
+                .values_of("preview-cache-size")
+                .and_then(|mut vals| vals.next_back())
+                .map(|s| s.parse::<usize>().unwrap_or(0))
+                .unwrap_or(0),
+        )
         .cmd(options.values_of("cmd").and_then(|vals| vals.last()))
         .query(options.values_of("query").and_then(|vals| vals.last()))
         .cmd_query(options.values_of("cmd-query").and_then(|vals| vals.last()))
         .replstr(options.values_of("replstr").and_then(|vals| vals.last()))
         .interactive(options.is_present("interactive"))
+        .show_cmd(options.is_present("show-cmd"))
         .prompt(options.values_of("prompt").and_then(|vals| vals.last()))
+        .placeholder(options.values_of("placeholder").and_then(|mut vals| vals.next_back()))
         .cmd_prompt(options.values_of("cmd-prompt").and_then(|vals| vals.last()))
         .ansi(options.is_present("ansi"))
         .delimiter(options.values_of("delimiter").and_then(|vals| vals.last()))
         .with_nth(options.values_of("with-nth").and_then(|vals| vals.last()))
+        .accept_nth(options.values_of("accept-nth").and_then(|mut vals| vals.next_back()))
         .nth(options.values_of("nth").and_then(|vals| vals.last()))
+        .match_prefix_chars(
+            options
+                .values_of("match-prefix-chars")
+                .and_then(|mut vals| vals.next_back())
+                .and_then(|s| s.parse::<usize>().ok()),
+        )
+        .match_skip(options.values_of("match-skip").and_then(|mut vals| vals.next_back()))
         .read0(options.is_present("read0"))
+        .crlf(match options.values_of("crlf").and_then(|mut vals| vals.next_back()) {
+            Some("keep") => CrLf::Keep,
+            _ => CrLf::Strip,
+        })
+        .trim(options.is_present("trim"))
+        .dedup(options.is_present("dedup"))
+        .memory_budget_bytes(
+            options
+                .values_of("memory-budget")
+                .and_then(|mut vals| vals.next_back())
+                .and_then(|s| s.parse::<usize>().ok()),
+        )
         .bind(
             options
                 .values_of("bind")
@@ -343,25 +565,76 @@ fn parse_options<'a>(options: &'a ArgMatches) -> SkimOptions<'a> {
                 .unwrap_or_default(),
         )
         .expect(options.values_of("expect").map(|x| x.collect::<Vec<_>>().join(",")))
+        .confirm_accept(
+            options
+                .values_of("confirm-accept")
+                .and_then(|mut vals| vals.next_back()),
+        )
+        .auto_accept_single(options.is_present("auto-accept-single"))
+        .record_events_file(options.values_of("record-events").and_then(|mut vals| vals.next_back()))
+        .replay_events_file(options.values_of("replay-events").and_then(|mut vals| vals.next_back()))
         .multi(if options.is_present("no-multi") {
             false
         } else {
             options.is_present("multi")
         })
+        .select_all_on_start(options.is_present("select-all-on-start"))
+        .output_select_order(options.is_present("output-select-order"))
+        .retain_filtered_selection(!options.is_present("no-retain-filtered-selection"))
         .layout(options.values_of("layout").and_then(|vals| vals.last()).unwrap_or(""))
+        .display_direction(
+            options
+                .values_of("display-direction")
+                .and_then(|mut vals| vals.next_back()),
+        )
         .reverse(options.is_present("reverse"))
+        .grid(options.is_present("grid"))
+        .show_trailing_whitespace(options.is_present("show-trailing-whitespace"))
+        .line_numbers(options.is_present("line-numbers"))
+        .pin_top(options.is_present("pin-top"))
+        .follow(options.is_present("follow"))
+        .scrollbar_heatmap(options.is_present("scrollbar-heatmap"))
+        .max_rendered_rows(
+            options
+                .values_of("max-rendered-rows")
+                .and_then(|mut vals| vals.next_back())
+                .and_then(|s| s.parse::<usize>().ok()),
+        )
+        .startup_delay_ms(
+            options
+                .values_of("startup-delay")
+                .and_then(|mut vals| vals.next_back())
+                .map(|s| s.parse::<u64>().unwrap_or(0))
+                .unwrap_or(0),
+        )
         .print0(options.is_present("print0"))
+        .no_trailing_newline(options.is_present("no-trailing-newline"))
         .print_query(options.is_present("print-query"))
         .print_cmd(options.is_present("print-cmd"))
         .print_score(options.is_present("print-score"))
+        .print_score_after(options.is_present("print-score-after"))
         .no_hscroll(options.is_present("no-hscroll"))
         .no_mouse(options.is_present("no-mouse"))
+        .mouse_scroll_lines(
+            options
+                .values_of("mouse-scroll-lines")
+                .and_then(|mut vals| vals.next_back()),
+        )
+        .no_expand(options.is_present("no-expand"))
+        .restrict_env(
+            options
+                .values_of("restrict-env")
+                .and_then(|mut vals| vals.next_back())
+                .map(|vars| vars.split(',').filter(|var| !var.is_empty()).collect()),
+        )
         .tabstop(options.values_of("tabstop").and_then(|vals| vals.last()))
         .tiebreak(options.values_of("tiebreak").map(|x| x.collect::<Vec<_>>().join(",")))
         .tac(options.is_present("tac"))
         .exact(options.is_present("exact"))
+        .word_match(options.is_present("word-match"))
         .regex(options.is_present("regex"))
         .inline_info(options.is_present("inline-info"))
+        .show_query_debug(options.is_present("show-query-debug"))
         .header(options.values_of("header").and_then(|vals| vals.last()))
         .header_lines(
             options
@@ -380,6 +653,27 @@ fn parse_options<'a>(options: &'a ArgMatches) -> SkimOptions<'a> {
             Some("ignore") => CaseMatching::Ignore,
             _ => CaseMatching::Respect,
         })
+        .query_case(
+            match options.values_of("query-case").and_then(|mut vals| vals.next_back()) {
+                Some("lower") => QueryCase::Lower,
+                Some("upper") => QueryCase::Upper,
+                _ => QueryCase::AsIs,
+            },
+        )
+        .single_thread(options.is_present("single-thread"))
+        .lazy_tail(options.is_present("lazy-tail"))
+        .max_query_length(
+            options
+                .values_of("max-query-length")
+                .and_then(|mut vals| vals.next_back())
+                .and_then(|s| s.parse::<usize>().ok()),
+        )
+        .match_budget_ms(
+            options
+                .values_of("match-budget")
+                .and_then(|mut vals| vals.next_back())
+                .and_then(|s| s.parse::<u64>().ok()),
+        )
         .build()
         .unwrap()
 }
@@ -417,38 +711,58 @@ fn write_history_to_file(
     Ok(())
 }
 
+/// write the item with its score, separated by a tab
+fn with_score(output: &str, score: &str, options: &SkimOptions) -> String {
+    if options.print_score_after {
+        format!("{}\t{}", output, score)
+    } else {
+        format!("{}\t{}", score, output)
+    }
+}
+
+fn get_default_command() -> String {
+    match env::var("SKIM_DEFAULT_COMMAND").as_ref().map(String::as_ref) {
+        Ok("") | Err(_) => "find .".to_owned(),
+        Ok(val) => val.to_owned(),
+    }
+}
+
+fn filter_engine_factory(options: &SkimOptions) -> Box<dyn MatchEngineFactory> {
+    if options.regex {
+        Box::new(RegexEngineFactory::new())
+    } else {
+        let fuzzy_engine_factory = ExactOrFuzzyEngineFactory::builder()
+            .fuzzy_algorithm(options.algorithm)
+            .exact_mode(options.exact)
+            .word_match(options.word_match)
+            .build();
+        Box::new(AndOrEngineFactory::new(fuzzy_engine_factory))
+    }
+}
+
 pub fn filter(options: &SkimOptions, source: Option<SkimItemReceiver>) -> Result<i32, std::io::Error> {
-    let mut stdout = std::io::stdout();
+    let stdout = std::io::stdout();
 
     let output_ending = if options.print0 { "\0" } else { "\n" };
+    let mut writer = OutputWriter::new(stdout.lock(), output_ending, !options.no_trailing_newline);
     let query = options.filter;
-    let default_command = match env::var("SKIM_DEFAULT_COMMAND").as_ref().map(String::as_ref) {
-        Ok("") | Err(_) => "find .".to_owned(),
-        Ok(val) => val.to_owned(),
-    };
+    let default_command = get_default_command();
     let cmd = options.cmd.unwrap_or(&default_command);
 
     // output query
     if options.print_query {
-        write!(stdout, "{}{}", query, output_ending)?;
+        writer.write_line(query)?;
     }
 
     if options.print_cmd {
-        write!(stdout, "{}{}", cmd, output_ending)?;
+        writer.write_line(cmd)?;
     }
 
     //------------------------------------------------------------------------------
     // matcher
-    let engine_factory: Box<dyn MatchEngineFactory> = if options.regex {
-        Box::new(RegexEngineFactory::new())
-    } else {
-        let fuzzy_engine_factory = ExactOrFuzzyEngineFactory::builder()
-            .fuzzy_algorithm(options.algorithm)
-            .exact_mode(options.exact)
-            .build();
-        Box::new(AndOrEngineFactory::new(fuzzy_engine_factory))
-    };
-
+    let engine_factory = filter_engine_factory(options);
+    let query = options.query_case.apply(query);
+    let query = truncate_query(&query, options.max_query_length);
     let engine = engine_factory.create_engine_with_case(query, options.case);
 
     //------------------------------------------------------------------------------
@@ -471,17 +785,118 @@ pub fn filter(options: &SkimOptions, source: Option<SkimItemReceiver>) -> Result
         .try_for_each(|matched| {
             num_matched += 1;
             if options.print_score {
-                write!(
-                    stdout,
-                    "{}\t{}{}",
-                    -matched.rank.score,
-                    matched.item.output(),
-                    output_ending
-                )
+                let score = (-matched.rank.score).to_string();
+                writer.write_line(&with_score(&matched.item.accepted_output(), &score, options))
             } else {
-                write!(stdout, "{}{}", matched.item.output(), output_ending)
+                writer.write_line(&matched.item.accepted_output())
             }
         })?;
 
     Ok(if num_matched == 0 { 1 } else { 0 })
 }
+
+/// Batch filter mode: load the items of the command once, then for each query read from stdin,
+/// print the matched items (best first) followed by a line of `delimiter`.
+pub fn filter_batch(options: &SkimOptions, delimiter: &str) -> Result<i32, std::io::Error> {
+    let stdout = std::io::stdout();
+
+    let output_ending = if options.print0 { "\0" } else { "\n" };
+    let mut writer = OutputWriter::new(stdout.lock(), output_ending, !options.no_trailing_newline);
+    let default_command = get_default_command();
+    let cmd = options.cmd.unwrap_or(&default_command);
+    let engine_factory = filter_engine_factory(options);
+    let compare = compare_matched_items(options);
+
+    //------------------------------------------------------------------------------
+    // load the items
+    let components_to_stop = Arc::new(AtomicUsize::new(0));
+    let collector_option = CollectorOption::with_options(options);
+    let collector_input = CollectorInput::Command(cmd.to_string());
+    let (stream_of_item, _control) =
+        read_and_collect_from_command(components_to_stop, collector_input, collector_option);
+    let items: Vec<Arc<ItemWrapper>> = stream_of_item
+        .into_iter()
+        .enumerate()
+        .map(|(idx, item)| Arc::new(ItemWrapper::new(item, (0, idx as u32))))
+        .collect();
+
+    //------------------------------------------------------------------------------
+    // serve the queries
+    let stdin = std::io::stdin();
+    for query in stdin.lock().lines() {
+        let query = query?;
+        let query = options.query_case.apply(&query);
+        let query = truncate_query(&query, options.max_query_length);
+        let engine = engine_factory.create_engine_with_case(query, options.case);
+        let mut matched_items: Vec<_> = items
+            .iter()
+            .filter_map(|item| engine.match_item(item.clone()))
+            .collect();
+        matched_items.sort_by(|a, b| compare(a, b));
+
+        if options.print_query {
+            writer.write_line(query)?;
+        }
+
+        for matched in matched_items.iter() {
+            if options.print_score {
+                let score = (-matched.rank.score).to_string();
+                writer.write_line(&with_score(&matched.item.accepted_output(), &score, options))?;
+            } else {
+                writer.write_line(&matched.item.accepted_output())?;
+            }
+        }
+
+        writer.write_line(delimiter)?;
+        writer.flush()?;
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn output(accept_key: Option<&str>, is_abort: bool) -> SkimOutput {
+        SkimOutput {
+            accept_key: accept_key.map(String::from),
+            query: String::new(),
+            cmd: String::new(),
+            selected_items: vec![Arc::new("item")],
+            selected_scores: vec![None],
+            matched_items: Vec::new(),
+            selected_outputs: Vec::new(),
+            is_abort,
+            deleted_items: Vec::new(),
+            item_action: None,
+        }
+    }
+
+    #[test]
+    fn test_exit_code_of_key() {
+        assert_eq!(exit_code_of_key("ctrl-d:2,ctrl-e:3", "ctrl-e"), Some(3));
+        assert_eq!(exit_code_of_key("ctrl-d:2,ctrl-e:3", "ctrl-x"), None);
+        // the malformed ones are ignored
+        assert_eq!(exit_code_of_key("ctrl-d:x,ctrl-e,ctrl-x:4", "ctrl-x"), Some(4));
+        assert_eq!(exit_code_of_key("ctrl-d:x", "ctrl-d"), None);
+        assert_eq!(exit_code_of_key("", "ctrl-d"), None);
+    }
+
+    #[test]
+    fn test_exit_code_of_output() {
+        let exit_codes = "ctrl-d:2,ctrl-e:3";
+        assert_eq!(exit_code_of_output(&output(Some("ctrl-d"), false), exit_codes), 2);
+
+        // unmapped, the default codes
+        assert_eq!(exit_code_of_output(&output(Some("ctrl-x"), false), exit_codes), 0);
+        assert_eq!(exit_code_of_output(&output(None, false), exit_codes), 0);
+        let mut nothing_accepted = output(None, false);
+        nothing_accepted.selected_items.clear();
+        assert_eq!(exit_code_of_output(&nothing_accepted, exit_codes), 1);
+
+        // aborted, even by a mapped key
+        assert_eq!(exit_code_of_output(&output(None, true), exit_codes), 130);
+        assert_eq!(exit_code_of_output(&output(Some("ctrl-d"), true), exit_codes), 130);
+    }
+}
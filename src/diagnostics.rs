@@ -0,0 +1,112 @@
+//! Route the internal diagnostics of skim to `SkimOptions::diagnostics` if it is set, otherwise
+//! to the `log` crate.
+//!
+//! The callback is kept per thread, and the threads skim spawns (by `spawn`) inherit the one of
+//! their parent, so that the runs of skim on different threads get their own diagnostics.
+use std::cell::{Cell, RefCell};
+use std::fmt::Arguments;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Once};
+use std::thread::{self, JoinHandle};
+
+use log::Level;
+
+/// receive the messages that skim emits, together with their levels
+pub type DiagnosticsCallback = Arc<dyn Fn(Level, &str) + Send + Sync>;
+
+thread_local! {
+    static SINK: RefCell<Option<DiagnosticsCallback>> = RefCell::new(None);
+    static QUIET_PANIC: Cell<bool> = const { Cell::new(false) };
+}
+
+/// replace the callback that receives the diagnostics of the current thread, return the previous one
+pub fn set_sink(callback: Option<DiagnosticsCallback>) -> Option<DiagnosticsCallback> {
+    SINK.with(|sink| sink.replace(callback))
+}
+
+/// `thread::spawn`, the thread emits to the callback of the current thread
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let callback = SINK.with(|sink| sink.borrow().clone());
+    thread::spawn(move || {
+        set_sink(callback);
+        f()
+    })
+}
+
+pub fn emit(level: Level, target: &str, args: Arguments) {
+    let callback = SINK.with(|sink| sink.borrow().clone());
+    match callback {
+        Some(callback) => callback(level, &args.to_string()),
+        None => log::log!(target: target, level, "{}", args),
+    }
+}
+
+/// `panic::catch_unwind`, the message of a panic is emitted as a warning instead of printed by the
+/// panic hook (i.e. to stderr, over the UI)
+pub fn catch_unwind_quietly<F: FnOnce() -> R, R>(f: F) -> thread::Result<R> {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if QUIET_PANIC.with(Cell::get) {
+                emit(Level::Warn, module_path!(), format_args!("{}", info));
+            } else {
+                hook(info);
+            }
+        }));
+    });
+
+    let quiet = QUIET_PANIC.with(|quiet| quiet.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    QUIET_PANIC.with(|q| q.set(quiet));
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_emit_to_callback() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let messages_clone = messages.clone();
+        let prev = set_sink(Some(Arc::new(move |level, msg: &str| {
+            messages_clone.lock().unwrap().push((level, msg.to_string()))
+        })));
+
+        debug!("diagnostics: {}", 42);
+        set_sink(prev);
+        debug!("diagnostics: not captured");
+
+        let messages = messages.lock().unwrap();
+        assert!(messages.contains(&(Level::Debug, "diagnostics: 42".to_string())));
+        assert!(!messages.iter().any(|(_, msg)| msg == "diagnostics: not captured"));
+    }
+
+    #[test]
+    fn test_sink_per_thread() {
+        let capture = |name: &'static str| {
+            thread::spawn(move || {
+                let messages = Arc::new(Mutex::new(Vec::new()));
+                let messages_clone = messages.clone();
+                set_sink(Some(Arc::new(move |_, msg: &str| {
+                    messages_clone.lock().unwrap().push(msg.to_string())
+                })));
+                debug!("{}", name);
+                // emitted by a thread of the run
+                spawn(move || debug!("{} in a spawned thread", name)).join().unwrap();
+                let messages = messages.lock().unwrap().clone();
+                messages
+            })
+        };
+
+        let (first, second) = (capture("first"), capture("second"));
+        assert_eq!(first.join().unwrap(), vec!["first", "first in a spawned thread"]);
+        assert_eq!(second.join().unwrap(), vec!["second", "second in a spawned thread"]);
+    }
+}
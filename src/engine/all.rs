@@ -2,7 +2,7 @@ use std::fmt::{Display, Error, Formatter};
 use std::sync::Arc;
 
 use crate::item::{ItemWrapper, MatchedItem, MatchedRange, Rank};
-use crate::MatchEngine;
+use crate::{MatchEngine, SkimItem};
 
 //------------------------------------------------------------------------------
 #[derive(Debug)]
@@ -25,6 +25,7 @@ impl MatchEngine for MatchAllEngine {
             index: item.get_index() as i64,
             begin: 0,
             end: 0,
+            external: item.external_rank(),
         };
 
         Some(
@@ -0,0 +1,119 @@
+//! Match the ANSI-stripped display of the items instead of their text
+use std::borrow::Cow;
+use std::fmt::{Display, Error, Formatter};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::item::{ItemWrapper, MatchedItem};
+use crate::{AnsiString, CaseMatching, MatchEngine, MatchEngineFactory, SkimItem};
+
+//------------------------------------------------------------------------------
+// Display engine factory
+pub struct DisplayEngineFactory {
+    inner: Rc<dyn MatchEngineFactory>,
+}
+
+impl DisplayEngineFactory {
+    pub fn new(inner: Rc<dyn MatchEngineFactory>) -> Self {
+        Self { inner }
+    }
+}
+
+impl MatchEngineFactory for DisplayEngineFactory {
+    fn create_engine_with_case(&self, query: &str, case: CaseMatching) -> Box<dyn MatchEngine> {
+        Box::new(DisplayEngine {
+            inner: self.inner.create_engine_with_case(query, case),
+        })
+    }
+}
+
+//------------------------------------------------------------------------------
+pub struct DisplayEngine {
+    inner: Box<dyn MatchEngine>,
+}
+
+impl MatchEngine for DisplayEngine {
+    fn match_item(&self, item: Arc<ItemWrapper>) -> Option<MatchedItem> {
+        let display = item.display().stripped().to_string();
+
+        // the matching ranges of the item are of its text, the whole display is matched instead
+        let display_item = DisplayItem {
+            matching_ranges: vec![(0, display.len())],
+            text: display,
+            external_rank: item.external_rank(),
+        };
+
+        let matched = self
+            .inner
+            .match_item(Arc::new(ItemWrapper::new(Arc::new(display_item), item.get_id())))?;
+
+        // the matched range is of the display, so is the highlighting (`SkimOptions::match_display`)
+        let builder = MatchedItem::builder(item.clone()).rank(matched.rank);
+        Some(match matched.matched_range {
+            Some(range) => builder.matched_range(range).build(),
+            None => builder.build(),
+        })
+    }
+}
+
+impl Display for DisplayEngine {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "(Display: {})", self.inner)
+    }
+}
+
+struct DisplayItem {
+    text: String,
+    matching_ranges: Vec<(usize, usize)>,
+    external_rank: i64,
+}
+
+impl SkimItem for DisplayItem {
+    fn display(&self) -> Cow<'_, AnsiString<'_>> {
+        Cow::Owned(self.text.as_str().into())
+    }
+
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.text)
+    }
+
+    fn get_matching_ranges(&self) -> Cow<'_, [(usize, usize)]> {
+        Cow::Borrowed(&self.matching_ranges)
+    }
+
+    fn external_rank(&self) -> i64 {
+        self.external_rank
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::factory::ExactOrFuzzyEngineFactory;
+    use crate::MatchedRange;
+
+    struct LabeledItem;
+
+    impl SkimItem for LabeledItem {
+        fn display(&self) -> Cow<'_, AnsiString<'_>> {
+            Cow::Owned(AnsiString::parse("\x1b[31m[lib]\x1b[0m src/lib.rs"))
+        }
+
+        fn text(&self) -> Cow<'_, str> {
+            Cow::Borrowed("src/lib.rs")
+        }
+    }
+
+    #[test]
+    fn test_display_engine() {
+        let factory = DisplayEngineFactory::new(Rc::new(ExactOrFuzzyEngineFactory::builder().build()));
+        let item = Arc::new(ItemWrapper::new(Arc::new(LabeledItem), (0, 0)));
+
+        // the label is only in the display
+        let matched = factory.create_engine("'[lib]").match_item(item.clone()).unwrap();
+        assert_eq!(matched.matched_range, Some(MatchedRange::ByteRange(0, 5)));
+        assert_eq!(&*matched.item.text(), "src/lib.rs");
+
+        assert!(factory.create_engine("'src/main").match_item(item).is_none());
+    }
+}
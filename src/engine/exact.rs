@@ -1,4 +1,4 @@
-use crate::engine::util::{contains_upper, regex_match};
+use crate::engine::util::{contains_upper, expand_bytes_to_graphemes, regex_match, regex_match_word};
 use crate::item::{ItemWrapper, MatchedItem, MatchedRange, Rank};
 use crate::SkimItem;
 use crate::{CaseMatching, MatchEngine};
@@ -14,6 +14,8 @@ pub struct ExactMatchingParam {
     pub postfix: bool,
     pub inverse: bool,
     pub case: CaseMatching,
+    /// only match the whole words, see `util::is_whole_word`
+    pub word_match: bool,
     __non_exhaustive: bool,
 }
 
@@ -22,6 +24,7 @@ pub struct ExactEngine {
     query: String,
     query_regex: Option<Regex>,
     inverse: bool,
+    word_match: bool,
 }
 
 impl ExactEngine {
@@ -57,6 +60,7 @@ impl ExactEngine {
             query: query.to_string(),
             query_regex,
             inverse: param.inverse,
+            word_match: param.word_match,
         }
     }
 
@@ -74,8 +78,14 @@ impl MatchEngine for ExactEngine {
                 break;
             }
 
-            matched_result =
-                regex_match(&item.text()[start..end], &self.query_regex).map(|(s, e)| (s + start, e + start));
+            let text = &item.text()[start..end];
+            matched_result = if self.word_match {
+                regex_match_word(text, &self.query_regex)
+            } else {
+                regex_match(text, &self.query_regex)
+            }
+            .map(|(s, e)| expand_bytes_to_graphemes(text, s, e))
+            .map(|(s, e)| (s + start, e + start));
 
             if self.inverse {
                 matched_result = matched_result.xor(Some((0, 0)))
@@ -93,6 +103,7 @@ impl MatchEngine for ExactEngine {
             index: item.get_index() as i64,
             begin: begin as i64,
             end: end as i64,
+            external: item.external_rank(),
         };
 
         Some(
@@ -5,16 +5,23 @@ use crate::engine::fuzzy::{FuzzyAlgorithm, FuzzyEngine};
 use crate::engine::regexp::RegexEngine;
 use crate::{CaseMatching, MatchEngine, MatchEngineFactory};
 use regex::Regex;
+use std::borrow::Cow;
 
 lazy_static! {
     static ref RE_AND: Regex = Regex::new(r"([^ |]+( +\| +[^ |]*)+)|( +)").unwrap();
     static ref RE_OR: Regex = Regex::new(r" +\| +").unwrap();
 }
+
+// the spaces and `|` in double quotes are replaced before splitting the query, so that they are
+// kept in the term.
+const QUOTED_SPACE: char = '\u{1}';
+const QUOTED_BAR: char = '\u{2}';
 //------------------------------------------------------------------------------
 // Exact engine factory
 pub struct ExactOrFuzzyEngineFactory {
     exact_mode: bool,
     fuzzy_algorithm: FuzzyAlgorithm,
+    word_match: bool,
 }
 
 impl ExactOrFuzzyEngineFactory {
@@ -22,6 +29,7 @@ impl ExactOrFuzzyEngineFactory {
         Self {
             exact_mode: false,
             fuzzy_algorithm: FuzzyAlgorithm::SkimV2,
+            word_match: false,
         }
     }
 
@@ -35,6 +43,12 @@ impl ExactOrFuzzyEngineFactory {
         self
     }
 
+    /// only match the terms at the word boundaries, for both the exact and the fuzzy terms
+    pub fn word_match(mut self, word_match: bool) -> Self {
+        self.word_match = word_match;
+        self
+    }
+
     pub fn build(self) -> Self {
         self
     }
@@ -54,6 +68,7 @@ impl MatchEngineFactory for ExactOrFuzzyEngineFactory {
         let mut exact = false;
         let mut param = ExactMatchingParam::default();
         param.case = case;
+        param.word_match = self.word_match;
 
         if query.starts_with('\'') {
             if self.exact_mode {
@@ -62,6 +77,7 @@ impl MatchEngineFactory for ExactOrFuzzyEngineFactory {
                         .query(&query[1..])
                         .algorithm(self.fuzzy_algorithm)
                         .case(case)
+                        .word_match(self.word_match)
                         .build(),
                 );
             } else {
@@ -105,6 +121,7 @@ impl MatchEngineFactory for ExactOrFuzzyEngineFactory {
                     .query(query)
                     .algorithm(self.fuzzy_algorithm)
                     .case(case)
+                    .word_match(self.word_match)
                     .build(),
             )
         }
@@ -125,7 +142,7 @@ impl AndOrEngineFactory {
 
     fn parse_or(&self, query: &str, case: CaseMatching) -> Box<dyn MatchEngine> {
         if query.trim().is_empty() {
-            self.inner.create_engine_with_case(query, case)
+            self.create_term_engine(query, case)
         } else {
             Box::new(
                 OrEngine::builder()
@@ -143,7 +160,7 @@ impl AndOrEngineFactory {
             let (start, end) = (mat.start(), mat.end());
             let term = query_trim[last..start].trim_matches(|c| c == ' ' || c == '|');
             if !term.is_empty() {
-                engines.push(self.inner.create_engine_with_case(term, case));
+                engines.push(self.create_term_engine(term, case));
             }
 
             if !mat.as_str().trim().is_empty() {
@@ -154,15 +171,46 @@ impl AndOrEngineFactory {
 
         let term = query_trim[last..].trim_matches(|c| c == ' ' || c == '|');
         if !term.is_empty() {
-            engines.push(self.inner.create_engine_with_case(term, case));
+            engines.push(self.create_term_engine(term, case));
         }
         Box::new(AndEngine::builder().engines(engines).build())
     }
+
+    fn create_term_engine(&self, term: &str, case: CaseMatching) -> Box<dyn MatchEngine> {
+        if term.contains([QUOTED_SPACE, QUOTED_BAR]) {
+            let term = term.replace(QUOTED_SPACE, " ").replace(QUOTED_BAR, "|");
+            self.inner.create_engine_with_case(&term, case)
+        } else {
+            self.inner.create_engine_with_case(term, case)
+        }
+    }
+}
+
+/// remove the double quotes of the query, while protecting the spaces and `|` between them.
+/// An unbalanced quote lasts till the end of the query, `\"` is a literal quote.
+fn protect_quoted(query: &str) -> Cow<'_, str> {
+    if !query.contains('"') {
+        return Cow::Borrowed(query);
+    }
+
+    let mut ret = String::with_capacity(query.len());
+    let mut in_quote = false;
+    let mut chars = query.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if chars.peek() == Some(&'"') => ret.push(chars.next().unwrap()),
+            '"' => in_quote = !in_quote,
+            ' ' if in_quote => ret.push(QUOTED_SPACE),
+            '|' if in_quote => ret.push(QUOTED_BAR),
+            ch => ret.push(ch),
+        }
+    }
+    Cow::Owned(ret)
 }
 
 impl MatchEngineFactory for AndOrEngineFactory {
     fn create_engine_with_case(&self, query: &str, case: CaseMatching) -> Box<dyn MatchEngine> {
-        self.parse_or(query, case)
+        self.parse_or(&protect_quoted(query), case)
     }
 }
 
@@ -222,4 +270,50 @@ mod test {
         let x = regex_factory.create_engine("'abc | def ^gh ij | kl mn");
         assert_eq!(format!("{}", x), "(Regex: 'abc | def ^gh ij | kl mn)");
     }
+
+    #[test]
+    fn test_word_match() {
+        use super::*;
+        use crate::item::ItemWrapper;
+        use std::sync::Arc;
+
+        let factory = ExactOrFuzzyEngineFactory::builder().word_match(true).build();
+        let begin = |query: &str, text: &'static str| {
+            let item = Arc::new(ItemWrapper::new(Arc::new(text), (0, 0)));
+            factory
+                .create_engine(query)
+                .match_item(item)
+                .map(|matched| matched.rank.begin)
+        };
+
+        assert_eq!(begin("'cat", "concatenate"), None);
+        assert_eq!(begin("'cat", "concat cat"), Some(7));
+        assert_eq!(begin("'-v", "ls -va -v"), Some(7));
+        assert_eq!(begin("^cat", "cats"), None);
+        assert_eq!(begin("!cat", "concatenate"), Some(0));
+        assert_eq!(begin("!cat", "a cat"), None);
+    }
+
+    #[test]
+    fn test_quoted_terms() {
+        use super::*;
+        let and_or_factory = AndOrEngineFactory::new(ExactOrFuzzyEngineFactory::builder().build());
+
+        let x = and_or_factory.create_engine("\"foo bar\" baz");
+        assert_eq!(format!("{}", x), "(Or: (And: (Fuzzy: foo bar), (Fuzzy: baz)))");
+
+        let x = and_or_factory.create_engine("'\"foo bar\" | ^\"a b\" !\"c | d\"");
+        assert_eq!(
+            format!("{}", x),
+            "(Or: (And: (Exact|(?i)foo bar)), (And: (Exact|(?i)^a b), (Exact|!(?i)c \\| d)))"
+        );
+
+        // unbalanced quote lasts till the end
+        let x = and_or_factory.create_engine("abc \"de f | gh");
+        assert_eq!(format!("{}", x), "(Or: (And: (Fuzzy: abc), (Fuzzy: de f | gh)))");
+
+        // escaped quote
+        let x = and_or_factory.create_engine("\\\"abc\\\" d");
+        assert_eq!(format!("{}", x), "(Or: (And: (Fuzzy: \"abc\"), (Fuzzy: d)))");
+    }
 }
@@ -5,6 +5,7 @@ use fuzzy_matcher::clangd::ClangdMatcher;
 use fuzzy_matcher::skim::{SkimMatcher, SkimMatcherV2};
 use fuzzy_matcher::FuzzyMatcher;
 
+use crate::engine::util::{expand_chars_to_graphemes, is_whole_word};
 use crate::item::{ItemWrapper, MatchedItem, MatchedRange, Rank};
 use crate::SkimItem;
 use crate::{CaseMatching, MatchEngine};
@@ -34,7 +35,9 @@ impl Default for FuzzyAlgorithm {
     }
 }
 
-const BYTES_1M: usize = 1024 * 1024 * 1024;
+// bound the score matrices (query length * choice length) of pathological choices/queries, they
+// are matched without the optimal highlighting beyond it.
+const MAX_MATRIX_ELEMENTS: usize = 1024 * 1024;
 
 //------------------------------------------------------------------------------
 // Fuzzy engine
@@ -43,6 +46,7 @@ pub struct FuzzyEngineBuilder {
     query: String,
     case: CaseMatching,
     algorithm: FuzzyAlgorithm,
+    word_match: bool,
 }
 
 impl FuzzyEngineBuilder {
@@ -61,11 +65,21 @@ impl FuzzyEngineBuilder {
         self
     }
 
+    /// only match if every run of the consecutive matched chars is a whole word
+    pub fn word_match(mut self, word_match: bool) -> Self {
+        self.word_match = word_match;
+        self
+    }
+
     pub fn build(self) -> FuzzyEngine {
         let matcher: Box<dyn FuzzyMatcher> = match self.algorithm {
             FuzzyAlgorithm::SkimV1 => Box::new(SkimMatcher::default()),
             FuzzyAlgorithm::SkimV2 => {
-                let matcher = SkimMatcherV2::default().element_limit(BYTES_1M);
+                // the score matrices are kept per thread by the matcher (of this engine), and only
+                // grown for the longer choices, instead of allocated per item
+                let matcher = SkimMatcherV2::default()
+                    .element_limit(MAX_MATRIX_ELEMENTS)
+                    .use_cache(true);
                 let matcher = match self.case {
                     CaseMatching::Respect => matcher.respect_case(),
                     CaseMatching::Ignore => matcher.ignore_case(),
@@ -87,6 +101,7 @@ impl FuzzyEngineBuilder {
         FuzzyEngine {
             matcher,
             query: self.query,
+            word_match: self.word_match,
         }
     }
 }
@@ -94,6 +109,7 @@ impl FuzzyEngineBuilder {
 pub struct FuzzyEngine {
     query: String,
     matcher: Box<dyn FuzzyMatcher>,
+    word_match: bool,
 }
 
 impl FuzzyEngine {
@@ -108,22 +124,59 @@ impl FuzzyEngine {
             return None;
         }
 
-        self.matcher.fuzzy_indices(choice, pattern)
+        self.matcher
+            .fuzzy_indices(choice, pattern)
+            .filter(|(_, indices)| !self.word_match || is_whole_words(choice, indices))
+    }
+}
+
+/// whether the runs of the consecutive matched chars (indices of chars) are whole words. Only the
+/// alignment picked by the matcher is checked, the other alignments of the query are not tried.
+fn is_whole_words(text: &str, indices: &[usize]) -> bool {
+    // the byte offsets of the chars (and the end of the text), looked up in increasing order while
+    // walking the text once, so that nothing is allocated per item
+    let mut offsets = text
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(text.len()))
+        .enumerate();
+    let mut offset_of = |char_idx: usize| {
+        offsets
+            .find(|&(idx, _)| idx == char_idx)
+            .map(|(_, offset)| offset)
+            .unwrap_or(text.len())
+    };
+
+    let mut run_start = 0;
+    for idx in 0..indices.len() {
+        let run_ends = idx + 1 == indices.len() || indices[idx + 1] != indices[idx] + 1;
+        if run_ends {
+            let start = offset_of(indices[run_start]);
+            let end = offset_of(indices[idx] + 1);
+            if !is_whole_word(text, start, end) {
+                return false;
+            }
+            run_start = idx + 1;
+        }
     }
+    true
 }
 
 impl MatchEngine for FuzzyEngine {
     fn match_item(&self, item: Arc<ItemWrapper>) -> Option<MatchedItem> {
         // iterate over all matching fields:
         let mut matched_result = None;
+        let text = item.text();
         for &(start, end) in item.get_matching_ranges().as_ref() {
-            matched_result = self.fuzzy_match(&item.text()[start..end], &self.query).map(|(s, vec)| {
+            let field = &text[start..end];
+            matched_result = self.fuzzy_match(field, &self.query).map(|(s, vec)| {
+                let mut vec = expand_chars_to_graphemes(field, vec);
+                // shifted in place, the indices are the only allocation per matched item
                 if start != 0 {
-                    let start_char = &item.text()[..start].chars().count();
-                    (s, vec.iter().map(|x| x + start_char).collect())
-                } else {
-                    (s, vec)
+                    let start_char = text[..start].chars().count();
+                    vec.iter_mut().for_each(|x| *x += start_char);
                 }
+                (s, vec)
             });
 
             if matched_result.is_some() {
@@ -145,6 +198,7 @@ impl MatchEngine for FuzzyEngine {
             index: item.get_index() as i64,
             begin,
             end,
+            external: item.external_rank(),
         };
 
         Some(
@@ -161,3 +215,50 @@ impl Display for FuzzyEngine {
         write!(f, "(Fuzzy: {})", self.query)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_begin_of_first_matched_char() {
+        let engine = FuzzyEngine::builder().query("conf").build();
+        let begin = |text: &'static str| {
+            let item = Arc::new(ItemWrapper::new(Arc::new(text), (0, 0)));
+            engine.match_item(item).map(|matched| matched.rank.begin)
+        };
+
+        assert_eq!(begin("config.rs"), Some(0));
+        assert_eq!(begin("my_config.rs"), Some(3));
+        assert_eq!(begin("src/c/o/n/f.rs"), Some(4));
+        assert_eq!(begin("nothing"), None);
+    }
+
+    #[test]
+    fn test_word_match() {
+        let engine = FuzzyEngine::builder().query("cat").word_match(true).build();
+        let matched = |text: &'static str| {
+            let item = Arc::new(ItemWrapper::new(Arc::new(text), (0, 0)));
+            engine.match_item(item).and_then(|matched| matched.matched_range)
+        };
+
+        assert_eq!(matched("concatenate"), None);
+        assert_eq!(matched("my_cat"), None);
+        assert_eq!(matched("the cat.rs"), Some(MatchedRange::Chars(vec![4, 5, 6])));
+        assert_eq!(matched("cat"), Some(MatchedRange::Chars(vec![0, 1, 2])));
+    }
+
+    #[test]
+    fn test_match_grapheme_clusters() {
+        let matched = |query: &str, text: &'static str| {
+            let engine = FuzzyEngine::builder().query(query).build();
+            let item = Arc::new(ItemWrapper::new(Arc::new(text), (0, 0)));
+            engine.match_item(item).and_then(|matched| matched.matched_range)
+        };
+
+        // matching a part of the family (man, ZWJ, woman, ZWJ, girl) highlights all of it
+        assert_eq!(matched("👩", "a 👨‍👩‍👧"), Some(MatchedRange::Chars(vec![2, 3, 4, 5, 6])));
+        // so does a regional indicator of the flag
+        assert_eq!(matched("a🇸", "a🇺🇸"), Some(MatchedRange::Chars(vec![0, 1, 2])));
+    }
+}
@@ -1,7 +1,10 @@
 pub mod all;
 pub mod andor;
+pub mod display;
 pub mod exact;
 pub mod factory;
 pub mod fuzzy;
 pub mod regexp;
+pub mod scoring;
+pub mod transform;
 mod util;
@@ -3,7 +3,7 @@ use std::sync::Arc;
 
 use regex::Regex;
 
-use crate::engine::util::regex_match;
+use crate::engine::util::{expand_bytes_to_graphemes, regex_match};
 use crate::item::{ItemWrapper, MatchedItem, MatchedRange, Rank};
 use crate::SkimItem;
 use crate::{CaseMatching, MatchEngine};
@@ -46,8 +46,10 @@ impl MatchEngine for RegexEngine {
                 break;
             }
 
-            matched_result =
-                regex_match(&item.text()[start..end], &self.query_regex).map(|(s, e)| (s + start, e + start));
+            let text = &item.text()[start..end];
+            matched_result = regex_match(text, &self.query_regex)
+                .map(|(s, e)| expand_bytes_to_graphemes(text, s, e))
+                .map(|(s, e)| (s + start, e + start));
 
             if matched_result.is_some() {
                 break;
@@ -61,6 +63,7 @@ impl MatchEngine for RegexEngine {
             index: item.get_index() as i64,
             begin: begin as i64,
             end: end as i64,
+            external: item.external_rank(),
         };
 
         Some(
@@ -0,0 +1,130 @@
+//! Match the items with a user provided scoring function
+use std::fmt::{Display, Error, Formatter};
+use std::sync::Arc;
+
+use crate::item::{ItemWrapper, MatchedItem, MatchedRange, Rank};
+use crate::{CaseMatching, MatchEngine, MatchEngineFactory, SkimItem};
+
+/// Scoring function called with `(query, item_text)` for every item. It returns `None` if the item
+/// doesn't match, or `Some((score, ranges))` where
+///
+/// - `score` is any `i64`, the higher the better. Items of the same score are ordered as usual
+///   (`--tiebreak`).
+/// - `ranges` are the `(start, end)` byte offsets (`end` exclusive) into `item_text` to highlight.
+///   The ranges not on char boundaries or out of the text are ignored, leave it empty to highlight
+///   nothing.
+///
+/// The query is passed as is, i.e. neither the extended search syntax nor `--case` applies.
+pub type ScoreFn = Arc<dyn Fn(&str, &str) -> Option<(i64, Vec<(usize, usize)>)> + Send + Sync>;
+
+//------------------------------------------------------------------------------
+// Scoring engine factory
+pub struct ScoringEngineFactory {
+    score_fn: ScoreFn,
+}
+
+impl ScoringEngineFactory {
+    pub fn new(score_fn: ScoreFn) -> Self {
+        Self { score_fn }
+    }
+}
+
+impl MatchEngineFactory for ScoringEngineFactory {
+    fn create_engine_with_case(&self, query: &str, _case: CaseMatching) -> Box<dyn MatchEngine> {
+        Box::new(ScoringEngine {
+            query: query.to_string(),
+            score_fn: self.score_fn.clone(),
+        })
+    }
+}
+
+//------------------------------------------------------------------------------
+pub struct ScoringEngine {
+    query: String,
+    score_fn: ScoreFn,
+}
+
+impl MatchEngine for ScoringEngine {
+    fn match_item(&self, item: Arc<ItemWrapper>) -> Option<MatchedItem> {
+        let text = item.text();
+        let (score, ranges) = (self.score_fn)(&self.query, &text)?;
+        let matched_range = byte_ranges_to_chars(&text, &ranges);
+
+        let begin = *matched_range.first().unwrap_or(&0) as i64;
+        let end = *matched_range.last().unwrap_or(&0) as i64;
+
+        let rank = Rank {
+            score: -score,
+            index: item.get_index() as i64,
+            begin,
+            end,
+            external: item.external_rank(),
+        };
+
+        Some(
+            MatchedItem::builder(item.clone())
+                .rank(rank)
+                .matched_range(MatchedRange::Chars(matched_range))
+                .build(),
+        )
+    }
+}
+
+impl Display for ScoringEngine {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "(Scoring: {})", self.query)
+    }
+}
+
+/// convert the byte ranges into the sorted indices of the chars covered, skipping invalid ranges
+fn byte_ranges_to_chars(text: &str, ranges: &[(usize, usize)]) -> Vec<usize> {
+    let valid = |&&(start, end): &&(usize, usize)| {
+        start <= end && end <= text.len() && text.is_char_boundary(start) && text.is_char_boundary(end)
+    };
+    let ranges: Vec<_> = ranges.iter().filter(valid).collect();
+
+    text.char_indices()
+        .enumerate()
+        .filter(|(_, (byte_idx, _))| {
+            ranges
+                .iter()
+                .any(|&&(start, end)| start <= *byte_idx && *byte_idx < end)
+        })
+        .map(|(char_idx, _)| char_idx)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn prefix_len(query: &str, text: &str) -> Option<(i64, Vec<(usize, usize)>)> {
+        if text.starts_with(query) {
+            Some((query.len() as i64 - text.len() as i64, vec![(0, query.len())]))
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_scoring_engine() {
+        let factory = ScoringEngineFactory::new(Arc::new(prefix_len));
+        let engine = factory.create_engine("ab");
+        let match_text = |text: &'static str| engine.match_item(Arc::new(ItemWrapper::new(Arc::new(text), (0, 0))));
+
+        let matched = match_text("abc").unwrap();
+        assert_eq!(matched.rank.score, 1);
+        assert_eq!(matched.matched_range, Some(MatchedRange::Chars(vec![0, 1])));
+        // the higher score ranks first, i.e. it is negated into the rank
+        assert!(match_text("ab").unwrap().rank.score < match_text("abcd").unwrap().rank.score);
+        assert!(match_text("cab").is_none());
+    }
+
+    #[test]
+    fn test_byte_ranges_to_chars() {
+        assert_eq!(byte_ranges_to_chars("a中b", &[(1, 4)]), vec![1]);
+        assert_eq!(byte_ranges_to_chars("a中b", &[(0, 1), (4, 5)]), vec![0, 2]);
+        // not on char boundary or out of the text
+        assert_eq!(byte_ranges_to_chars("a中b", &[(2, 4), (4, 9)]), Vec::<usize>::new());
+    }
+}
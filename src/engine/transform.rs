@@ -0,0 +1,150 @@
+//! Fold the query and the items with a custom function before matching
+use std::borrow::Cow;
+use std::fmt::{Display, Error, Formatter};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::item::{ItemWrapper, MatchedItem, MatchedRange};
+use crate::{AnsiString, CaseMatching, MatchEngine, MatchEngineFactory, SkimItem};
+
+/// Transform applied to both the query and the text of items before they are matched, e.g. to
+/// strip a known prefix or to match phonetically.
+pub type MatchTransform = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+//------------------------------------------------------------------------------
+// Transform engine factory
+pub struct TransformEngineFactory {
+    inner: Rc<dyn MatchEngineFactory>,
+    transform: MatchTransform,
+}
+
+impl TransformEngineFactory {
+    pub fn new(inner: Rc<dyn MatchEngineFactory>, transform: MatchTransform) -> Self {
+        Self { inner, transform }
+    }
+}
+
+impl MatchEngineFactory for TransformEngineFactory {
+    fn create_engine_with_case(&self, query: &str, case: CaseMatching) -> Box<dyn MatchEngine> {
+        let query = (self.transform)(query);
+        Box::new(TransformEngine {
+            inner: self.inner.create_engine_with_case(&query, case),
+            transform: self.transform.clone(),
+        })
+    }
+}
+
+//------------------------------------------------------------------------------
+pub struct TransformEngine {
+    inner: Box<dyn MatchEngine>,
+    transform: MatchTransform,
+}
+
+impl MatchEngine for TransformEngine {
+    fn match_item(&self, item: Arc<ItemWrapper>) -> Option<MatchedItem> {
+        let text = item.text();
+        let transformed = (self.transform)(&text);
+
+        // the matching ranges are byte offsets, only valid if the length is kept
+        let matching_ranges = if transformed.len() == text.len() {
+            item.get_matching_ranges().into_owned()
+        } else {
+            vec![(0, transformed.len())]
+        };
+        let transformed_item = TransformedItem {
+            text: transformed,
+            matching_ranges,
+            external_rank: item.external_rank(),
+        };
+
+        let matched = self
+            .inner
+            .match_item(Arc::new(ItemWrapper::new(Arc::new(transformed_item), item.get_id())))?;
+        let transformed = matched.item.text();
+        let matched_range = matched
+            .matched_range
+            .clone()
+            .and_then(|range| map_matched_range(range, &text, &transformed));
+
+        let builder = MatchedItem::builder(item.clone()).rank(matched.rank);
+        Some(match matched_range {
+            Some(range) => builder.matched_range(range).build(),
+            None => builder.build(),
+        })
+    }
+}
+
+impl Display for TransformEngine {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "(Transform: {})", self.inner)
+    }
+}
+
+/// map the range matched in the transformed text back to the original text, the highlighting is
+/// dropped(None) if the lengths don't match.
+fn map_matched_range(range: MatchedRange, orig: &str, transformed: &str) -> Option<MatchedRange> {
+    match range {
+        MatchedRange::Chars(indices) if orig.chars().count() == transformed.chars().count() => {
+            Some(MatchedRange::Chars(indices))
+        }
+        MatchedRange::ByteRange(start, end)
+            if orig.len() == transformed.len() && orig.is_char_boundary(start) && orig.is_char_boundary(end) =>
+        {
+            Some(MatchedRange::ByteRange(start, end))
+        }
+        _ => None,
+    }
+}
+
+struct TransformedItem {
+    text: String,
+    matching_ranges: Vec<(usize, usize)>,
+    external_rank: i64,
+}
+
+impl SkimItem for TransformedItem {
+    fn display(&self) -> Cow<'_, AnsiString<'_>> {
+        Cow::Owned(self.text.as_str().into())
+    }
+
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.text)
+    }
+
+    fn get_matching_ranges(&self) -> Cow<'_, [(usize, usize)]> {
+        Cow::Borrowed(&self.matching_ranges)
+    }
+
+    fn external_rank(&self) -> i64 {
+        self.external_rank
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::factory::ExactOrFuzzyEngineFactory;
+
+    fn match_with(transform: MatchTransform, query: &str, text: &'static str) -> Option<MatchedItem> {
+        let factory = TransformEngineFactory::new(Rc::new(ExactOrFuzzyEngineFactory::builder().build()), transform);
+        let item = Arc::new(ItemWrapper::new(Arc::new(text), (0, 0)));
+        factory.create_engine(query).match_item(item)
+    }
+
+    #[test]
+    fn test_transform_engine() {
+        // same length: the highlighting is kept
+        let dash_to_underscore: MatchTransform = Arc::new(|text| text.replace('-', "_"));
+        let matched = match_with(dash_to_underscore, "a-b", "xa_b").unwrap();
+        assert_eq!(&*matched.item.text(), "xa_b");
+        assert_eq!(matched.matched_range, Some(MatchedRange::Chars(vec![1, 2, 3])));
+
+        // different length: matched, but not highlighted
+        let strip_prefix: MatchTransform = Arc::new(|text| text.trim_start_matches("src/").to_string());
+        let matched = match_with(strip_prefix.clone(), "src/lib", "src/lib.rs").unwrap();
+        assert_eq!(&*matched.item.text(), "src/lib.rs");
+        assert_eq!(matched.matched_range, None);
+
+        assert!(match_with(strip_prefix, "src/main", "src/lib.rs").is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
 
 pub fn regex_match(choice: &str, pattern: &Option<Regex>) -> Option<(usize, usize)> {
     match *pattern {
@@ -10,6 +11,93 @@ pub fn regex_match(choice: &str, pattern: &Option<Regex>) -> Option<(usize, usiz
     }
 }
 
+/// like `regex_match`, but skips the matches that are not whole words (see `is_whole_word`)
+pub fn regex_match_word(choice: &str, pattern: &Option<Regex>) -> Option<(usize, usize)> {
+    let pat = pattern.as_ref()?;
+    let mut start = 0;
+    while start <= choice.len() {
+        let mat = pat.find_at(choice, start)?;
+        if is_whole_word(choice, mat.start(), mat.end()) {
+            return Some((mat.start(), mat.end()));
+        }
+
+        // the matches could overlap, retry from the char next to the start of the last one
+        start = mat.start() + choice[mat.start()..].chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+/// The chars that separate the words for `word_match`: everything except the alphanumeric chars
+/// and `_`, so that the identifiers like `my_config` are single words.
+pub fn is_word_separator(ch: char) -> bool {
+    !(ch.is_alphanumeric() || ch == '_')
+}
+
+/// whether the span `text[start..end]` is bounded by the word separators (or the ends of the
+/// text). A span that starts/ends with a separator is a boundary itself, e.g. `-v` in `ls -va`.
+// `Option::is_none_or` is too recent for the supported rust versions
+#[allow(clippy::unnecessary_map_or)]
+pub fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let span = &text[start..end];
+    let bounded_before = text[..start].chars().next_back().map_or(true, is_word_separator)
+        || span.chars().next().map_or(true, is_word_separator);
+    let bounded_after = text[end..].chars().next().map_or(true, is_word_separator)
+        || span.chars().next_back().map_or(true, is_word_separator);
+    bounded_before && bounded_after
+}
+
+/// widen the matched chars (the indices of chars, in order) to the whole grapheme clusters, e.g.
+/// an emoji joined by ZWJs or a flag is matched as a unit, so that it is highlighted as a whole
+pub fn expand_chars_to_graphemes(text: &str, indices: Vec<usize>) -> Vec<usize> {
+    // only the non-ASCII texts have clusters of more than a char that could be matched
+    if text.is_ascii() || indices.is_empty() {
+        return indices;
+    }
+
+    let mut ret = Vec::with_capacity(indices.len());
+    let mut matched = indices.iter().peekable();
+    let mut cluster_start = 0;
+    for cluster in text.graphemes(true) {
+        let cluster_end = cluster_start + cluster.chars().count();
+        let mut hit = false;
+        while matched.next_if(|&&idx| idx < cluster_end).is_some() {
+            hit = true;
+        }
+        if hit {
+            ret.extend(cluster_start..cluster_end);
+        }
+
+        if matched.peek().is_none() {
+            break;
+        }
+        cluster_start = cluster_end;
+    }
+    ret
+}
+
+/// widen the matched bytes `start..end` to the whole grapheme clusters, see
+/// `expand_chars_to_graphemes`
+pub fn expand_bytes_to_graphemes(text: &str, start: usize, end: usize) -> (usize, usize) {
+    if text.is_ascii() || start == end {
+        return (start, end);
+    }
+
+    let (mut new_start, mut new_end) = (start, end);
+    for (offset, cluster) in text.grapheme_indices(true) {
+        let cluster_end = offset + cluster.len();
+        if offset < start && start < cluster_end {
+            new_start = offset;
+        }
+        if offset < end && end < cluster_end {
+            new_end = cluster_end;
+        }
+        if cluster_end >= end {
+            break;
+        }
+    }
+    (new_start, new_end)
+}
+
 pub fn contains_upper(string: &str) -> bool {
     for ch in string.chars() {
         if ch.is_ascii_uppercase() {
@@ -1,6 +1,7 @@
 // All the events that will be used
 
 use bitflags::bitflags;
+use std::mem;
 use std::sync::mpsc::{Receiver, Sender};
 use tuikit::key::Key;
 
@@ -15,7 +16,10 @@ pub enum Event {
 
     // user bind actions
     EvActAbort,
+    EvActAbortWithCurrent,
     EvActAccept(Option<String>),
+    EvActAcceptAll(Option<String>),
+    EvActActionMenu,
     EvActAddChar(char),
     EvActAppendAndSelect,
     EvActBackwardChar,
@@ -25,8 +29,10 @@ pub enum Event {
     EvActBeginningOfLine,
     EvActCancel,
     EvActClearScreen,
+    EvActCustom(usize),
     EvActDeleteChar,
     EvActDeleteCharEOF,
+    EvActDeleteItem,
     EvActDeselectAll,
     EvActDown(i32),
     EvActEndOfLine,
@@ -34,13 +40,21 @@ pub enum Event {
     EvActExecuteSilent(String),
     EvActForwardChar,
     EvActForwardWord,
+    EvActGotoMark(usize),
+    EvActHeaderDown(i32),
+    EvActHeaderUp(i32),
     EvActIfQueryEmpty(String),
     EvActIfQueryNotEmpty(String),
     EvActIfNonMatched(String),
     EvActIgnore,
+    EvActJumpNext(String),
+    EvActJumpPrev(String),
     EvActKillLine,
     EvActKillWord,
+    EvActLeft(i32),
+    EvActMark(usize),
     EvActNextHistory,
+    EvActPage(Option<String>),
     EvActPageDown(i32),
     EvActPageUp(i32),
     EvActPreviewUp(i32),
@@ -50,20 +64,26 @@ pub enum Event {
     EvActPreviewPageUp(i32),
     EvActPreviewPageDown(i32),
     EvActPreviousHistory,
+    EvActPut(String),
     EvActRedraw,
+    EvActRefreshPreview,
+    EvActRight(i32),
     EvActRotateMode,
     EvActScrollLeft(i32),
     EvActScrollRight(i32),
     EvActSelectAll,
+    EvActShowHelp,
     EvActSelectRow(usize),
     EvActToggle,
     EvActToggleAll,
+    EvActToggleCase,
     EvActToggleIn,
     EvActToggleInteractive,
     EvActToggleOut,
     EvActTogglePreview,
     EvActTogglePreviewWrap,
     EvActToggleSort,
+    EvActTransformQuery(String),
     EvActUnixLineDiscard,
     EvActUnixWordRubout,
     EvActUp(i32),
@@ -87,7 +107,10 @@ pub trait EventHandler {
 pub fn parse_event(action: &str, arg: Option<String>) -> Option<Event> {
     match action {
         "abort"                =>   Some(Event::EvActAbort),
+        "abort-with-current"   =>   Some(Event::EvActAbortWithCurrent),
         "accept"               =>   Some(Event::EvActAccept(arg)),
+        "accept-all"           =>   Some(Event::EvActAcceptAll(arg)),
+        "action-menu"          =>   Some(Event::EvActActionMenu),
         "append-and-select"    =>   Some(Event::EvActAppendAndSelect),
         "backward-char"        =>   Some(Event::EvActBackwardChar),
         "backward-delete-char" =>   Some(Event::EvActBackwardDeleteChar),
@@ -96,8 +119,10 @@ pub fn parse_event(action: &str, arg: Option<String>) -> Option<Event> {
         "beginning-of-line"    =>   Some(Event::EvActBeginningOfLine),
         "cancel"               =>   Some(Event::EvActCancel),
         "clear-screen"         =>   Some(Event::EvActClearScreen),
+        "custom"               =>   arg.and_then(|s|s.parse().ok()).map(Event::EvActCustom),
         "delete-char"          =>   Some(Event::EvActDeleteChar),
         "delete-charEOF"       =>   Some(Event::EvActDeleteCharEOF),
+        "delete-item"          =>   Some(Event::EvActDeleteItem),
         "deselect-all"         =>   Some(Event::EvActDeselectAll),
         "down"                 =>   Some(Event::EvActDown(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
         "end-of-line"          =>   Some(Event::EvActEndOfLine),
@@ -105,13 +130,22 @@ pub fn parse_event(action: &str, arg: Option<String>) -> Option<Event> {
         "execute-silent"       =>   Some(Event::EvActExecuteSilent(arg.expect("execute-silent event should have argument"))),
         "forward-char"         =>   Some(Event::EvActForwardChar),
         "forward-word"         =>   Some(Event::EvActForwardWord),
+        "goto-mark"            =>   Some(Event::EvActGotoMark(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
+        "header-down"          =>   Some(Event::EvActHeaderDown(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
+        "header-up"            =>   Some(Event::EvActHeaderUp(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
         "if-non-matched"       =>   Some(Event::EvActIfNonMatched(arg.expect("no arg specified for event if-non-matched"))),
         "if-query-empty"       =>   Some(Event::EvActIfQueryEmpty(arg.expect("no arg specified for event if-query-empty"))),
         "if-query-not-empty"   =>   Some(Event::EvActIfQueryNotEmpty(arg.expect("no arg specified for event if-query-not-empty"))),
         "ignore"               =>   Some(Event::EvActIgnore),
+        "invert-selection"     =>   Some(Event::EvActToggleAll),
+        "jump-next"            =>   Some(Event::EvActJumpNext(arg.expect("jump-next event should have argument"))),
+        "jump-prev"            =>   Some(Event::EvActJumpPrev(arg.expect("jump-prev event should have argument"))),
         "kill-line"            =>   Some(Event::EvActKillLine),
         "kill-word"            =>   Some(Event::EvActKillWord),
+        "left"                 =>   Some(Event::EvActLeft(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
+        "mark"                 =>   Some(Event::EvActMark(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
         "next-history"         =>   Some(Event::EvActNextHistory),
+        "page"                 =>   Some(Event::EvActPage(arg)),
         "page-down"            =>   Some(Event::EvActPageDown(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
         "page-up"              =>   Some(Event::EvActPageUp(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
         "preview-up"           =>   Some(Event::EvActPreviewUp(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
@@ -121,17 +155,23 @@ pub fn parse_event(action: &str, arg: Option<String>) -> Option<Event> {
         "preview-page-up"      =>   Some(Event::EvActPreviewPageUp(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
         "preview-page-down"    =>   Some(Event::EvActPreviewPageDown(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
         "previous-history"     =>   Some(Event::EvActPreviousHistory),
+        "put"                  =>   Some(Event::EvActPut(arg.expect("put event should have argument"))),
+        "refresh-preview"      =>   Some(Event::EvActRefreshPreview),
+        "right"                =>   Some(Event::EvActRight(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
         "scroll-left"          =>   Some(Event::EvActScrollLeft(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
         "scroll-right"         =>   Some(Event::EvActScrollRight(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
         "select-all"           =>   Some(Event::EvActSelectAll),
+        "show-help"            =>   Some(Event::EvActShowHelp),
         "toggle"               =>   Some(Event::EvActToggle),
         "toggle-all"           =>   Some(Event::EvActToggleAll),
+        "toggle-case"          =>   Some(Event::EvActToggleCase),
         "toggle-in"            =>   Some(Event::EvActToggleIn),
         "toggle-interactive"   =>   Some(Event::EvActToggleInteractive),
         "toggle-out"           =>   Some(Event::EvActToggleOut),
         "toggle-preview"       =>   Some(Event::EvActTogglePreview),
         "toggle-preview-wrap"  =>   Some(Event::EvActTogglePreviewWrap),
         "toggle-sort"          =>   Some(Event::EvActToggleSort),
+        "transform-query"      =>   Some(Event::EvActTransformQuery(arg.expect("transform-query event should have argument"))),
         "unix-line-discard"    =>   Some(Event::EvActUnixLineDiscard),
         "unix-word-rubout"     =>   Some(Event::EvActUnixWordRubout),
         "up"                   =>   Some(Event::EvActUp(arg.and_then(|s|s.parse().ok()).unwrap_or(1))),
@@ -139,3 +179,75 @@ pub fn parse_event(action: &str, arg: Option<String>) -> Option<Event> {
         _ => None
     }
 }
+
+/// the names of the actions parsed by `parse_event`, one per event (`invert-selection` is the
+/// alias of `toggle-all`)
+#[rustfmt::skip]
+const ACTION_NAMES: &[&str] = &[
+    "abort", "abort-with-current", "accept", "accept-all", "action-menu", "append-and-select",
+    "backward-char", "backward-delete-char", "backward-kill-word", "backward-word",
+    "beginning-of-line", "cancel", "clear-screen", "custom", "delete-char", "delete-charEOF",
+    "delete-item", "deselect-all", "down", "end-of-line", "execute", "execute-silent",
+    "forward-char", "forward-word", "goto-mark", "header-down", "header-up", "if-non-matched",
+    "if-query-empty", "if-query-not-empty", "ignore", "jump-next", "jump-prev", "kill-line",
+    "kill-word", "left", "mark", "next-history", "page", "page-down", "page-up", "preview-up",
+    "preview-down", "preview-left", "preview-right", "preview-page-up", "preview-page-down",
+    "previous-history", "put", "refresh-preview", "right", "scroll-left", "scroll-right",
+    "select-all", "show-help", "toggle", "toggle-all", "toggle-case", "toggle-in",
+    "toggle-interactive", "toggle-out", "toggle-preview", "toggle-preview-wrap", "toggle-sort",
+    "transform-query", "unix-line-discard", "unix-word-rubout", "up", "yank",
+];
+
+/// the name of the action (as in `--bind`) that gives the event, `None` if it is not bindable
+pub fn action_name(event: &Event) -> Option<&'static str> {
+    let discriminant = mem::discriminant(event);
+    ACTION_NAMES.iter().cloned().find(|name| {
+        // any argument is good for the events that take one
+        parse_event(name, Some("1".to_string())).map(|ev| mem::discriminant(&ev)) == Some(discriminant)
+    })
+}
+
+/// the argument of the action that gives the event (as in `--bind`), e.g. `2` of `down(2)`
+#[rustfmt::skip]
+pub fn action_arg(event: &Event) -> Option<String> {
+    use Event::*;
+    match event {
+        EvActAccept(arg) | EvActAcceptAll(arg) | EvActPage(arg) => arg.clone(),
+
+        EvActExecute(arg) | EvActExecuteSilent(arg) | EvActIfNonMatched(arg) | EvActIfQueryEmpty(arg)
+        | EvActIfQueryNotEmpty(arg) | EvActJumpNext(arg) | EvActJumpPrev(arg) | EvActPut(arg)
+        | EvActTransformQuery(arg) => Some(arg.clone()),
+
+        EvActCustom(arg) | EvActGotoMark(arg) | EvActMark(arg) => Some(arg.to_string()),
+
+        EvActDown(arg) | EvActHeaderDown(arg) | EvActHeaderUp(arg) | EvActLeft(arg) | EvActPageDown(arg)
+        | EvActPageUp(arg) | EvActPreviewUp(arg) | EvActPreviewDown(arg) | EvActPreviewLeft(arg)
+        | EvActPreviewRight(arg) | EvActPreviewPageUp(arg) | EvActPreviewPageDown(arg) | EvActRight(arg)
+        | EvActScrollLeft(arg) | EvActScrollRight(arg) | EvActUp(arg) => Some(arg.to_string()),
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_action_name() {
+        for &name in ACTION_NAMES {
+            let event = parse_event(name, Some("2".to_string())).unwrap();
+            assert_eq!(action_name(&event), Some(name));
+        }
+        assert_eq!(action_name(&Event::EvActToggleAll), Some("toggle-all"));
+        assert_eq!(action_name(&Event::EvHeartBeat), None);
+
+        assert_eq!(action_arg(&Event::EvActDown(2)), Some("2".to_string()));
+        assert_eq!(action_arg(&Event::EvActAccept(None)), None);
+        assert_eq!(
+            action_arg(&Event::EvActAccept(Some("ctrl-y".to_string()))),
+            Some("ctrl-y".to_string())
+        );
+        assert_eq!(action_arg(&Event::EvActToggle), None);
+    }
+}
@@ -158,6 +158,29 @@ pub fn parse_transform_fields(delimiter: &Regex, text: &str, fields: &[FieldRang
     ret
 }
 
+// -> the fields joined by the delimiter, or by a space if the delimiter is not a literal string
+// Given delimiter `,`, text: "a,b,c"
+// &[Single(3), Single(1)] => "c,a"
+pub fn parse_output_fields(delimiter: &Regex, text: &str, fields: &[FieldRange]) -> String {
+    let ranges = get_ranges_by_delimiter(delimiter, text);
+    let separator = if regex::escape(delimiter.as_str()) == delimiter.as_str() {
+        delimiter.as_str()
+    } else {
+        " "
+    };
+
+    fields
+        .iter()
+        .filter_map(|field| field.to_index_pair(ranges.len()))
+        .map(|(start, stop)| {
+            let &(begin, _) = &ranges[start];
+            let &(_, end) = &ranges[stop - 1];
+            &text[begin..end]
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
 #[cfg(test)]
 mod test {
     use super::FieldRange::*;
@@ -267,6 +290,24 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_parse_output_fields() {
+        let re = Regex::new(",").unwrap();
+        assert_eq!(super::parse_output_fields(&re, "A,B,C", &[LeftInf(2)]), "A,B");
+        assert_eq!(super::parse_output_fields(&re, "A,B,C", &[Single(3), Single(1)]), "C,A");
+        assert_eq!(super::parse_output_fields(&re, "A,B,C", &[Single(4)]), "");
+
+        let re = Regex::new(r"[\t\n ]+").unwrap();
+        assert_eq!(
+            super::parse_output_fields(&re, "rw-r--r-- 1 main.rs", &[Single(3)]),
+            "main.rs"
+        );
+        assert_eq!(
+            super::parse_output_fields(&re, "rw-r--r--  1 main.rs", &[Single(1), Single(-1)]),
+            "rw-r--r-- main.rs"
+        );
+    }
+
     #[test]
     fn test_parse_matching_fields() {
         // delimiter is ","
@@ -5,9 +5,10 @@ use crate::event::{Event, EventHandler};
 use crate::item::ItemPool;
 use crate::theme::ColorTheme;
 use crate::theme::DEFAULT_THEME;
-use crate::util::{print_item, LinePrinter};
+use crate::util::{cluster_char_widths, print_item, LinePrinter};
 use crate::SkimOptions;
-use std::cmp::max;
+use std::cmp::{max, min};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tuikit::prelude::*;
 
@@ -15,6 +16,8 @@ pub struct Header {
     header: AnsiString<'static>,
     tabstop: usize,
     hscroll_offset: usize,
+    vscroll_offset: usize, // the lines skipped from the one next to the query, if it overflows
+    height: AtomicUsize,   // the lines shown in the last draw
     reverse: bool,
     theme: Arc<ColorTheme>,
 
@@ -28,6 +31,8 @@ impl Header {
             header: AnsiString::new_empty(),
             tabstop: 8,
             hscroll_offset: 0,
+            vscroll_offset: 0,
+            height: AtomicUsize::new(0),
             reverse: false,
             theme: Arc::new(*DEFAULT_THEME),
             item_pool: Arc::new(ItemPool::new()),
@@ -75,6 +80,15 @@ impl Header {
         self.hscroll_offset = hscroll_offset as usize;
     }
 
+    /// scroll to the lines farther from the query by `diff`, clamped so that the screen is filled
+    pub fn act_vscroll(&mut self, diff: i32) {
+        let max_offset = self
+            .lines_of_header()
+            .saturating_sub(self.height.load(Ordering::Relaxed));
+        let offset = max(0, self.vscroll_offset as i32 + diff) as usize;
+        self.vscroll_offset = min(offset, max_offset);
+    }
+
     fn lines_of_header(&self) -> usize {
         let fixed = if self.header.is_empty() { 0 } else { 1 };
         fixed + self.item_pool.reserved().len()
@@ -88,26 +102,41 @@ impl Draw for Header {
             return Err("screen width is too small".into());
         }
 
-        if screen_height < self.lines_of_header() {
-            return Err("screen height is too small".into());
-        }
-
         canvas.clear()?;
 
-        if !self.is_empty() {
-            // print fixed header(specified by --header)
-            let mut printer = LinePrinter::builder()
-                .row(if self.reverse { 0 } else { screen_height - 1 })
-                .col(2)
-                .tabstop(self.tabstop)
-                .container_width(screen_width - 2)
-                .shift(0)
-                .text_width(screen_width - 2)
-                .hscroll_offset(self.hscroll_offset)
-                .build();
+        // the header taller than the screen is scrolled (`header-up`/`header-down`)
+        self.height.store(screen_height, Ordering::Relaxed);
+        let offset = min(
+            self.vscroll_offset,
+            self.lines_of_header().saturating_sub(screen_height),
+        );
+        let row_of_line = |line: usize| {
+            if line < offset || line >= offset + screen_height {
+                None
+            } else if self.reverse {
+                Some(line - offset)
+            } else {
+                Some(screen_height - (line - offset) - 1)
+            }
+        };
 
-            for (ch, _attr) in self.header.iter() {
-                printer.print_char(canvas, ch, self.theme.header(), false);
+        if !self.is_empty() {
+            if let Some(row) = row_of_line(0) {
+                // print fixed header(specified by --header)
+                let mut printer = LinePrinter::builder()
+                    .row(row)
+                    .col(2)
+                    .tabstop(self.tabstop)
+                    .container_width(screen_width - 2)
+                    .shift(0)
+                    .text_width(screen_width - 2)
+                    .hscroll_offset(self.hscroll_offset)
+                    .build();
+
+                let widths = cluster_char_widths(self.header.stripped());
+                for ((ch, _attr), width) in self.header.iter().zip(widths) {
+                    printer.print_char_of_width(canvas, ch, width, self.theme.header(), false);
+                }
             }
         }
 
@@ -115,10 +144,9 @@ impl Draw for Header {
 
         // print "reserved" header lines (--header-lines)
         for (idx, item) in self.item_pool.reserved().iter().enumerate() {
-            let row = if self.reverse {
-                idx + lines_used
-            } else {
-                screen_height - lines_used - idx - 1
+            let row = match row_of_line(idx + lines_used) {
+                Some(row) => row,
+                None => continue,
             };
 
             let mut printer = LinePrinter::builder()
@@ -154,6 +182,15 @@ impl EventHandler for Header {
                 self.act_scroll(*diff);
             }
 
+            // the lines farther from the query are above it, unless reversed
+            Event::EvActHeaderUp(diff) => {
+                self.act_vscroll(if self.reverse { -*diff } else { *diff });
+            }
+
+            Event::EvActHeaderDown(diff) => {
+                self.act_vscroll(if self.reverse { *diff } else { -*diff });
+            }
+
             _ => {
                 return UpdateScreen::DONT_REDRAW;
             }
@@ -162,3 +199,46 @@ impl EventHandler for Header {
         UpdateScreen::REDRAW
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::item::ItemWrapper;
+    use crate::util::screen_row;
+    use tuikit::screen::Screen;
+
+    fn header_with_lines(num: usize) -> Header {
+        let item_pool = Arc::new(ItemPool::new().lines_to_reserve(num));
+        item_pool.append(
+            (0..num)
+                .map(|idx| Arc::new(ItemWrapper::new(Arc::new(format!("line {}", idx)), (0, idx as u32))))
+                .collect(),
+        );
+        Header::empty().item_pool(item_pool)
+    }
+
+    #[test]
+    fn test_vscroll() {
+        let mut header = header_with_lines(5);
+        let mut screen = Screen::new(20, 2);
+        header.draw(&mut screen).unwrap();
+        // the lines next to the query are shown first, bottom up
+        assert_eq!(screen_row(&screen, 0), "line 1");
+        assert_eq!(screen_row(&screen, 1), "line 0");
+
+        header.handle(&Event::EvActHeaderUp(2));
+        header.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 0), "line 3");
+        assert_eq!(screen_row(&screen, 1), "line 2");
+
+        // clamped to the content
+        header.handle(&Event::EvActHeaderUp(10));
+        header.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 0), "line 4");
+        assert_eq!(screen_row(&screen, 1), "line 3");
+
+        header.handle(&Event::EvActHeaderDown(10));
+        header.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 1), "line 0");
+    }
+}
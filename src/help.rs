@@ -0,0 +1,103 @@
+//! the overlay of the key bindings, shown by `show-help`
+use crate::event::UpdateScreen;
+use crate::event::{Event, EventHandler};
+use crate::theme::ColorTheme;
+use crate::theme::DEFAULT_THEME;
+use std::cmp::{max, min};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tuikit::prelude::*;
+
+const TITLE: &str = "Key bindings (scroll by up/down, close by any other key)";
+
+pub struct Help {
+    lines: Vec<String>,
+    vscroll_offset: usize,
+    height: AtomicUsize, // the lines shown in the last draw
+    theme: Arc<ColorTheme>,
+}
+
+impl Help {
+    pub fn new(lines: Vec<String>) -> Self {
+        Self {
+            lines,
+            vscroll_offset: 0,
+            height: AtomicUsize::new(0),
+            theme: Arc::new(*DEFAULT_THEME),
+        }
+    }
+
+    pub fn theme(mut self, theme: Arc<ColorTheme>) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// scroll down by `diff` lines, clamped so that the screen is filled
+    pub fn act_vscroll(&mut self, diff: i32) {
+        let max_offset = self.lines.len().saturating_sub(self.height.load(Ordering::Relaxed));
+        let offset = max(0, self.vscroll_offset as i32 + diff) as usize;
+        self.vscroll_offset = min(offset, max_offset);
+    }
+}
+
+impl Draw for Help {
+    fn draw(&self, canvas: &mut dyn Canvas) -> Result<()> {
+        let (_screen_width, screen_height) = canvas.size()?;
+        canvas.clear()?;
+        canvas.print_with_attr(0, 0, TITLE, self.theme.prompt())?;
+
+        // the first row is the title
+        let height = screen_height.saturating_sub(1);
+        self.height.store(height, Ordering::Relaxed);
+        let offset = min(self.vscroll_offset, self.lines.len().saturating_sub(height));
+        for (row, line) in self.lines.iter().skip(offset).take(height).enumerate() {
+            canvas.print_with_attr(row + 1, 2, line, self.theme.normal())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Widget<Event> for Help {}
+
+impl EventHandler for Help {
+    fn handle(&mut self, event: &Event) -> UpdateScreen {
+        let page = max(1, self.height.load(Ordering::Relaxed) as i32 - 1);
+        match event {
+            Event::EvActUp(diff) => self.act_vscroll(-*diff),
+            Event::EvActDown(diff) => self.act_vscroll(*diff),
+            Event::EvActPageUp(diff) => self.act_vscroll(-*diff * page),
+            Event::EvActPageDown(diff) => self.act_vscroll(*diff * page),
+            _ => return UpdateScreen::DONT_REDRAW,
+        }
+
+        UpdateScreen::REDRAW
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::screen_row;
+    use tuikit::screen::Screen;
+
+    #[test]
+    fn test_vscroll() {
+        let mut help = Help::new((0..5).map(|idx| format!("line {}", idx)).collect());
+        let mut screen = Screen::new(80, 3);
+        help.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 0), TITLE);
+        assert_eq!(screen_row(&screen, 1), "line 0");
+        assert_eq!(screen_row(&screen, 2), "line 1");
+
+        help.handle(&Event::EvActDown(1));
+        help.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 1), "line 1");
+
+        // clamped so that the screen is filled
+        help.handle(&Event::EvActPageDown(10));
+        help.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 1), "line 3");
+        assert_eq!(screen_row(&screen, 2), "line 4");
+    }
+}
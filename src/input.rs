@@ -1,8 +1,10 @@
 ///! Input will listens to user input, modify the query string, send special
 ///! keystrokes(such as Enter, Ctrl-p, Ctrl-n, etc) to the controller.
 use crate::event::{parse_event, Event};
+use crate::SkimItem;
 use regex::Regex;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tuikit::event::Event as TermEvent;
 use tuikit::key::{from_keyname, Key};
 
@@ -58,14 +60,43 @@ impl Input {
         debug!("got key_action: {:?}", key_action);
         for (key, action_chain) in parse_key_action(key_action).into_iter() {
             debug!("parsed key_action: {:?}: {:?}", key, action_chain);
-            let action_chain = action_chain
-                .into_iter()
-                .filter_map(|(action, arg)| parse_event(action, arg))
-                .collect();
-            self.bind(key, action_chain);
+            self.bind(key, parse_action_chain(action_chain));
+        }
+    }
+
+    pub fn bind_keymap(&mut self, keymap: &Keymap) {
+        for (key, actions) in keymap.bindings.iter() {
+            if actions.is_empty() {
+                continue;
+            }
+            let action_chain = actions.iter().cloned().map(Action::into_event).collect();
+            self.keymap.insert(*key, action_chain);
         }
     }
 
+    /// the lines of the key bindings sorted by the keys, e.g. `ctrl-j  down(1)`. The custom actions
+    /// are labeled by `custom_labels` (by index) if given.
+    pub fn describe_bindings(&self, custom_labels: &[String]) -> Vec<String> {
+        let mut bindings: Vec<(String, String)> = self
+            .keymap
+            .iter()
+            .map(|(key, action_chain)| {
+                let actions: Vec<String> = action_chain
+                    .iter()
+                    .map(|event| action_name(event, custom_labels))
+                    .collect();
+                (key_name(key), actions.join("+"))
+            })
+            .collect();
+        bindings.sort();
+
+        let key_width = bindings.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+        bindings
+            .into_iter()
+            .map(|(key, actions)| format!("{:width$}  {}", key, actions, width = key_width))
+            .collect()
+    }
+
     pub fn parse_expect_keys(&mut self, keys: Option<&str>) {
         if let Some(keys) = keys {
             self.bind("enter", vec![Event::EvActAccept(Some("".to_string()))]);
@@ -76,6 +107,244 @@ impl Input {
     }
 }
 
+/// The built-in actions that could be bound to keys, see the man page for the details.
+/// e.g. `Action::Up(1)` is the same as the action `up` in `--bind`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Abort,
+    /// abort, but return the current item in `SkimOutput` (with `is_abort` set)
+    AbortWithCurrent,
+    Accept(Option<String>),
+    AcceptAll(Option<String>),
+    /// list the actions of the current item (`SkimItem::actions`), to accept it with the one chosen
+    ActionMenu,
+    AddChar(char),
+    AppendAndSelect,
+    BackwardChar,
+    BackwardDeleteChar,
+    BackwardKillWord,
+    BackwardWord,
+    BeginningOfLine,
+    Cancel,
+    ClearScreen,
+    /// run the `n`th closure of `SkimOptions::custom_actions`
+    Custom(usize),
+    DeleteChar,
+    DeleteCharEOF,
+    /// remove the current item, reported by `SkimOutput::deleted_items`
+    DeleteItem,
+    DeselectAll,
+    Down(i32),
+    EndOfLine,
+    Execute(String),
+    ExecuteSilent(String),
+    ForwardChar,
+    ForwardWord,
+    GotoMark(usize),
+    /// scroll the header down by `n` lines, when it is taller than the space for it
+    HeaderDown(i32),
+    /// scroll the header up by `n` lines, when it is taller than the space for it
+    HeaderUp(i32),
+    IfQueryEmpty(String),
+    IfQueryNotEmpty(String),
+    IfNonMatched(String),
+    Ignore,
+    /// move the cursor to the next matched item containing the fixed string (`{q}` for the query)
+    JumpNext(String),
+    /// move the cursor to the previous matched item containing the fixed string
+    JumpPrev(String),
+    KillLine,
+    KillWord,
+    Left(i32),
+    Mark(usize),
+    NextHistory,
+    /// show the output of the command (default: `cat {}`) in `$PAGER`, then return to skim
+    Page(Option<String>),
+    PageDown(i32),
+    PageUp(i32),
+    PreviewUp(i32),
+    PreviewDown(i32),
+    PreviewLeft(i32),
+    PreviewRight(i32),
+    PreviewPageUp(i32),
+    PreviewPageDown(i32),
+    PreviousHistory,
+    /// insert the text into the query at the cursor
+    Put(String),
+    Redraw,
+    RefreshPreview,
+    Right(i32),
+    RotateMode,
+    ScrollLeft(i32),
+    ScrollRight(i32),
+    SelectAll,
+    /// show the key bindings in an overlay, any key other than moving ones closes it
+    ShowHelp,
+    SelectRow(usize),
+    Toggle,
+    ToggleAll,
+    /// rotate the case matching (respect, ignore, smart) and match again
+    ToggleCase,
+    ToggleIn,
+    ToggleInteractive,
+    ToggleOut,
+    TogglePreview,
+    TogglePreviewWrap,
+    ToggleSort,
+    /// replace the query with the output of the command, which reads the query from stdin
+    TransformQuery(String),
+    UnixLineDiscard,
+    UnixWordRubout,
+    Up(i32),
+    Yank,
+}
+
+impl Action {
+    fn into_event(self) -> Event {
+        match self {
+            Action::Abort => Event::EvActAbort,
+            Action::AbortWithCurrent => Event::EvActAbortWithCurrent,
+            Action::Accept(arg) => Event::EvActAccept(arg),
+            Action::AcceptAll(arg) => Event::EvActAcceptAll(arg),
+            Action::ActionMenu => Event::EvActActionMenu,
+            Action::AddChar(arg) => Event::EvActAddChar(arg),
+            Action::AppendAndSelect => Event::EvActAppendAndSelect,
+            Action::BackwardChar => Event::EvActBackwardChar,
+            Action::BackwardDeleteChar => Event::EvActBackwardDeleteChar,
+            Action::BackwardKillWord => Event::EvActBackwardKillWord,
+            Action::BackwardWord => Event::EvActBackwardWord,
+            Action::BeginningOfLine => Event::EvActBeginningOfLine,
+            Action::Cancel => Event::EvActCancel,
+            Action::ClearScreen => Event::EvActClearScreen,
+            Action::Custom(arg) => Event::EvActCustom(arg),
+            Action::DeleteChar => Event::EvActDeleteChar,
+            Action::DeleteCharEOF => Event::EvActDeleteCharEOF,
+            Action::DeleteItem => Event::EvActDeleteItem,
+            Action::DeselectAll => Event::EvActDeselectAll,
+            Action::Down(arg) => Event::EvActDown(arg),
+            Action::EndOfLine => Event::EvActEndOfLine,
+            Action::Execute(arg) => Event::EvActExecute(arg),
+            Action::ExecuteSilent(arg) => Event::EvActExecuteSilent(arg),
+            Action::ForwardChar => Event::EvActForwardChar,
+            Action::ForwardWord => Event::EvActForwardWord,
+            Action::GotoMark(arg) => Event::EvActGotoMark(arg),
+            Action::HeaderDown(arg) => Event::EvActHeaderDown(arg),
+            Action::HeaderUp(arg) => Event::EvActHeaderUp(arg),
+            Action::IfQueryEmpty(arg) => Event::EvActIfQueryEmpty(arg),
+            Action::IfQueryNotEmpty(arg) => Event::EvActIfQueryNotEmpty(arg),
+            Action::IfNonMatched(arg) => Event::EvActIfNonMatched(arg),
+            Action::Ignore => Event::EvActIgnore,
+            Action::JumpNext(arg) => Event::EvActJumpNext(arg),
+            Action::JumpPrev(arg) => Event::EvActJumpPrev(arg),
+            Action::KillLine => Event::EvActKillLine,
+            Action::KillWord => Event::EvActKillWord,
+            Action::Left(arg) => Event::EvActLeft(arg),
+            Action::Mark(arg) => Event::EvActMark(arg),
+            Action::NextHistory => Event::EvActNextHistory,
+            Action::Page(arg) => Event::EvActPage(arg),
+            Action::PageDown(arg) => Event::EvActPageDown(arg),
+            Action::PageUp(arg) => Event::EvActPageUp(arg),
+            Action::PreviewUp(arg) => Event::EvActPreviewUp(arg),
+            Action::PreviewDown(arg) => Event::EvActPreviewDown(arg),
+            Action::PreviewLeft(arg) => Event::EvActPreviewLeft(arg),
+            Action::PreviewRight(arg) => Event::EvActPreviewRight(arg),
+            Action::PreviewPageUp(arg) => Event::EvActPreviewPageUp(arg),
+            Action::PreviewPageDown(arg) => Event::EvActPreviewPageDown(arg),
+            Action::PreviousHistory => Event::EvActPreviousHistory,
+            Action::Put(arg) => Event::EvActPut(arg),
+            Action::Redraw => Event::EvActRedraw,
+            Action::RefreshPreview => Event::EvActRefreshPreview,
+            Action::Right(arg) => Event::EvActRight(arg),
+            Action::RotateMode => Event::EvActRotateMode,
+            Action::ScrollLeft(arg) => Event::EvActScrollLeft(arg),
+            Action::ScrollRight(arg) => Event::EvActScrollRight(arg),
+            Action::SelectAll => Event::EvActSelectAll,
+            Action::ShowHelp => Event::EvActShowHelp,
+            Action::SelectRow(arg) => Event::EvActSelectRow(arg),
+            Action::Toggle => Event::EvActToggle,
+            Action::ToggleAll => Event::EvActToggleAll,
+            Action::ToggleCase => Event::EvActToggleCase,
+            Action::ToggleIn => Event::EvActToggleIn,
+            Action::ToggleInteractive => Event::EvActToggleInteractive,
+            Action::ToggleOut => Event::EvActToggleOut,
+            Action::TogglePreview => Event::EvActTogglePreview,
+            Action::TogglePreviewWrap => Event::EvActTogglePreviewWrap,
+            Action::ToggleSort => Event::EvActToggleSort,
+            Action::TransformQuery(arg) => Event::EvActTransformQuery(arg),
+            Action::UnixLineDiscard => Event::EvActUnixLineDiscard,
+            Action::UnixWordRubout => Event::EvActUnixWordRubout,
+            Action::Up(arg) => Event::EvActUp(arg),
+            Action::Yank => Event::EvActYank,
+        }
+    }
+}
+
+/// What a custom action could see when it is fired
+pub struct ActionContext {
+    pub query: String,
+    pub cmd_query: String,
+    pub current_item: Option<Arc<dyn SkimItem>>,
+    pub selected_items: Vec<Arc<dyn SkimItem>>,
+}
+
+/// A user provided action, bound by `Action::Custom(n)` where `n` is its index in
+/// `SkimOptions::custom_actions`. The returned actions are executed right after it, so it could be
+/// composed with the built-in ones (e.g. return `vec![Action::Accept(None)]` to finish skim).
+pub type CustomAction = Arc<dyn Fn(&ActionContext) -> Vec<Action> + Send + Sync>;
+
+/// fire the custom action of `index`, return the events of the actions it returns
+pub fn fire_custom_action(custom_actions: &[CustomAction], index: usize, context: &ActionContext) -> ActionChain {
+    match custom_actions.get(index) {
+        Some(action) => action(context).into_iter().map(Action::into_event).collect(),
+        None => {
+            debug!("custom action {} is not provided", index);
+            Vec::new()
+        }
+    }
+}
+
+/// A typed alternative of `--bind`, e.g.
+///
+/// ```rust
+/// use skim::prelude::*;
+///
+/// let keymap = Keymap::new()
+///     .bind(Key::Ctrl('j'), Action::Accept(None))
+///     .bind_chain(Key::Ctrl('a'), vec![Action::SelectAll, Action::Accept(None)]);
+/// let options = SkimOptionsBuilder::default().keymap(keymap).build().unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Keymap {
+    bindings: Vec<(Key, Vec<Action>)>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// bind `action` to `key`, override the existing bindings of the key
+    pub fn bind(self, key: Key, action: Action) -> Self {
+        self.bind_chain(key, vec![action])
+    }
+
+    /// bind a chain of actions to `key`, they are executed in order
+    pub fn bind_chain(mut self, key: Key, actions: Vec<Action>) -> Self {
+        self.bindings.push((key, actions));
+        self
+    }
+}
+
+/// composite actions are shortcuts for a chain of actions
+/// e.g. `toggle-down` is the same as `toggle+down`
+fn parse_composite_action(action: &str) -> Option<ActionChain> {
+    match action {
+        "toggle-down" => Some(vec![Event::EvActToggle, Event::EvActDown(1)]),
+        "toggle-up" => Some(vec![Event::EvActToggle, Event::EvActUp(1)]),
+        _ => None,
+    }
+}
+
 type KeyActions<'a> = (&'a str, Vec<(&'a str, Option<String>)>);
 
 /// parse key action string to `(key, action, argument)` tuple
@@ -117,19 +386,97 @@ pub fn parse_key_action(key_action: &str) -> Vec<KeyActions> {
         .collect()
 }
 
-/// e.g. execute(...) => Some(Event::EvActExecute, Box::new(Option("...")))
-pub fn parse_action_arg(action_arg: &str) -> Option<Event> {
+/// convert the parsed `(action, arg)` pairs into events, in order
+fn parse_action_chain(actions: Vec<(&str, Option<String>)>) -> ActionChain {
+    actions
+        .into_iter()
+        .flat_map(|(action, arg)| {
+            parse_composite_action(action).unwrap_or_else(|| parse_event(action, arg).into_iter().collect())
+        })
+        .collect()
+}
+
+/// e.g. execute(...)+abort => vec![Event::EvActExecute("..."), Event::EvActAbort]
+pub fn parse_action_arg(action_arg: &str) -> ActionChain {
     // construct a fake key_action: `fake_key:action(arg)`
     let fake_key_action = format!("fake_key:{}", action_arg);
     // get keys: [(key, [(action, arg), (action, arg)]), ...]
-    let keys = parse_key_action(&fake_key_action);
-    // only get the first key(since it is faked), and get the first action
-    if keys.is_empty() || keys[0].1.is_empty() {
-        None
+    let mut keys = parse_key_action(&fake_key_action);
+    // only get the first key(since it is faked)
+    if keys.is_empty() {
+        Vec::new()
     } else {
-        // first action pair of key(keys[0].1) and first action (keys[0].1[0])
-        let (action, new_arg) = keys[0].1[0].clone();
-        parse_event(action, new_arg)
+        parse_action_chain(keys.swap_remove(0).1)
+    }
+}
+
+/// the name of the key as in `--bind`
+#[rustfmt::skip]
+fn key_name(key: &Key) -> String {
+    use Key::*;
+
+    let name = match *key {
+        Ctrl(' ') | Null                    => "ctrl-space", // the terms send NUL for it
+        Ctrl(ch)                            => return format!("ctrl-{}", ch),
+        CtrlAlt(ch)                         => return format!("ctrl-alt-{}", ch),
+        Alt(ch) if ch.is_ascii_uppercase()  => return format!("alt-shift-{}", ch.to_ascii_lowercase()),
+        Alt(ch)                             => return format!("alt-{}", ch),
+        Char(' ')                           => "space",
+        Char(ch)                            => return ch.to_string(),
+        F(num)                              => return format!("f{}", num),
+        ESC                                 => "esc",
+        Tab                                 => "tab",
+        Enter                               => "enter",
+        BackTab                             => "btab",
+        Backspace                           => "bspace",
+        Delete                              => "del",
+        PageUp                              => "pgup",
+        PageDown                            => "pgdn",
+        Up                                  => "up",
+        Down                                => "down",
+        Left                                => "left",
+        Right                               => "right",
+        Home                                => "home",
+        End                                 => "end",
+        ShiftUp                             => "shift-up",
+        ShiftDown                           => "shift-down",
+        ShiftLeft                           => "shift-left",
+        ShiftRight                          => "shift-right",
+        AltBackTab                          => "alt-btab",
+        AltBackspace                        => "alt-bspace",
+        AltPageUp                           => "alt-pgup",
+        AltPageDown                         => "alt-pgdn",
+        AltUp                               => "alt-up",
+        AltDown                             => "alt-down",
+        AltLeft                             => "alt-left",
+        AltRight                            => "alt-right",
+        AltHome                             => "alt-home",
+        AltEnd                              => "alt-end",
+        AltShiftUp                          => "alt-shift-up",
+        AltShiftDown                        => "alt-shift-down",
+        AltShiftLeft                        => "alt-shift-left",
+        AltShiftRight                       => "alt-shift-right",
+        AltEnter                            => "alt-enter",
+        AltTab                              => "alt-tab",
+        ref key                             => return format!("{:?}", key),
+    };
+    name.to_string()
+}
+
+/// the name of the action as in `--bind`, e.g. `EvActPageDown(1)` => `page-down(1)`, the (default)
+/// arguments are kept. The events that are not bindable are shown as they are.
+fn action_name(event: &Event, custom_labels: &[String]) -> String {
+    if let Event::EvActCustom(index) = event {
+        return match custom_labels.get(*index) {
+            Some(label) => format!("custom({}): {}", index, label),
+            None => format!("custom({})", index),
+        };
+    }
+
+    match (crate::event::action_name(event), crate::event::action_arg(event)) {
+        (Some(name), Some(arg)) => format!("{}({})", name, arg),
+        (Some(name), None) => name.to_string(),
+        (None, _) => format!("{:?}", event),
     }
 }
 
@@ -238,4 +585,119 @@ mod test {
             key_action[1]
         );
     }
+
+    #[test]
+    fn action_chain_should_be_bound_in_order() {
+        let mut input = Input::new();
+        input.parse_keymap("ctrl-a:select-all+accept");
+        assert_eq!(
+            Some(&vec![Event::EvActSelectAll, Event::EvActAccept(None)]),
+            input.keymap.get(&Key::Ctrl('a'))
+        );
+
+        assert_eq!(vec![Event::EvActAbort], parse_action_arg("abort"));
+        assert_eq!(
+            vec![Event::EvActToggle, Event::EvActDown(1), Event::EvActAccept(None)],
+            parse_action_arg("toggle+down+accept")
+        );
+        assert!(parse_action_arg("no-such-action").is_empty());
+
+        input.parse_keymap("ctrl-g:put(git )+accept");
+        assert_eq!(
+            Some(&vec![Event::EvActPut("git ".to_string()), Event::EvActAccept(None)]),
+            input.keymap.get(&Key::Ctrl('g'))
+        );
+    }
+
+    #[test]
+    fn composite_action_should_be_expanded() {
+        let mut input = Input::new();
+        input.parse_keymap("ctrl-t:toggle-up,ctrl-d:toggle-down+accept,ctrl-x:toggle+down");
+        assert_eq!(
+            Some(&vec![Event::EvActToggle, Event::EvActUp(1)]),
+            input.keymap.get(&Key::Ctrl('t'))
+        );
+        assert_eq!(
+            Some(&vec![Event::EvActToggle, Event::EvActDown(1), Event::EvActAccept(None)]),
+            input.keymap.get(&Key::Ctrl('d'))
+        );
+        assert_eq!(
+            Some(&vec![Event::EvActToggle, Event::EvActDown(1)]),
+            input.keymap.get(&Key::Ctrl('x'))
+        );
+    }
+
+    #[test]
+    fn typed_keymap_should_be_bound() {
+        let mut input = Input::new();
+        input.parse_keymap("ctrl-j:accept,ctrl-k:kill-line");
+        input.bind_keymap(
+            &Keymap::new()
+                .bind(Key::Ctrl('j'), Action::Down(2))
+                .bind_chain(Key::Ctrl('a'), vec![Action::SelectAll, Action::Accept(None)]),
+        );
+
+        assert_eq!(
+            vec![Event::EvActDown(2)],
+            input.translate_event(TermEvent::Key(Key::Ctrl('j')))
+        );
+        assert_eq!(
+            vec![Event::EvActKillLine],
+            input.translate_event(TermEvent::Key(Key::Ctrl('k')))
+        );
+        assert_eq!(
+            vec![Event::EvActSelectAll, Event::EvActAccept(None)],
+            input.translate_event(TermEvent::Key(Key::Ctrl('a')))
+        );
+    }
+
+    #[test]
+    fn custom_action_should_be_bound() {
+        let mut input = Input::new();
+        input.bind_keymap(&Keymap::new().bind(Key::Ctrl('x'), Action::Custom(1)));
+        input.parse_keymap("ctrl-y:custom(5)");
+
+        assert_eq!(
+            vec![Event::EvActCustom(1)],
+            input.translate_event(TermEvent::Key(Key::Ctrl('x')))
+        );
+        assert_eq!(
+            vec![Event::EvActCustom(5)],
+            input.translate_event(TermEvent::Key(Key::Ctrl('y')))
+        );
+    }
+
+    #[test]
+    fn test_describe_bindings() {
+        let mut input = Input { keymap: HashMap::new() };
+        input.parse_keymap("ctrl-j:down,alt-shift-a:accept(alt-a),f1:execute(less {})+abort,space:custom(0)");
+        input.parse_keymap("ctrl-v:custom(1),ctrl-d:delete-charEOF,?:show-help");
+
+        let labels = vec!["open".to_string()];
+        assert_eq!(
+            input.describe_bindings(&labels),
+            vec![
+                "?            show-help",
+                "alt-shift-a  accept(alt-a)",
+                "ctrl-d       delete-charEOF",
+                "ctrl-j       down(1)",
+                "ctrl-v       custom(1)",
+                "f1           execute(less {})+abort",
+                "space        custom(0): open",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_action_names_are_parsed_back() {
+        for (action, arg) in &[
+            ("page-down", Some("2")),
+            ("accept", None),
+            ("transform-query", Some("tr a b")),
+        ] {
+            let event = parse_event(action, arg.map(|arg| arg.to_string())).unwrap();
+            let name = action_name(&event, &[]);
+            assert_eq!(parse_action_arg(&name), vec![event]);
+        }
+    }
 }
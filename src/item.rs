@@ -1,16 +1,18 @@
 ///! An item is line of text that read from `find` command or stdin together with
 ///! the internal states, such as selected or not
 use std::borrow::Cow;
-use std::cmp::min;
+use std::cmp::{max, min};
+use std::collections::HashSet;
 use std::default::Default;
 use std::ops::Deref;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use regex::Regex;
+use tuikit::attr::Attr;
 
 use crate::ansi::{ANSIParser, AnsiString};
-use crate::field::{parse_matching_fields, parse_transform_fields, FieldRange};
+use crate::field::{parse_matching_fields, parse_output_fields, parse_transform_fields, FieldRange};
 use crate::spinlock::{SpinLock, SpinLockGuard};
 use crate::{ItemPreview, SkimItem};
 
@@ -32,6 +34,9 @@ pub struct DefaultSkimItem {
     /// `None` => that it is safe to output `text` directly
     orig_text: Option<String>,
 
+    /// The fields output once accepted (`accept_nth`), `None` => the whole output
+    accepted_output: Option<String>,
+
     /// The text that will be shown on screen and matched.
     text: AnsiString<'static>,
 
@@ -84,10 +89,79 @@ impl<'a> DefaultSkimItem {
 
         DefaultSkimItem {
             orig_text,
+            accepted_output: None,
             text,
             matching_ranges,
         }
     }
+
+    /// Limit the matching ranges to the first `prefix_chars` characters of the text, the rest
+    /// characters are shown but never matched.
+    pub fn match_prefix_chars(mut self, prefix_chars: usize) -> Self {
+        let text = self.text.stripped();
+        let limit = text
+            .char_indices()
+            .nth(prefix_chars)
+            .map(|(idx, _)| idx)
+            .unwrap_or(text.len());
+        self.matching_ranges = self
+            .matching_ranges
+            .iter()
+            .filter(|&&(start, _)| start < limit)
+            .map(|&(start, end)| (start, min(end, limit)))
+            .collect();
+        self
+    }
+
+    /// Never match the leading characters of the text (e.g. a status glyph), they are still shown
+    /// and output.
+    pub fn match_skip(mut self, skip: MatchSkip) -> Self {
+        let text = self.text.stripped();
+        let start = match skip {
+            MatchSkip::Chars(chars) => text.char_indices().nth(chars).map(|(idx, _)| idx).unwrap_or(text.len()),
+            MatchSkip::UntilSpace => text.find(' ').map(|idx| idx + 1).unwrap_or(0),
+        };
+        self.matching_ranges = self
+            .matching_ranges
+            .iter()
+            .filter(|&&(_, end)| end > start)
+            .map(|&(begin, end)| (max(begin, start), end))
+            .collect();
+        self
+    }
+}
+
+impl DefaultSkimItem {
+    /// the display of the item, e.g. for the items that are parsed on demand
+    pub fn to_display(&self) -> AnsiString<'static> {
+        self.text.clone()
+    }
+
+    /// Output only the given fields of the original line once accepted, the text shown and matched
+    /// is kept, as well as the output given to the commands and the preview.
+    pub fn output_fields(mut self, fields: &[FieldRange], delimiter: &Regex) -> Self {
+        self.accepted_output = Some(parse_output_fields(delimiter, &self.output(), fields));
+        self
+    }
+}
+
+/// The leading part of an item to exclude from matching
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MatchSkip {
+    /// the first N characters
+    Chars(usize),
+    /// the characters till the first space (inclusive), nothing if there is no space
+    UntilSpace,
+}
+
+impl MatchSkip {
+    /// "N" => the first N chars, "space" => till the first space
+    pub fn from_str(skip: &str) -> Option<Self> {
+        match skip {
+            "space" => Some(MatchSkip::UntilSpace),
+            chars => chars.parse().ok().map(MatchSkip::Chars),
+        }
+    }
 }
 
 impl SkimItem for DefaultSkimItem {
@@ -115,6 +189,13 @@ impl SkimItem for DefaultSkimItem {
         }
     }
 
+    fn accepted_output(&self) -> Cow<'_, str> {
+        match self.accepted_output {
+            Some(ref output) => Cow::Borrowed(output),
+            None => self.output(),
+        }
+    }
+
     fn get_matching_ranges(&self) -> Cow<[(usize, usize)]> {
         Cow::Borrowed(&self.matching_ranges)
     }
@@ -165,9 +246,41 @@ impl SkimItem for ItemWrapper {
         self.inner.output()
     }
 
+    fn accepted_output(&self) -> Cow<'_, str> {
+        self.inner.accepted_output()
+    }
+
     fn get_matching_ranges(&self) -> Cow<[(usize, usize)]> {
         self.inner.get_matching_ranges()
     }
+
+    fn external_rank(&self) -> i64 {
+        self.inner.external_rank()
+    }
+
+    fn icon(&self) -> Option<char> {
+        self.inner.icon()
+    }
+
+    fn row_style(&self) -> Option<Attr> {
+        self.inner.row_style()
+    }
+
+    fn source(&self) -> Option<Cow<'_, str>> {
+        self.inner.source()
+    }
+
+    fn actions(&self) -> Vec<&str> {
+        self.inner.actions()
+    }
+
+    fn rows(&self) -> usize {
+        self.inner.rows()
+    }
+
+    fn pinned(&self) -> bool {
+        self.inner.pinned()
+    }
 }
 
 //------------------------------------------------------------------------------
@@ -177,6 +290,7 @@ pub struct Rank {
     pub index: i64,
     pub begin: i64,
     pub end: i64,
+    pub external: i64,
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -282,6 +396,13 @@ impl ItemPool {
         self.taken.store(0, Ordering::SeqCst);
     }
 
+    /// the last `num` items taken (e.g. not matched as the time is up) are to be taken again
+    pub fn give_back(&self, num: usize) {
+        let _items = self.pool.lock();
+        let taken = self.taken.load(Ordering::SeqCst);
+        self.taken.store(taken.saturating_sub(num), Ordering::SeqCst);
+    }
+
     pub fn append(&self, mut items: Vec<Arc<ItemWrapper>>) {
         let mut pool = self.pool.lock();
         let mut header_items = self.reserved_items.lock();
@@ -297,6 +418,16 @@ impl ItemPool {
         self.length.store(pool.len(), Ordering::SeqCst);
     }
 
+    /// remove the items of `ids` (the header items are kept), the taken items stay taken
+    pub fn remove(&self, ids: &HashSet<ItemIndex>) {
+        let mut pool = self.pool.lock();
+        let taken = self.taken.load(Ordering::SeqCst);
+        let taken_removed = pool[..taken].iter().filter(|item| ids.contains(&item.get_id())).count();
+        pool.retain(|item| !ids.contains(&item.get_id()));
+        self.taken.store(taken - taken_removed, Ordering::SeqCst);
+        self.length.store(pool.len(), Ordering::SeqCst);
+    }
+
     pub fn take(&self) -> ItemPoolGuard<Arc<ItemWrapper>> {
         let guard = self.pool.lock();
         let taken = self.taken.swap(guard.len(), Ordering::SeqCst);
@@ -329,10 +460,12 @@ pub enum RankCriteria {
     Index,
     Begin,
     End,
+    External,
     NegScore,
     NegIndex,
     NegBegin,
     NegEnd,
+    NegExternal,
 }
 
 pub fn parse_criteria(text: &str) -> Option<RankCriteria> {
@@ -341,10 +474,94 @@ pub fn parse_criteria(text: &str) -> Option<RankCriteria> {
         "index" => Some(RankCriteria::Index),
         "begin" => Some(RankCriteria::Begin),
         "end" => Some(RankCriteria::End),
+        "external" => Some(RankCriteria::External),
         "-score" => Some(RankCriteria::NegScore),
         "-index" => Some(RankCriteria::NegIndex),
         "-begin" => Some(RankCriteria::NegBegin),
         "-end" => Some(RankCriteria::NegEnd),
+        "-external" => Some(RankCriteria::NegExternal),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::field::FieldRange::*;
+
+    #[test]
+    fn test_match_prefix_chars() {
+        let delimiter = Regex::new(",").unwrap();
+        let item = DefaultSkimItem::new("中华,人民,abc".to_string(), false, &[], &[], &delimiter);
+        assert_eq!(item.match_prefix_chars(4).get_matching_ranges().as_ref(), &[(0, 10)]);
+
+        let item = DefaultSkimItem::new("a,b,c".to_string(), false, &[], &[Single(3), Single(1)], &delimiter);
+        assert_eq!(item.match_prefix_chars(3).get_matching_ranges().as_ref(), &[(0, 2)]);
+
+        let item = DefaultSkimItem::new("abc".to_string(), false, &[], &[], &delimiter);
+        assert_eq!(item.match_prefix_chars(10).get_matching_ranges().as_ref(), &[(0, 3)]);
+    }
+
+    #[test]
+    fn test_output_fields() {
+        let delimiter = Regex::new(",").unwrap();
+        let item = DefaultSkimItem::new("a,b,c".to_string(), false, &[Single(2)], &[Single(1)], &delimiter);
+        let item = item.output_fields(&[Single(3), Single(1)], &delimiter);
+        assert_eq!(item.text(), "b,");
+        assert_eq!(item.get_matching_ranges().as_ref(), &[(0, 2)]);
+        assert_eq!(item.output(), "a,b,c");
+        assert_eq!(item.accepted_output(), "c,a");
+    }
+
+    #[test]
+    fn test_remove_from_pool() {
+        let pool = ItemPool::new();
+        let items = |range: std::ops::Range<u32>| {
+            range
+                .map(|idx| Arc::new(ItemWrapper::new(Arc::new(format!("{}", idx)), (0, idx))))
+                .collect::<Vec<_>>()
+        };
+        pool.append(items(0..3));
+        drop(pool.take());
+        pool.append(items(3..5));
+
+        let ids = vec![(0, 1), (0, 3)].into_iter().collect();
+        pool.remove(&ids);
+        assert_eq!(pool.len(), 3);
+
+        // only the ones appended after the take are left to take
+        let texts: Vec<_> = pool.take().iter().map(|item| item.text().into_owned()).collect();
+        assert_eq!(texts, vec!["4"]);
+    }
+
+    #[test]
+    fn test_match_skip() {
+        assert_eq!(MatchSkip::from_str("2"), Some(MatchSkip::Chars(2)));
+        assert_eq!(MatchSkip::from_str("space"), Some(MatchSkip::UntilSpace));
+        assert_eq!(MatchSkip::from_str("x"), None);
+
+        let delimiter = Regex::new(",").unwrap();
+        let item = DefaultSkimItem::new("* modified.rs".to_string(), false, &[], &[], &delimiter);
+        let item = item.match_skip(MatchSkip::UntilSpace);
+        assert_eq!(item.get_matching_ranges().as_ref(), &[(2, 13)]);
+        assert_eq!(item.output(), "* modified.rs");
+
+        let item = DefaultSkimItem::new("中a,bc".to_string(), false, &[], &[], &delimiter);
+        assert_eq!(
+            item.match_skip(MatchSkip::Chars(1)).get_matching_ranges().as_ref(),
+            &[(3, 7)]
+        );
+
+        let item = DefaultSkimItem::new("a,b,c".to_string(), false, &[], &[Single(1), Single(3)], &delimiter);
+        assert_eq!(
+            item.match_skip(MatchSkip::Chars(3)).get_matching_ranges().as_ref(),
+            &[(4, 5)]
+        );
+
+        let item = DefaultSkimItem::new("nospace".to_string(), false, &[], &[], &delimiter);
+        assert_eq!(
+            item.match_skip(MatchSkip::UntilSpace).get_matching_ranges().as_ref(),
+            &[(0, 7)]
+        );
+    }
+}
@@ -1,30 +1,47 @@
+use crate::ansi::AnsiString;
+use crate::diagnostics;
 use crate::field::FieldRange;
-use crate::item::DefaultSkimItem;
-use crate::{SkimItem, SkimItemReceiver, SkimItemSender, SkimOptions};
+use crate::item::{DefaultSkimItem, MatchSkip};
+use crate::util::expand_command;
+use crate::{CrLf, PresortKey, SkimItem, SkimItemReceiver, SkimItemSender, SkimOptions};
 use crossbeam::channel::{bounded, Receiver, Sender};
 use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
 use std::error::Error;
-use std::io::{BufRead, BufReader};
-use std::process::{Child, Command, Stdio};
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
-use std::thread;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::mem;
+use std::os::unix::fs::FileExt;
+use std::process::{self, Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 const CMD_CHANNEL_SIZE: usize = 1024;
 const ITEM_CHANNEL_SIZE: usize = 10240;
 const DELIMITER_STR: &str = r"[\t\n ]+";
 const READ_BUFFER_SIZE: usize = 1024;
+const SPILL_CACHE_SIZE: usize = 1024;
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct CollectorOption {
     pub use_ansi_color: bool,
     pub default_arg: String,
     pub transform_fields: Vec<FieldRange>,
     pub matching_fields: Vec<FieldRange>,
+    pub match_prefix_chars: Option<usize>,
+    pub match_skip: Option<MatchSkip>,
+    pub output_fields: Vec<FieldRange>,
     pub delimiter: Regex,
     pub replace_str: String,
     pub line_ending: u8,
+    pub crlf: CrLf,
+    pub trim: bool,
+    pub dedup: bool,
+    pub expand_command: bool,
+    pub memory_budget: Option<usize>, // the bytes of the lines kept in memory, the rest are spilled
+    pub presort_key: Option<PresortKey>, // the items are sent sorted by it once the input ends (if not interrupted)
 }
 
 impl Default for CollectorOption {
@@ -34,9 +51,18 @@ impl Default for CollectorOption {
             default_arg: String::new(),
             transform_fields: Vec::new(),
             matching_fields: Vec::new(),
+            match_prefix_chars: None,
+            match_skip: None,
+            output_fields: Vec::new(),
             delimiter: Regex::new(DELIMITER_STR).unwrap(),
             replace_str: "{}".to_string(),
             line_ending: b'\n',
+            crlf: CrLf::default(),
+            trim: false,
+            dedup: false,
+            expand_command: true,
+            memory_budget: None,
+            presort_key: None,
         }
     }
 }
@@ -71,9 +97,32 @@ impl CollectorOption {
                 .collect();
         }
 
+        if let Some(output_fields) = options.accept_nth {
+            self.output_fields = output_fields.split(',').filter_map(FieldRange::from_str).collect();
+        }
+
+        self.match_prefix_chars = options.match_prefix_chars;
+        self.match_skip = options.match_skip.and_then(MatchSkip::from_str);
+
         if options.read0 {
             self.line_ending = b'\0';
         }
+        self.crlf = options.crlf;
+
+        if options.trim {
+            self.trim = true;
+        }
+
+        if options.dedup {
+            self.dedup = true;
+        }
+
+        if options.no_expand {
+            self.expand_command = false;
+        }
+
+        self.memory_budget = options.memory_budget_bytes;
+        self.presort_key = options.presort_key.clone();
     }
 }
 
@@ -91,6 +140,9 @@ pub fn read_and_collect_from_command(
 ) -> (Receiver<Arc<dyn SkimItem>>, Sender<i32>) {
     let (command, mut source) = match input {
         CollectorInput::Pipe(pipe) => (None, pipe),
+        CollectorInput::Command(cmd) if option.expand_command => {
+            get_command_output(&expand_command(&cmd)).expect("command not found")
+        }
         CollectorInput::Command(cmd) => get_command_output(&cmd).expect("command not found"),
     };
 
@@ -100,14 +152,17 @@ pub fn read_and_collect_from_command(
     let started = Arc::new(AtomicBool::new(false));
     let started_clone = started.clone();
     let components_to_stop_clone = components_to_stop.clone();
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupted_clone = interrupted.clone();
     // listening to close signal and kill command if needed
-    thread::spawn(move || {
+    diagnostics::spawn(move || {
         debug!("collector: command killer start");
         components_to_stop_clone.fetch_add(1, Ordering::SeqCst);
         started_clone.store(true, Ordering::SeqCst); // notify parent that it is started
 
         let _ = rx_interrupt.recv(); // block waiting
-                                     // clean up resources
+        interrupted_clone.store(true, Ordering::SeqCst);
+        // clean up resources
         if let Some(mut x) = command {
             let _ = x.kill();
             let _ = x.wait();
@@ -124,12 +179,22 @@ pub fn read_and_collect_from_command(
     let started = Arc::new(AtomicBool::new(false));
     let started_clone = started.clone();
     let tx_interrupt_clone = tx_interrupt.clone();
-    thread::spawn(move || {
+    diagnostics::spawn(move || {
         debug!("collector: command collector start");
         components_to_stop.fetch_add(1, Ordering::SeqCst);
         started_clone.store(true, Ordering::SeqCst); // notify parent that it is started
 
-        let opt = option;
+        let opt = Arc::new(option);
+        // the texts of the items sent, to suppress the duplicates. Note that all the unique texts
+        // are kept till the command exits
+        let mut seen = HashSet::new();
+        // the lines read after the memory budget is used up are spilled to the file
+        let mut memory_used = 0;
+        let mut spill_file: Option<Arc<SpillFile>> = None;
+        let mut spill_failed = false;
+        // with `presort_key`, the items are held with their keys till the input ends, they are
+        // dropped once interrupted (e.g. by a reload) as they are stale
+        let mut presorted = Vec::new();
         // set the proper run number
         loop {
             let mut buffer = Vec::with_capacity(READ_BUFFER_SIZE);
@@ -140,24 +205,50 @@ pub fn read_and_collect_from_command(
                         break;
                     }
 
-                    if buffer.ends_with(&[b'\r', b'\n']) {
-                        buffer.pop();
-                        buffer.pop();
-                    } else if buffer.ends_with(&[b'\n']) || buffer.ends_with(&[b'\0']) {
-                        buffer.pop();
+                    trim_line_ending(&mut buffer, opt.line_ending, opt.crlf);
+                    let line = String::from_utf8_lossy(&buffer);
+                    let line = if opt.trim { line.trim() } else { &*line }.to_string();
+
+                    let over_budget = opt.memory_budget.map(|budget| memory_used >= budget).unwrap_or(false);
+                    if over_budget && spill_file.is_none() && !spill_failed {
+                        match SpillFile::create() {
+                            Ok(file) => spill_file = Some(Arc::new(file)),
+                            Err(err) => {
+                                warn!("collector: failed to create the spill file, keep in memory: {}", err);
+                                spill_failed = true;
+                            }
+                        }
                     }
+                    let line_to_spill = spill_file.as_ref().filter(|_| over_budget).map(|_| line.clone());
 
-                    let line = String::from_utf8_lossy(&buffer).to_string();
+                    let raw_item = build_item(line, &opt);
+                    if opt.dedup && !seen.insert(raw_item.text().into_owned()) {
+                        continue;
+                    }
 
-                    let raw_item = DefaultSkimItem::new(
-                        line,
-                        opt.use_ansi_color,
-                        &opt.transform_fields,
-                        &opt.matching_fields,
-                        &opt.delimiter,
-                    );
+                    let spilled = line_to_spill.and_then(|line| {
+                        let spill_file = spill_file.clone()?;
+                        SpilledItem::spill(spill_file, &line, opt.clone())
+                            .map_err(|err| warn!("collector: failed to spill the line, keep in memory: {}", err))
+                            .ok()
+                    });
+                    let item: Arc<dyn SkimItem> = match spilled {
+                        Some(spilled) => Arc::new(spilled),
+                        None => {
+                            memory_used += estimate_memory(&raw_item);
+                            Arc::new(raw_item)
+                        }
+                    };
+
+                    if let Some(ref presort_key) = opt.presort_key {
+                        if interrupted.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        presorted.push((presort_key(item.as_ref()), item));
+                        continue;
+                    }
 
-                    match tx_item.send(Arc::new(raw_item)) {
+                    match tx_item.send(item) {
                         Ok(_) => {}
                         Err(_) => {
                             debug!("collector: failed to send item, quit");
@@ -169,6 +260,26 @@ pub fn read_and_collect_from_command(
             }
         }
 
+        if interrupted.load(Ordering::SeqCst) {
+            presorted.clear();
+        }
+
+        // the sort is stable, the items of the same key are kept in the order read, and the index
+        // (the last tiebreak) follows the sorted order
+        presorted.sort_by(|(key_a, _), (key_b, _)| key_a.cmp(key_b));
+        for (_, item) in presorted {
+            if tx_item.send(item).is_err() {
+                debug!("collector: failed to send item, quit");
+                break;
+            }
+        }
+
+        if let Some(spill_file) = spill_file {
+            if let Err(err) = spill_file.flush() {
+                error!("collector: failed to flush the spill file: {}", err);
+            }
+        }
+
         let _ = tx_interrupt_clone.send(1); // ensure the waiting thread will exit
         components_to_stop.fetch_sub(1, Ordering::SeqCst);
         debug!("collector: command collector stop");
@@ -181,6 +292,198 @@ pub fn read_and_collect_from_command(
     (rx_item, tx_interrupt)
 }
 
+/// strip the ending of the line read, and the `\r` before a `\n` unless it is kept
+fn trim_line_ending(buffer: &mut Vec<u8>, line_ending: u8, crlf: CrLf) {
+    let newline = buffer.ends_with(&[b'\n']);
+    if newline || buffer.ends_with(&[b'\0']) {
+        buffer.pop();
+    }
+    if newline && line_ending == b'\n' && crlf == CrLf::Strip && buffer.ends_with(&[b'\r']) {
+        buffer.pop();
+    }
+}
+
+fn build_item(line: String, opt: &CollectorOption) -> DefaultSkimItem {
+    let raw_item = DefaultSkimItem::new(
+        line,
+        opt.use_ansi_color,
+        &opt.transform_fields,
+        &opt.matching_fields,
+        &opt.delimiter,
+    );
+    let raw_item = match opt.match_prefix_chars {
+        Some(prefix_chars) => raw_item.match_prefix_chars(prefix_chars),
+        None => raw_item,
+    };
+    let raw_item = match opt.match_skip {
+        Some(skip) => raw_item.match_skip(skip),
+        None => raw_item,
+    };
+    if opt.output_fields.is_empty() {
+        raw_item
+    } else {
+        raw_item.output_fields(&opt.output_fields, &opt.delimiter)
+    }
+}
+
+/// roughly the bytes an item takes: its texts and the fixed part
+fn estimate_memory(item: &DefaultSkimItem) -> usize {
+    let output = match item.output() {
+        Cow::Owned(output) => output.len(),
+        Cow::Borrowed(_) => 0,
+    };
+    // the fields of `accept_nth` are kept apart from the line
+    let accepted_output = item.accepted_output();
+    let accepted_output = if accepted_output != item.output() {
+        accepted_output.len()
+    } else {
+        0
+    };
+    mem::size_of::<DefaultSkimItem>() + item.text().len() + output + accepted_output
+}
+
+//------------------------------------------------------------------------------
+// spill to disk (`--memory-budget`)
+
+/// The file of the spilled lines, appended by the collector and read by the matcher. It is removed
+/// once created, so that it is gone with skim.
+struct SpillFile {
+    writer: Mutex<BufWriter<File>>,
+    reader: File,
+    written: AtomicU64,
+    flushed: AtomicU64,
+    // the items read back lately by their offsets, so that e.g. an item drawn is read and parsed
+    // once for its display and matching ranges
+    decoded: Mutex<DecodedItems>,
+}
+
+/// the items read back by their offsets, and the offsets in the order read
+type DecodedItems = (HashMap<u64, Arc<DefaultSkimItem>>, VecDeque<u64>);
+
+impl SpillFile {
+    fn create() -> io::Result<Self> {
+        lazy_static! {
+            static ref NUM_CREATED: AtomicUsize = AtomicUsize::new(0);
+        }
+
+        let num = NUM_CREATED.fetch_add(1, Ordering::SeqCst);
+        let path = env::temp_dir().join(format!("skim-spill-{}-{}", process::id(), num));
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+        let _ = fs::remove_file(&path);
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(file.try_clone()?)),
+            reader: file,
+            written: AtomicU64::new(0),
+            flushed: AtomicU64::new(0),
+            decoded: Mutex::new((HashMap::new(), VecDeque::new())),
+        })
+    }
+
+    /// append the line, return its offset in the file
+    fn append(&self, line: &str) -> io::Result<u64> {
+        let mut writer = self.writer.lock().expect("spill file: failed to lock the writer");
+        writer.write_all(line.as_bytes())?;
+        Ok(self.written.fetch_add(line.len() as u64, Ordering::SeqCst))
+    }
+
+    /// write out the lines appended, the collector does it once the input ends, before that the
+    /// lines beyond the ones flushed are flushed when read
+    fn flush(&self) -> io::Result<()> {
+        let mut writer = self.writer.lock().expect("spill file: failed to lock the writer");
+        writer.flush()?;
+        self.flushed
+            .store(self.written.load(Ordering::SeqCst), Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn read(&self, offset: u64, len: usize) -> io::Result<String> {
+        if offset + len as u64 > self.flushed.load(Ordering::SeqCst) {
+            self.flush()?;
+        }
+
+        let mut buffer = vec![0; len];
+        self.reader.read_exact_at(&mut buffer, offset)?;
+        String::from_utf8(buffer).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn cached(&self, offset: u64) -> Option<Arc<DefaultSkimItem>> {
+        let decoded = self.decoded.lock().expect("spill file: failed to lock the cache");
+        decoded.0.get(&offset).cloned()
+    }
+
+    /// keep the item read back, the one kept longest is dropped beyond `SPILL_CACHE_SIZE`
+    fn cache(&self, offset: u64, item: Arc<DefaultSkimItem>) {
+        let mut decoded = self.decoded.lock().expect("spill file: failed to lock the cache");
+        let (items, order) = &mut *decoded;
+        if items.insert(offset, item).is_some() {
+            return;
+        }
+        order.push_back(offset);
+        if order.len() > SPILL_CACHE_SIZE {
+            if let Some(oldest) = order.pop_front() {
+                items.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// An item whose line is kept in the spill file, it is read back and parsed (by the options of the
+/// collector) when accessed, e.g. matched, the items read lately are kept by the spill file.
+struct SpilledItem {
+    file: Arc<SpillFile>,
+    offset: u64,
+    len: usize,
+    option: Arc<CollectorOption>,
+}
+
+impl SpilledItem {
+    fn spill(file: Arc<SpillFile>, line: &str, option: Arc<CollectorOption>) -> io::Result<Self> {
+        let offset = file.append(line)?;
+        Ok(Self {
+            file,
+            offset,
+            len: line.len(),
+            option,
+        })
+    }
+
+    fn load(&self) -> Arc<DefaultSkimItem> {
+        if let Some(item) = self.file.cached(self.offset) {
+            return item;
+        }
+
+        let line = self.file.read(self.offset, self.len).unwrap_or_else(|err| {
+            error!("collector: failed to read the spilled line: {}", err);
+            String::new()
+        });
+        let item = Arc::new(build_item(line, &self.option));
+        self.file.cache(self.offset, item.clone());
+        item
+    }
+}
+
+impl SkimItem for SpilledItem {
+    fn display(&self) -> Cow<'_, AnsiString<'_>> {
+        Cow::Owned(self.load().to_display())
+    }
+
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Owned(self.load().text().into_owned())
+    }
+
+    fn output(&self) -> Cow<'_, str> {
+        Cow::Owned(self.load().output().into_owned())
+    }
+
+    fn accepted_output(&self) -> Cow<'_, str> {
+        Cow::Owned(self.load().accepted_output().into_owned())
+    }
+
+    fn get_matching_ranges(&self) -> Cow<'_, [(usize, usize)]> {
+        Cow::Owned(self.load().get_matching_ranges().into_owned())
+    }
+}
+
 type CommandOutput = (Option<Child>, Box<dyn BufRead + Send>);
 fn get_command_output(cmd: &str) -> Result<CommandOutput, Box<dyn Error>> {
     let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
@@ -204,6 +507,7 @@ fn get_command_output(cmd: &str) -> Result<CommandOutput, Box<dyn Error>> {
 pub struct SkimItemReader {
     buf_size: usize,
     line_ending: u8,
+    crlf: CrLf,
 }
 
 impl Default for SkimItemReader {
@@ -211,6 +515,7 @@ impl Default for SkimItemReader {
         Self {
             buf_size: ITEM_CHANNEL_SIZE,
             line_ending: b'\n',
+            crlf: CrLf::default(),
         }
     }
 }
@@ -225,6 +530,11 @@ impl SkimItemReader {
         self.line_ending = line_ending;
         self
     }
+
+    pub fn crlf(mut self, crlf: CrLf) -> Self {
+        self.crlf = crlf;
+        self
+    }
 }
 
 impl SkimItemReader {
@@ -232,7 +542,8 @@ impl SkimItemReader {
     pub fn of_bufread(&self, mut source: impl BufRead + Send + 'static) -> SkimItemReceiver {
         let (tx_item, rx_item): (SkimItemSender, SkimItemReceiver) = bounded(self.buf_size);
         let line_ending = self.line_ending;
-        thread::spawn(move || {
+        let crlf = self.crlf;
+        diagnostics::spawn(move || {
             let mut buffer = Vec::with_capacity(1024);
             loop {
                 buffer.clear();
@@ -243,12 +554,7 @@ impl SkimItemReader {
                             break;
                         }
 
-                        if buffer.ends_with(&[b'\r', b'\n']) {
-                            buffer.pop();
-                            buffer.pop();
-                        } else if buffer.ends_with(&[b'\n']) || buffer.ends_with(&[b'\0']) {
-                            buffer.pop();
-                        }
+                        trim_line_ending(&mut buffer, line_ending, crlf);
 
                         let string = String::from_utf8_lossy(&buffer);
                         let result = tx_item.send(Arc::new(string.into_owned()));
@@ -263,3 +569,101 @@ impl SkimItemReader {
         rx_item
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_spill_over_memory_budget() {
+        let option = CollectorOption {
+            memory_budget: Some(1),
+            ..CollectorOption::default()
+        };
+        let input = CollectorInput::Pipe(Box::new(Cursor::new("apple\nbanana\ncherry\n")));
+        let (rx_item, _tx_interrupt) = read_and_collect_from_command(Arc::new(AtomicUsize::new(0)), input, option);
+
+        let items: Vec<_> = rx_item.iter().collect();
+        let texts: Vec<_> = items.iter().map(|item| item.text().into_owned()).collect();
+        assert_eq!(texts, vec!["apple", "banana", "cherry"]);
+        // the first line uses up the budget, the rest are spilled
+        let spilled: Vec<_> = items
+            .iter()
+            .map(|item| (**item).as_any().downcast_ref::<SpilledItem>().is_some())
+            .collect();
+        assert_eq!(spilled, vec![false, true, true]);
+    }
+
+    #[test]
+    fn test_crlf() {
+        let collect = |input: &'static str, option: CollectorOption| {
+            let input = CollectorInput::Pipe(Box::new(Cursor::new(input)));
+            let (rx_item, _tx_interrupt) = read_and_collect_from_command(Arc::new(AtomicUsize::new(0)), input, option);
+            rx_item
+                .iter()
+                .map(|item| item.output().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            collect("a.rs\r\nb.rs\r\n", CollectorOption::default()),
+            vec!["a.rs", "b.rs"]
+        );
+        // a `\r` not followed by `\n` (e.g. the last line without an ending) is kept
+        assert_eq!(
+            collect("a.rs\r\nb.rs\r", CollectorOption::default()),
+            vec!["a.rs", "b.rs\r"]
+        );
+        let keep = CollectorOption {
+            crlf: CrLf::Keep,
+            ..CollectorOption::default()
+        };
+        assert_eq!(collect("a.rs\r\nb.rs\n", keep), vec!["a.rs\r", "b.rs"]);
+        // the lines ended by `\0` are read as is
+        let read0 = CollectorOption {
+            line_ending: b'\0',
+            ..CollectorOption::default()
+        };
+        assert_eq!(collect("a.rs\r\0b.rs\0", read0), vec!["a.rs\r", "b.rs"]);
+    }
+
+    #[test]
+    fn test_presort_key() {
+        let presort_key: PresortKey = Arc::new(|item| item.text().split(' ').nth(1).unwrap_or("").to_string());
+        let option = CollectorOption {
+            presort_key: Some(presort_key),
+            ..CollectorOption::default()
+        };
+        let input = CollectorInput::Pipe(Box::new(Cursor::new("vim tool\nmain.rs src\nls tool\nlib.rs src\n")));
+        let (rx_item, _tx_interrupt) = read_and_collect_from_command(Arc::new(AtomicUsize::new(0)), input, option);
+
+        // the items of the same key keep the order read
+        let texts: Vec<_> = rx_item.iter().map(|item| item.text().into_owned()).collect();
+        assert_eq!(texts, vec!["main.rs src", "lib.rs src", "vim tool", "ls tool"]);
+    }
+
+    #[test]
+    fn test_presort_key_interrupted() {
+        let presort_key: PresortKey = Arc::new(|item| item.text().into_owned());
+        let option = CollectorOption {
+            presort_key: Some(presort_key),
+            ..CollectorOption::default()
+        };
+        let (mut writer, reader) = UnixStream::pair().unwrap();
+        let components_to_stop = Arc::new(AtomicUsize::new(0));
+        let input = CollectorInput::Pipe(Box::new(BufReader::new(reader)));
+        let (rx_item, tx_interrupt) = read_and_collect_from_command(components_to_stop.clone(), input, option);
+
+        writer.write_all(b"b\na\n").unwrap();
+        tx_interrupt.send(1).unwrap();
+        // the killer stops once it knows, the collector waits for the input
+        while components_to_stop.load(Ordering::SeqCst) > 1 {}
+        writer.write_all(b"c\n").unwrap();
+        drop(writer);
+
+        // the items held are stale, none is sent
+        assert_eq!(rx_item.iter().count(), 0);
+    }
+}
@@ -1,32 +1,70 @@
 #[macro_use]
 extern crate lazy_static;
-#[macro_use]
-extern crate log;
+// shadow the macros of `log`, so that the diagnostics could be routed by `SkimOptions::diagnostics`
+#[allow(unused_macros)]
+macro_rules! trace {
+    ($($arg:tt)+) => ($crate::diagnostics::emit(log::Level::Trace, module_path!(), format_args!($($arg)+)))
+}
+
+#[allow(unused_macros)]
+macro_rules! debug {
+    ($($arg:tt)+) => ($crate::diagnostics::emit(log::Level::Debug, module_path!(), format_args!($($arg)+)))
+}
+
+#[allow(unused_macros)]
+macro_rules! info {
+    ($($arg:tt)+) => ($crate::diagnostics::emit(log::Level::Info, module_path!(), format_args!($($arg)+)))
+}
+
+#[allow(unused_macros)]
+macro_rules! warn {
+    ($($arg:tt)+) => ($crate::diagnostics::emit(log::Level::Warn, module_path!(), format_args!($($arg)+)))
+}
+
+#[allow(unused_macros)]
+macro_rules! error {
+    ($($arg:tt)+) => ($crate::diagnostics::emit(log::Level::Error, module_path!(), format_args!($($arg)+)))
+}
 
 use std::any::Any;
 use std::borrow::Cow;
+use std::error::Error;
 use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::channel;
 use std::sync::Arc;
-use std::thread;
 
 use crossbeam::channel::{Receiver, Sender};
 use tuikit::prelude::{Event as TermEvent, *};
+#[cfg(feature = "test-render")]
+use unicode_width::UnicodeWidthChar;
 
 pub use crate::ansi::AnsiString;
+pub use crate::diagnostics::DiagnosticsCallback;
 pub use crate::engine::fuzzy::FuzzyAlgorithm;
+pub use crate::engine::scoring::ScoreFn;
+pub use crate::engine::transform::MatchTransform;
 use crate::event::{EventReceiver, EventSender};
-pub use crate::item::{ItemWrapper, MatchedItem};
+pub use crate::input::{Action, ActionContext, CustomAction, Keymap};
+pub use crate::item::{ItemWrapper, MatchedItem, MatchedRange, Rank};
+pub use crate::matcher::truncate_query;
 use crate::model::Model;
 pub use crate::options::SkimOptions;
-pub use crate::output::SkimOutput;
+pub use crate::output::{FinalOutputTransform, OutputTransform, OutputWriter, SkimOutput};
 use crate::reader::Reader;
+use crate::recorder::{read_recorded_events, EventRecorder};
+pub use crate::selection::compare_matched_items;
+pub use crate::theme::ColorScheme;
 
+mod action_menu;
 mod ansi;
+mod diagnostics;
 mod engine;
 mod event;
 mod field;
 mod header;
+mod help;
 mod input;
 mod item;
 mod item_collector;
@@ -39,6 +77,7 @@ pub mod prelude;
 mod previewer;
 mod query;
 mod reader;
+mod recorder;
 mod selection;
 mod spinlock;
 mod theme;
@@ -125,11 +164,62 @@ pub trait SkimItem: AsAny + Send + Sync + 'static {
         self.text()
     }
 
+    /// The output of the item once accepted (e.g. only some fields of it by `accept_nth`), default
+    /// to `output()`. The commands (`{}`) and the preview are still given `output()`.
+    fn accepted_output(&self) -> Cow<'_, str> {
+        self.output()
+    }
+
     /// we could limit the matching ranges of the `get_text` of the item.
     /// providing (start_byte, end_byte) of the range
     fn get_matching_ranges(&self) -> Cow<[(usize, usize)]> {
         Cow::Owned(vec![(0, self.display().stripped().len())])
     }
+
+    /// The rank given by an external ranker, smaller is better. Only used by the sort criteria
+    /// `external`, e.g. `--tiebreak=external,score` sorts by it and breaks the ties by fuzzy
+    /// score, while `--tiebreak=score,external` does it the other way around.
+    fn external_rank(&self) -> i64 {
+        0
+    }
+
+    /// An icon (e.g. a nerd-font glyph of the file type) drawn in a gutter before the display
+    /// text, it is neither matched nor output.
+    fn icon(&self) -> Option<char> {
+        None
+    }
+
+    /// The base style of the whole row of the item (e.g. a background tinted by its category),
+    /// the highlighting of the matches and the style of the cursor line are drawn over it.
+    fn row_style(&self) -> Option<Attr> {
+        None
+    }
+
+    /// The label of the source the item comes from (e.g. "files" or "recent"), the info line shows
+    /// the number of matches per source if the items come from more than one.
+    fn source(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+
+    /// The actions that apply to the item (e.g. "open", "edit", "delete"), listed by the
+    /// `action-menu` action to accept the item with the one chosen (`SkimOutput::item_action`).
+    fn actions(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// The number of rows the item takes on the list (e.g. the subject and the body of a commit),
+    /// its display is split by `\n` into the rows. The rows are one item to the cursor and the
+    /// selection, and the text is still matched as a whole. Ignored by the grid layout.
+    fn rows(&self) -> usize {
+        1
+    }
+
+    /// Whether the item is pinned (e.g. a favorite), the pinned items that match the query are
+    /// listed above the others regardless of their rank, separated by a line from them, and stay
+    /// on the top rows while the others scroll.
+    fn pinned(&self) -> bool {
+        false
+    }
 }
 
 impl<T: AsRef<str> + Send + Sync + 'static> SkimItem for T {
@@ -172,6 +262,46 @@ impl Default for CaseMatching {
     }
 }
 
+impl CaseMatching {
+    /// the next one of `toggle-case`: respect -> ignore -> smart -> respect
+    pub fn rotate(self) -> Self {
+        match self {
+            CaseMatching::Respect => CaseMatching::Ignore,
+            CaseMatching::Ignore => CaseMatching::Smart,
+            CaseMatching::Smart => CaseMatching::Respect,
+        }
+    }
+}
+
+/// Fold the case of the query (but not the items) before it is passed to the engines, e.g. with
+/// `Lower` and `CaseMatching::Respect`, an uppercase char in the items could never be matched.
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+pub enum QueryCase {
+    #[default]
+    AsIs,
+    Lower,
+    Upper,
+}
+
+impl QueryCase {
+    pub fn apply<'a>(&self, query: &'a str) -> Cow<'a, str> {
+        match self {
+            QueryCase::AsIs => Cow::Borrowed(query),
+            QueryCase::Lower => Cow::Owned(query.to_lowercase()),
+            QueryCase::Upper => Cow::Owned(query.to_uppercase()),
+        }
+    }
+}
+
+/// The `\r` before the `\n` ending the input lines (e.g. by Windows tools), stripped unless kept. It
+/// does not apply to the lines ended by `\0` (`read0`).
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+pub enum CrLf {
+    #[default]
+    Strip,
+    Keep,
+}
+
 pub trait MatchEngine: Sync + Send + Display {
     fn match_item(&self, item: Arc<ItemWrapper>) -> Option<MatchedItem>;
 }
@@ -184,13 +314,138 @@ pub trait MatchEngineFactory {
 }
 
 //------------------------------------------------------------------------------
+const TTY_PATH: &str = "/dev/tty";
+
+/// skim draws on and reads keys from the controlling terminal, check that it could be opened
+fn check_tty(path: &str) -> std::result::Result<(), Box<dyn Error>> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map(|_| ())
+        .map_err(|err| format!("no terminal available ({}: {})", path, err).into())
+}
+
 pub type SkimItemSender = Sender<Arc<dyn SkimItem>>;
 pub type SkimItemReceiver = Receiver<Arc<dyn SkimItem>>;
+/// A test on items, e.g. to find the item to put the cursor on
+pub type ItemPredicate = Arc<dyn Fn(&dyn SkimItem) -> bool + Send + Sync>;
+/// Notified of an item, e.g. the one removed by `delete-item`
+pub type ItemCallback = Arc<dyn Fn(Arc<dyn SkimItem>) + Send + Sync>;
+/// Validates the items about to be accepted, `Err` with the message to show cancels the accept, see
+/// `SkimOptions::on_accept_attempt`
+pub type AcceptValidator = Arc<dyn Fn(&[Arc<dyn SkimItem>]) -> std::result::Result<(), String> + Send + Sync>;
+/// Gives the preview of an item by a future (e.g. fetched from a network service), see
+/// `SkimOptions::async_preview`
+#[cfg(feature = "async-preview")]
+pub type AsyncPreview = Arc<dyn Fn(Arc<dyn SkimItem>) -> futures::future::BoxFuture<'static, String> + Send + Sync>;
+/// The key to sort the items read by before matching, see `SkimOptions::presort_key`. The items are
+/// held till the input ends, so it is for the finite inputs only, and nothing is sent once the input
+/// is interrupted (e.g. by a reload)
+pub type PresortKey = Arc<dyn Fn(&dyn SkimItem) -> String + Send + Sync>;
 
 pub struct Skim {}
 
 impl Skim {
+    /// Run skim, return `None` if it is aborted (e.g. by `ESC`) or it fails to start.
+    /// Use `try_run_with` to know why it fails.
     pub fn run_with(options: &SkimOptions, source: Option<SkimItemReceiver>) -> Option<SkimOutput> {
+        Skim::try_run_with(options, source).unwrap_or_else(|err| {
+            error!("skim: failed to run: {}", err);
+            None
+        })
+    }
+
+    /// Same as `run_with`, but return an error instead if skim could not start, e.g. there is no
+    /// controlling terminal (skim is invoked in a pipe or by a daemon).
+    pub fn try_run_with(
+        options: &SkimOptions,
+        source: Option<SkimItemReceiver>,
+    ) -> std::result::Result<Option<SkimOutput>, Box<dyn Error>> {
+        Skim::run_model(options, source, None)
+    }
+
+    /// Show the items matched and ranked elsewhere (sorted by their ranks with `tiebreak`), e.g.
+    /// to select among the results of an external search. The highlighting is given by their
+    /// `matched_range`. Nothing is read or matched, the query could still be edited and output.
+    pub fn run_with_matched(options: &SkimOptions, items: Vec<MatchedItem>) -> Option<SkimOutput> {
+        Skim::run_model(options, None, Some(items)).unwrap_or_else(|err| {
+            error!("skim: failed to run: {}", err);
+            None
+        })
+    }
+
+    /// Run skim in a `Term` created and managed by the caller, e.g. to embed it in a larger TUI.
+    /// Skim draws in `region` of the term (the whole term if `None`), the rest of the screen is
+    /// left as is. The height options are ignored, and mouse support is not enabled on the term
+    /// (enable it on the term if needed, `no_mouse` still makes skim ignore the mouse).
+    ///
+    /// While it runs, skim reads the events from the term in a thread of its own, so the caller
+    /// should not poll the term meanwhile (the events would be split between the two). Skim
+    /// stops reading before it returns, interrupting itself by sending `Event::User1` to the term;
+    /// the term is not paused, but its content is left unchanged, so redraw it.
+    pub fn run_in_term(
+        options: &SkimOptions,
+        source: Option<SkimItemReceiver>,
+        term: Arc<Term>,
+        region: Option<Rectangle>,
+    ) -> Option<SkimOutput> {
+        let prev_sink = diagnostics::set_sink(options.diagnostics.clone());
+        let ret = Skim::run_in(options, source, None, term, !options.no_mouse, region);
+        diagnostics::set_sink(prev_sink);
+        ret
+    }
+
+    /// Draw skim headless, into a `width` x `height` screen in memory instead of a terminal, e.g.
+    /// for the snapshot tests of a picker built on skim. The screen is the one drawn once the
+    /// items are all read and matched (against `query` of the options), and the preview (if any)
+    /// has shown its first output. No keys are read, and no tty is needed.
+    #[cfg(feature = "test-render")]
+    pub fn render_screen(
+        options: &SkimOptions,
+        source: Option<SkimItemReceiver>,
+        width: usize,
+        height: usize,
+    ) -> tuikit::screen::Screen {
+        let prev_sink = diagnostics::set_sink(options.diagnostics.clone());
+        let (tx, rx): (EventSender, EventReceiver) = channel();
+        let reader = Reader::with_options(options).source(source);
+        let screen = Model::headless(rx, tx, reader, width, height, options).render();
+        diagnostics::set_sink(prev_sink);
+        screen
+    }
+
+    /// Same as `render_screen`, but return the text of the screen, the rows are joined by `\n`
+    /// with their trailing spaces trimmed. The attributes (e.g. of the highlighted matches) are
+    /// kept by the cells of the screen only.
+    #[cfg(feature = "test-render")]
+    pub fn render_frame(
+        options: &SkimOptions,
+        source: Option<SkimItemReceiver>,
+        width: usize,
+        height: usize,
+    ) -> String {
+        let screen = Skim::render_screen(options, source, width, height);
+        let mut rows = vec![String::new(); screen.height()];
+        let mut wide_char_end = 0;
+        for (row, col, cell) in screen.iter_cell() {
+            // the column after a wide char is covered by it
+            if col == 0 || col >= wide_char_end {
+                rows[row].push(if cell.ch == '\0' { ' ' } else { cell.ch });
+                wide_char_end = col + cell.ch.width().unwrap_or(1);
+            }
+        }
+        let rows: Vec<&str> = rows.iter().map(|row| row.trim_end()).collect();
+        rows.join("\n")
+    }
+
+    fn run_model(
+        options: &SkimOptions,
+        source: Option<SkimItemReceiver>,
+        matched_items: Option<Vec<MatchedItem>>,
+    ) -> std::result::Result<Option<SkimOutput>, Box<dyn Error>> {
+        check_tty(TTY_PATH)?;
+
         let min_height = options
             .min_height
             .map(Skim::parse_height_string)
@@ -200,27 +455,87 @@ impl Skim {
             .map(Skim::parse_height_string)
             .expect("height should have default values");
 
+        // the diagnostics are routed to the callback while skim is running
+        let prev_sink = diagnostics::set_sink(options.diagnostics.clone());
+
+        let term = match Term::with_options(TermOptions::default().min_height(min_height).height(height)) {
+            Ok(term) => Arc::new(term),
+            Err(err) => {
+                diagnostics::set_sink(prev_sink);
+                return Err(format!("failed to initialize the terminal: {}", err).into());
+            }
+        };
+        let mouse_enabled = !options.no_mouse
+            && match term.enable_mouse_support() {
+                Ok(_) => true,
+                Err(err) => {
+                    warn!("skim: failed to enable mouse support, mouse is disabled: {}", err);
+                    false
+                }
+            };
+
+        let ret = Skim::run_in(options, source, matched_items, term.clone(), mouse_enabled, None);
+        let _ = term.pause();
+        diagnostics::set_sink(prev_sink);
+        Ok(ret)
+    }
+
+    /// run the model in the term till it quits, the input is read from the term meanwhile
+    fn run_in(
+        options: &SkimOptions,
+        source: Option<SkimItemReceiver>,
+        matched_items: Option<Vec<MatchedItem>>,
+        term: Arc<Term>,
+        mouse_enabled: bool,
+        region: Option<Rectangle>,
+    ) -> Option<SkimOutput> {
         let (tx, rx): (EventSender, EventReceiver) = channel();
-        let term = Arc::new(Term::with_options(TermOptions::default().min_height(min_height).height(height)).unwrap());
-        if !options.no_mouse {
-            let _ = term.enable_mouse_support();
-        }
 
         //------------------------------------------------------------------------------
         // input
         let mut input = input::Input::new();
+        // the cursor of the grid is moved by left/right, the query is still edited by ctrl-b/ctrl-f
+        if options.grid {
+            input.parse_keymaps(&["left:left,right:right"]);
+        }
         input.parse_keymaps(&options.bind);
+        input.bind_keymap(&options.keymap);
         input.parse_expect_keys(options.expect.as_ref().map(|x| &**x));
+        let help = input.describe_bindings(&options.custom_action_labels);
+
+        let mut recorder = options.record_events_file.and_then(|path| {
+            EventRecorder::create(path)
+                .map_err(|err| warn!("skim: failed to record the events to {}: {}", path, err))
+                .ok()
+        });
+        let replay_events = options
+            .replay_events_file
+            .and_then(|path| {
+                read_recorded_events(path)
+                    .map_err(|err| warn!("skim: failed to replay the events: {}", err))
+                    .ok()
+            })
+            .unwrap_or_default();
+        // the replay is instead of the live keys, they are dropped till it ends
+        let replaying = Arc::new(AtomicBool::new(!replay_events.is_empty()));
 
         let tx_clone = tx.clone();
         let term_clone = term.clone();
-        let input_thread = thread::spawn(move || loop {
+        let replaying_clone = replaying.clone();
+        let input_thread = diagnostics::spawn(move || loop {
             if let Ok(key) = term_clone.poll_event() {
                 if key == TermEvent::User1 {
                     break;
                 }
 
+                if replaying_clone.load(Ordering::SeqCst) && matches!(key, TermEvent::Key(_)) {
+                    continue;
+                }
+
                 for ev in input.translate_event(key).into_iter() {
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.record(&ev);
+                    }
                     let _ = tx_clone.send(ev);
                 }
             }
@@ -233,11 +548,18 @@ impl Skim {
 
         //------------------------------------------------------------------------------
         // model + previewer
-        let mut model = Model::new(rx, tx, reader, term.clone(), &options);
+        let model = Model::new(rx, tx, reader, term.clone(), &options)
+            .mouse_enabled(mouse_enabled)
+            .region(region)
+            .help(help)
+            .replay_events(replay_events, replaying);
+        let mut model = match matched_items {
+            Some(items) => model.pre_matched_items(items),
+            None => model,
+        };
         let ret = model.start();
         let _ = term.send_event(TermEvent::User1); // interrupt the input thread
         let _ = input_thread.join();
-        let _ = term.pause();
         ret
     }
 
@@ -251,3 +573,68 @@ impl Skim {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_query_case() {
+        assert_eq!(QueryCase::default().apply("aBc"), "aBc");
+        assert_eq!(QueryCase::Lower.apply("aBc"), "abc");
+        assert_eq!(QueryCase::Upper.apply("aBc"), "ABC");
+    }
+
+    #[test]
+    fn test_case_matching_rotate() {
+        let case = CaseMatching::default();
+        assert_eq!(case.rotate(), CaseMatching::Respect);
+        assert_eq!(case.rotate().rotate(), CaseMatching::Ignore);
+        assert_eq!(case.rotate().rotate().rotate(), case);
+    }
+
+    #[cfg(feature = "test-render")]
+    #[test]
+    fn test_render_frame() {
+        let (tx, rx) = crossbeam::channel::unbounded::<Arc<dyn SkimItem>>();
+        for item in &["apple", "banana", "cherry"] {
+            let _ = tx.send(Arc::new(item.to_string()));
+        }
+        drop(tx);
+
+        let options = crate::options::SkimOptionsBuilder::default()
+            .query(Some("an"))
+            .layout("reverse")
+            .build()
+            .unwrap();
+        let frame = Skim::render_frame(&options, Some(rx), 20, 4);
+        let rows: Vec<&str> = frame.split('\n').collect();
+        assert_eq!(rows[0], "> an");
+        assert!(rows[1].starts_with("  1/3"));
+        assert_eq!(rows[2], "> banana");
+        assert_eq!(rows[3], "");
+    }
+
+    #[cfg(feature = "test-render")]
+    #[test]
+    fn test_render_frame_with_preview() {
+        let (tx, rx) = crossbeam::channel::unbounded::<Arc<dyn SkimItem>>();
+        let _ = tx.send(Arc::new("apple".to_string()));
+        drop(tx);
+
+        let options = crate::options::SkimOptionsBuilder::default()
+            .layout("reverse")
+            .preview(Some("echo preview of {}"))
+            .preview_window(Some("down:2"))
+            .build()
+            .unwrap();
+        let frame = Skim::render_frame(&options, Some(rx), 30, 6);
+        assert!(frame.contains("preview of apple"));
+    }
+
+    #[test]
+    fn test_check_tty() {
+        let err = check_tty("/nonexistent/tty").unwrap_err();
+        assert!(err.to_string().starts_with("no terminal available (/nonexistent/tty: "));
+    }
+}
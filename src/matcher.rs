@@ -1,20 +1,31 @@
+use std::cmp::min;
+use std::mem;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::thread;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use rayon::prelude::*;
 
-use crate::item::{ItemPool, MatchedItem};
+use crate::diagnostics;
+use crate::item::{ItemPool, ItemWrapper, MatchedItem};
 use crate::spinlock::SpinLock;
-use crate::{CaseMatching, MatchEngineFactory};
+use crate::{CaseMatching, MatchEngineFactory, QueryCase};
 use std::rc::Rc;
 
+// with a budget, the time is checked between the chunks of items, a run matches one at least
+const BUDGET_CHUNK_SIZE: usize = 4096;
+
 //==============================================================================
 pub struct MatcherControl {
     stopped: Arc<AtomicBool>,
+    partial: Arc<AtomicBool>,
     processed: Arc<AtomicUsize>,
     matched: Arc<AtomicUsize>,
+    head_done: Arc<AtomicBool>,
+    // set under the lock of `items` when the tail is added to them
+    tail_done: Arc<AtomicBool>,
+    taken: usize,
     items: Arc<SpinLock<Vec<MatchedItem>>>,
     thread_matcher: JoinHandle<()>,
 }
@@ -24,8 +35,29 @@ impl MatcherControl {
         self.processed.load(Ordering::Relaxed)
     }
 
+    /// the number of matched items that are not yet taken by `take_head`
     pub fn get_num_matched(&self) -> usize {
-        self.matched.load(Ordering::Relaxed)
+        self.matched.load(Ordering::Relaxed) - self.taken
+    }
+
+    /// the matcher is done with the head and still matching the tail in the background
+    pub fn matching_tail(&self) -> bool {
+        self.head_done.load(Ordering::Relaxed) && !self.tail_done.load(Ordering::Relaxed) && !self.stopped()
+    }
+
+    /// take the items matched in the head once it is done, the rest are returned by `into_items`
+    pub fn take_head(&mut self) -> Option<Vec<MatchedItem>> {
+        if self.taken > 0 {
+            return None;
+        }
+
+        // checked under the lock, so that the head is not taken along with the tail
+        let mut items = self.items.lock();
+        if !self.matching_tail() {
+            return None;
+        }
+        self.taken = items.len();
+        Some(mem::take(&mut *items))
     }
 
     pub fn kill(self) {
@@ -37,6 +69,11 @@ impl MatcherControl {
         self.stopped.load(Ordering::Relaxed)
     }
 
+    /// the matcher stopped as its budget is used up, the items not matched are left in the pool
+    pub fn is_partial(&self) -> bool {
+        self.partial.load(Ordering::Relaxed)
+    }
+
     pub fn into_items(self) -> Arc<SpinLock<Vec<MatchedItem>>> {
         while !self.stopped.load(Ordering::Relaxed) {}
         self.items
@@ -47,6 +84,10 @@ impl MatcherControl {
 pub struct Matcher {
     engine_factory: Rc<dyn MatchEngineFactory>,
     case_matching: CaseMatching,
+    query_case: QueryCase,
+    max_query_length: Option<usize>,
+    parallel: bool,
+    budget: Option<Duration>,
 }
 
 impl Matcher {
@@ -54,6 +95,10 @@ impl Matcher {
         Self {
             engine_factory,
             case_matching: CaseMatching::default(),
+            query_case: QueryCase::default(),
+            max_query_length: None,
+            parallel: true,
+            budget: None,
         }
     }
 
@@ -62,14 +107,58 @@ impl Matcher {
         self
     }
 
+    /// change the case matching of the later runs, e.g. by `toggle-case`
+    pub fn set_case(&mut self, case_matching: CaseMatching) {
+        self.case_matching = case_matching;
+    }
+
+    pub fn query_case(mut self, query_case: QueryCase) -> Self {
+        self.query_case = query_case;
+        self
+    }
+
+    /// only the first `max_query_length` chars of the query are matched
+    pub fn max_query_length(mut self, max_query_length: Option<usize>) -> Self {
+        self.max_query_length = max_query_length;
+        self
+    }
+
+    /// match the items with rayon, otherwise one by one in the matcher thread
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// stop matching once the time is up, the items not matched yet are left to the next run
+    pub fn budget(mut self, budget: Option<Duration>) -> Self {
+        self.budget = budget;
+        self
+    }
+
     pub fn build(self) -> Self {
         self
     }
 
-    pub fn run<C>(&self, query: &str, item_pool: Arc<ItemPool>, callback: C) -> MatcherControl
+    /// how the query is parsed into the terms (the engines) to match the items, for debugging
+    pub fn describe(&self, query: &str) -> String {
+        let query = self.query_case.apply(query);
+        let query = truncate_query(&query, self.max_query_length);
+        self.engine_factory
+            .create_engine_with_case(query, self.case_matching)
+            .to_string()
+    }
+
+    /// Match the items one by one until `head_size` items are matched, so that the first screen
+    /// could be shown (see `MatcherControl::take_head`), then match the tail in the background.
+    /// With a budget, the items left when the time is up are given back to the pool (see
+    /// `MatcherControl::is_partial`), a chunk of them is matched at least so that each run makes some
+    /// progress.
+    pub fn run<C>(&self, query: &str, item_pool: Arc<ItemPool>, head_size: Option<usize>, callback: C) -> MatcherControl
     where
         C: Fn(Arc<SpinLock<Vec<MatchedItem>>>) + Send + 'static,
     {
+        let query = self.query_case.apply(query);
+        let query = truncate_query(&query, self.max_query_length);
         let matcher_engine = self.engine_factory.create_engine_with_case(query, self.case_matching);
         debug!("engine: {}", matcher_engine);
         let stopped = Arc::new(AtomicBool::new(false));
@@ -80,32 +169,92 @@ impl Matcher {
         let matched_clone = matched.clone();
         let matched_items = Arc::new(SpinLock::new(Vec::new()));
         let matched_items_clone = matched_items.clone();
+        let head_done = Arc::new(AtomicBool::new(false));
+        let head_done_clone = head_done.clone();
+        let tail_done = Arc::new(AtomicBool::new(false));
+        let tail_done_clone = tail_done.clone();
+        let partial = Arc::new(AtomicBool::new(false));
+        let partial_clone = partial.clone();
+        let parallel = self.parallel;
+        let budget = self.budget;
+        let started = Instant::now();
 
-        let thread_matcher = thread::spawn(move || {
+        let thread_matcher = diagnostics::spawn(move || {
             let items = item_pool.take();
 
-            // 1. use rayon for parallel
+            // 1. use rayon for parallel (unless disabled)
             // 2. return Err to skip iteration
             //    check https://doc.rust-lang.org/std/result/enum.Result.html#method.from_iter
 
-            let result: Result<Vec<_>, _> = items
-                .par_iter()
-                .filter_map(|item| {
-                    processed.fetch_add(1, Ordering::Relaxed);
-                    if stopped.load(Ordering::Relaxed) {
-                        Some(Err("matcher killed"))
-                    } else if let Some(item) = matcher_engine.match_item(item.clone()) {
-                        matched.fetch_add(1, Ordering::Relaxed);
-                        Some(Ok(item))
-                    } else {
-                        None
+            let match_item = |item: &Arc<ItemWrapper>| {
+                processed.fetch_add(1, Ordering::Relaxed);
+                if stopped.load(Ordering::Relaxed) {
+                    Some(Err("matcher killed"))
+                } else if let Some(item) = matcher_engine.match_item(item.clone()) {
+                    matched.fetch_add(1, Ordering::Relaxed);
+                    Some(Ok(item))
+                } else {
+                    None
+                }
+            };
+
+            let mut tail = &items[..];
+            if let Some(head_size) = head_size {
+                let mut head = Vec::new();
+                let mut killed = false;
+                while head.len() < head_size && !tail.is_empty() && !killed {
+                    match match_item(&tail[0]) {
+                        Some(Ok(item)) => head.push(item),
+                        Some(Err(_)) => killed = true,
+                        None => {}
                     }
-                })
-                .collect();
+                    tail = &tail[1..];
+                }
+
+                if !killed {
+                    *matched_items.lock() = head;
+                    head_done.store(true, Ordering::Relaxed);
+                }
+            }
+
+            let match_items = |items: &[Arc<ItemWrapper>]| -> Result<Vec<_>, _> {
+                if parallel {
+                    items.par_iter().filter_map(match_item).collect()
+                } else {
+                    items.iter().filter_map(match_item).collect()
+                }
+            };
 
-            if let Ok(items) = result {
+            // without a budget, the tail is matched at once
+            let mut matched_tail = Vec::new();
+            let mut killed = false;
+            let mut num_chunks = 0;
+            while !tail.is_empty() && !killed {
+                if num_chunks > 0 && budget.map(|budget| started.elapsed() >= budget).unwrap_or(false) {
+                    break;
+                }
+                num_chunks += 1;
+
+                let chunk_size = budget.map(|_| min(BUDGET_CHUNK_SIZE, tail.len())).unwrap_or(tail.len());
+                let (chunk, rest) = tail.split_at(chunk_size);
+                match match_items(chunk) {
+                    Ok(items) => matched_tail.extend(items),
+                    Err(_) => killed = true,
+                }
+                tail = rest;
+            }
+
+            if !killed {
                 let mut pool = matched_items.lock();
-                *pool = items;
+                pool.extend(matched_tail);
+                tail_done.store(true, Ordering::Relaxed);
+            }
+
+            let not_matched = tail.len();
+            drop(items);
+            if !killed && not_matched > 0 {
+                item_pool.give_back(not_matched);
+                partial.store(true, Ordering::Relaxed);
             }
 
             callback(matched_items.clone());
@@ -114,10 +263,162 @@ impl Matcher {
 
         MatcherControl {
             stopped: stopped_clone,
+            partial: partial_clone,
             matched: matched_clone,
+            head_done: head_done_clone,
+            tail_done: tail_done_clone,
+            taken: 0,
             processed: processed_clone,
             items: matched_items_clone,
             thread_matcher,
         }
     }
 }
+
+/// truncate the query to `max_len` chars (if any), the cost of matching grows with its length
+pub fn truncate_query(query: &str, max_len: Option<usize>) -> &str {
+    match max_len.and_then(|len| query.char_indices().nth(len)) {
+        Some((idx, _)) => &query[..idx],
+        None => query,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::factory::{AndOrEngineFactory, ExactOrFuzzyEngineFactory};
+    use crate::{AnsiString, SkimItem};
+    use std::borrow::Cow;
+
+    /// an item that is not matched till it is opened, to hold the matcher in the tail
+    struct GatedItem {
+        open: Arc<AtomicBool>,
+    }
+
+    impl SkimItem for GatedItem {
+        fn display(&self) -> Cow<'_, AnsiString<'_>> {
+            Cow::Owned("a5".into())
+        }
+
+        fn text(&self) -> Cow<'_, str> {
+            while !self.open.load(Ordering::SeqCst) {}
+            Cow::Borrowed("a5")
+        }
+    }
+
+    fn item_pool(items: Vec<Arc<dyn SkimItem>>) -> Arc<ItemPool> {
+        let pool = Arc::new(ItemPool::new());
+        pool.append(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(idx, item)| Arc::new(ItemWrapper::new(item, (0, idx as u32))))
+                .collect(),
+        );
+        pool
+    }
+
+    fn texts(items: &[MatchedItem]) -> Vec<String> {
+        items.iter().map(|matched| matched.item.text().into_owned()).collect()
+    }
+
+    #[test]
+    fn test_match_with_head() {
+        let open = Arc::new(AtomicBool::new(false));
+        let pool = item_pool(vec![
+            Arc::new("a1"),
+            Arc::new("b"),
+            Arc::new("a2"),
+            Arc::new("a3"),
+            Arc::new(GatedItem { open: open.clone() }),
+        ]);
+
+        let matcher = Matcher::builder(Rc::new(ExactOrFuzzyEngineFactory::builder().build())).parallel(false);
+        let mut ctrl = matcher.run("a", pool, Some(2), |_| {});
+        while !ctrl.matching_tail() {}
+
+        // the head is taken once, the tail is returned at the end
+        assert_eq!(texts(&ctrl.take_head().unwrap()), ["a1", "a2"]);
+        assert!(ctrl.take_head().is_none());
+        open.store(true, Ordering::SeqCst);
+        while !ctrl.stopped() {}
+        assert!(!ctrl.matching_tail());
+        assert_eq!(ctrl.get_num_matched(), 2);
+        assert_eq!(texts(&ctrl.into_items().lock()), ["a3", "a5"]);
+    }
+
+    #[test]
+    fn test_take_head_after_tail() {
+        let pool = item_pool(vec![Arc::new("a1"), Arc::new("a2"), Arc::new("a3"), Arc::new("a4")]);
+        let in_callback = Arc::new(AtomicBool::new(false));
+        let in_callback_clone = in_callback.clone();
+        let done = Arc::new(AtomicBool::new(false));
+        let done_clone = done.clone();
+
+        // the tail is added to the items, but the matcher is not stopped yet
+        let matcher = Matcher::builder(Rc::new(ExactOrFuzzyEngineFactory::builder().build())).parallel(false);
+        let mut ctrl = matcher.run("a", pool, Some(2), move |_| {
+            in_callback_clone.store(true, Ordering::SeqCst);
+            while !done_clone.load(Ordering::SeqCst) {}
+        });
+        while !in_callback.load(Ordering::SeqCst) {}
+
+        // the head is not taken, the whole result is returned at the end
+        assert!(!ctrl.matching_tail());
+        assert!(ctrl.take_head().is_none());
+        done.store(true, Ordering::SeqCst);
+        assert_eq!(ctrl.get_num_matched(), 4);
+        assert_eq!(ctrl.into_items().lock().len(), 4);
+    }
+
+    #[test]
+    fn test_match_with_budget() {
+        let num_items = BUDGET_CHUNK_SIZE + 10;
+        let pool = Arc::new(ItemPool::new());
+        pool.append(
+            (0..num_items as u32)
+                .map(|idx| Arc::new(ItemWrapper::new(Arc::new(format!("a{}", idx)), (0, idx))))
+                .collect(),
+        );
+
+        // the time is up already, still the first chunk is matched, the rest are left in the pool
+        let matcher = Matcher::builder(Rc::new(ExactOrFuzzyEngineFactory::builder().build()))
+            .budget(Some(Duration::from_millis(0)))
+            .parallel(false);
+        let ctrl = matcher.run("a", pool.clone(), None, |_| {});
+        while !ctrl.stopped() {}
+        assert!(ctrl.is_partial());
+        assert_eq!(ctrl.into_items().lock().len(), BUDGET_CHUNK_SIZE);
+        assert_eq!(pool.num_not_taken(), 10);
+
+        // the next run goes on with the rest
+        let ctrl = matcher.run("a", pool.clone(), None, |_| {});
+        while !ctrl.stopped() {}
+        assert!(!ctrl.is_partial());
+        assert_eq!(ctrl.into_items().lock().len(), 10);
+        assert_eq!(pool.num_not_taken(), 0);
+    }
+
+    #[test]
+    fn test_truncate_query() {
+        assert_eq!(truncate_query("abc", None), "abc");
+        assert_eq!(truncate_query("abc", Some(3)), "abc");
+        assert_eq!(truncate_query("中文abc", Some(3)), "中文a");
+        assert_eq!(truncate_query("abc", Some(0)), "");
+    }
+
+    #[test]
+    fn test_describe() {
+        let factory = AndOrEngineFactory::new(ExactOrFuzzyEngineFactory::builder().build());
+        let matcher = Matcher::builder(Rc::new(factory)).max_query_length(Some(10));
+        assert_eq!(
+            matcher.describe("src .rs$"),
+            r"(Or: (And: (Fuzzy: src), (Exact|(?i)\.rs$)))"
+        );
+        // only the matched prefix of the query is parsed
+        assert_eq!(
+            matcher.describe("a | b !tmp x"),
+            "(Or: (And: (Fuzzy: a)), (And: (Fuzzy: b), (Exact|!(?i)tmp)))"
+        );
+    }
+}
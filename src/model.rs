@@ -1,37 +1,54 @@
 use std::borrow::Cow;
+use std::cmp::min;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::mem;
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use chrono::Duration as TimerDuration;
 use regex::Regex;
 use timer::{Guard as TimerGuard, Timer};
+use tuikit::canvas::BoundedCanvas;
 use tuikit::prelude::{Event as TermEvent, *};
+#[cfg(any(test, feature = "test-render"))]
+use tuikit::screen::Screen;
 
+use crate::action_menu::ActionMenu;
+use crate::engine::display::DisplayEngineFactory;
 use crate::engine::factory::{AndOrEngineFactory, ExactOrFuzzyEngineFactory, RegexEngineFactory};
-use crate::event::{Event, EventHandler, EventReceiver, EventSender};
+use crate::engine::scoring::ScoringEngineFactory;
+use crate::engine::transform::TransformEngineFactory;
+use crate::event::{action_name, Event, EventHandler, EventReceiver, EventSender};
 use crate::header::Header;
-use crate::input::parse_action_arg;
-use crate::item::{ItemPool, ItemWrapper};
+use crate::help::Help;
+use crate::input::{fire_custom_action, parse_action_arg, ActionContext, CustomAction};
+use crate::item::{ItemIndex, ItemPool, ItemWrapper, MatchedItem};
 use crate::matcher::{Matcher, MatcherControl};
 use crate::options::SkimOptions;
-use crate::output::SkimOutput;
+use crate::output::{outputs_of, FinalOutputTransform, OutputTransform, SkimOutput};
 use crate::previewer::Previewer;
 use crate::query::Query;
 use crate::reader::{Reader, ReaderControl};
 use crate::selection::Selection;
 use crate::spinlock::SpinLock;
 use crate::theme::ColorTheme;
-use crate::util::{depends_on_items, inject_command, margin_string_to_size, parse_margin, InjectContext};
-use crate::{FuzzyAlgorithm, MatchEngineFactory, SkimItem};
+use crate::util::{
+    depends_on_items, expand_command, expand_command_in, inject_command, margin_string_to_size, parse_margin,
+    restrict_env, InjectContext,
+};
+use crate::{AcceptValidator, CaseMatching, FuzzyAlgorithm, ItemCallback, ItemPredicate, MatchEngineFactory, SkimItem};
 
 const REFRESH_DURATION: i64 = 100;
 const SPINNER_DURATION: u32 = 200;
 const SPINNERS: [char; 8] = ['-', '\\', '|', '/', '-', '\\', '|', '/'];
 const SPINNERS_INLINE: [char; 2] = ['-', '<'];
+const LAZY_TAIL_HEAD_SIZE: usize = 100; // used before the height of the selection is known
 const DELIMITER_STR: &str = r"[\t\n ]+";
 
 lazy_static! {
@@ -43,12 +60,20 @@ pub struct Model {
     query: Query,
     selection: Selection,
     num_options: usize,
+    source_counts: Vec<(String, usize)>, // the number of matched items per source, in order of appearance
+    pre_matched: bool,                   // the items are matched elsewhere, the reader and the matcher are not run
+
+    confirm_accept: Option<String>,                 // the prompt to confirm the accept
+    pending_accept: Option<(Option<String>, bool)>, // (accept key, accept all) waiting for confirmation
+    auto_accept_single: bool,                       // accept the only match once the query is changed
+    query_changed: bool,                            // the query is changed since the last full match
 
     use_regex: bool,
+    case: CaseMatching, // rotated by `toggle-case`
     regex_matcher: Matcher,
     matcher: Matcher,
 
-    term: Arc<Term>,
+    output: Output,
 
     item_pool: Arc<ItemPool>,
 
@@ -62,11 +87,19 @@ pub struct Model {
     matcher_control: Option<MatcherControl>,
 
     header: Header,
+    help: Help,
+    help_shown: bool,                // the key bindings are shown instead of the rest
+    action_menu: Option<ActionMenu>, // the actions of an item, shown instead of the rest
+    replay_events: VecDeque<Event>,  // the recorded events, one each time the items are matched
+    replaying: Arc<AtomicBool>,      // cleared once the last recorded event is sent
 
     preview_hidden: bool,
     previewer: Option<Previewer>,
     preview_direction: Direction,
     preview_size: Size,
+    preview_only: bool,              // show only the preview window
+    preview_separator: Option<char>, // drawn instead of the border of the preview window
+    preview_inline: usize,           // the lines of the preview drawn under the current item, 0 if none
 
     margin_top: Size,
     margin_right: Size,
@@ -76,17 +109,63 @@ pub struct Model {
     layout: String,
     delimiter: Regex,
     inline_info: bool,
+    show_cmd: bool, // the command run for the command query is shown (interactive mode)
+    show_query_debug: bool,
+    query_debug: Option<String>, // the terms the query is parsed into, if `show_query_debug`
     theme: Arc<ColorTheme>,
 
     // timer thread for scheduled events
     timer: Timer,
     hb_timer_guard: Option<TimerGuard>,
 
-    next_idx_to_append: u32, // for AppendAndSelect action
+    // nothing is drawn till the delay passed since the start
+    startup_delay: Duration,
+    started_at: Instant,
+    startup_timer_guard: Option<TimerGuard>,
+
+    next_idx_to_append: u32,           // for AppendAndSelect action
+    expand_command: bool,              // expand `~` and env vars in commands to execute
+    restrict_env: Option<Vec<String>>, // the only env vars passed to the commands to execute
+    custom_actions: Vec<CustomAction>,
+    initial_cursor: Option<ItemPredicate>, // taken once the initial matching is done
+    select_all_on_start: bool,             // reset once the initial matching is done
+    lazy_tail: bool,                       // show the head before the whole pool is matched
+    match_partial: bool,                   // the last matcher ran out of its budget
+    max_query_length: Option<usize>,
+    status_hint: Option<String>, // shown in the info line till the query is changed
+    output_matched_items: bool,
+    expect_outputs: HashMap<String, OutputTransform>,
+    output_transform: Option<FinalOutputTransform>,
+    accept_nth: bool,
+    deleted_items: Vec<Arc<dyn SkimItem>>,
+    // deleted, but still in the item pool (or in the results of a running matcher)
+    pending_deletions: HashSet<ItemIndex>,
+    on_delete_item: Option<ItemCallback>,
+    on_accept_attempt: Option<AcceptValidator>,
+    mouse_enabled: bool,
+    region: Option<Rectangle>, // the area of the term to draw in, the whole term if None
 }
 
 impl Model {
     pub fn new(rx: EventReceiver, tx: EventSender, reader: Reader, term: Arc<Term>, options: &SkimOptions) -> Self {
+        Self::with_output(rx, tx, reader, Output::Term(term), options)
+    }
+
+    /// the model drawing into a `width` x `height` screen in memory instead of a term, see `render`
+    #[cfg(any(test, feature = "test-render"))]
+    pub fn headless(
+        rx: EventReceiver,
+        tx: EventSender,
+        reader: Reader,
+        width: usize,
+        height: usize,
+        options: &SkimOptions,
+    ) -> Self {
+        let screen = SpinLock::new(Screen::new(width, height));
+        Self::with_output(rx, tx, reader, Output::Screen(screen), options)
+    }
+
+    fn with_output(rx: EventReceiver, tx: EventSender, reader: Reader, output: Output, options: &SkimOptions) -> Self {
         let default_command = match env::var("SKIM_DEFAULT_COMMAND").as_ref().map(String::as_ref) {
             Ok("") | Err(_) => "find .".to_owned(),
             Ok(val) => val.to_owned(),
@@ -100,16 +179,47 @@ impl Model {
 
         let selection = Selection::with_options(options).theme(theme.clone());
         let regex_engine: Rc<dyn MatchEngineFactory> = Rc::new(RegexEngineFactory::new());
-        let regex_matcher = Matcher::builder(regex_engine).build();
+        let match_budget = options.match_budget_ms.map(Duration::from_millis);
+        let regex_matcher = Matcher::builder(with_match_transform(regex_engine, options))
+            .query_case(options.query_case)
+            .max_query_length(options.max_query_length)
+            .parallel(!options.single_thread)
+            .budget(match_budget)
+            .build();
 
         let matcher = if let Some(engine_factory) = options.engine_factory.as_ref() {
             // use provided engine
-            Matcher::builder(engine_factory.clone()).case(options.case).build()
+            Matcher::builder(with_match_transform(engine_factory.clone(), options))
+                .case(options.case)
+                .query_case(options.query_case)
+                .max_query_length(options.max_query_length)
+                .parallel(!options.single_thread)
+                .budget(match_budget)
+                .build()
+        } else if let Some(score_fn) = options.score_fn.as_ref() {
+            // the query is passed to the function as is, no case matching applies
+            let scoring_engine_factory: Rc<dyn MatchEngineFactory> =
+                Rc::new(ScoringEngineFactory::new(score_fn.clone()));
+            Matcher::builder(with_match_transform(scoring_engine_factory, options))
+                .query_case(options.query_case)
+                .max_query_length(options.max_query_length)
+                .parallel(!options.single_thread)
+                .budget(match_budget)
+                .build()
         } else {
             let fuzzy_engine_factory: Rc<dyn MatchEngineFactory> = Rc::new(AndOrEngineFactory::new(
-                ExactOrFuzzyEngineFactory::builder().exact_mode(options.exact).build(),
+                ExactOrFuzzyEngineFactory::builder()
+                    .exact_mode(options.exact)
+                    .word_match(options.word_match)
+                    .build(),
             ));
-            Matcher::builder(fuzzy_engine_factory).case(options.case).build()
+            Matcher::builder(with_match_transform(fuzzy_engine_factory, options))
+                .case(options.case)
+                .query_case(options.query_case)
+                .max_query_length(options.max_query_length)
+                .parallel(!options.single_thread)
+                .budget(match_budget)
+                .build()
         };
 
         let item_pool = Arc::new(ItemPool::new().lines_to_reserve(options.header_lines));
@@ -129,10 +239,18 @@ impl Model {
             query,
             selection,
             num_options: 0,
+            source_counts: Vec::new(),
+            pre_matched: false,
+
+            confirm_accept: options.confirm_accept.map(|prompt| prompt.to_string()),
+            pending_accept: None,
+            auto_accept_single: options.auto_accept_single,
+            query_changed: false,
             use_regex: options.regex,
+            case: options.case,
             regex_matcher,
             matcher,
-            term,
+            output,
             item_pool,
 
             rx,
@@ -144,10 +262,18 @@ impl Model {
             fuzzy_algorithm: FuzzyAlgorithm::default(),
 
             header,
+            help: Help::new(Vec::new()).theme(theme.clone()),
+            help_shown: false,
+            action_menu: None,
+            replay_events: VecDeque::new(),
+            replaying: Arc::new(AtomicBool::new(false)),
             preview_hidden: true,
             previewer: None,
             preview_direction: Direction::Right,
             preview_size: Size::Default,
+            preview_only: false,
+            preview_separator: None,
+            preview_inline: 0,
 
             margin_top,
             margin_right,
@@ -157,16 +283,82 @@ impl Model {
             layout: "default".to_string(),
             delimiter: Regex::new(DELIMITER_STR).unwrap(),
             inline_info: false,
+            show_cmd: options.show_cmd,
+            show_query_debug: false,
+            query_debug: None,
             theme,
             timer: Timer::new(),
             hb_timer_guard: None,
 
+            startup_delay: Duration::from_millis(options.startup_delay_ms),
+            started_at: Instant::now(),
+            startup_timer_guard: None,
+
             next_idx_to_append: 0,
+            expand_command: !options.no_expand,
+            restrict_env: restrict_env_of(options),
+            custom_actions: options.custom_actions.clone(),
+            initial_cursor: options.initial_cursor.clone(),
+            select_all_on_start: options.select_all_on_start,
+            lazy_tail: options.lazy_tail,
+            match_partial: false,
+            max_query_length: options.max_query_length,
+            status_hint: None,
+            output_matched_items: options.output_matched_items,
+            expect_outputs: options.expect_outputs.clone(),
+            output_transform: options.output_transform.clone(),
+            accept_nth: options.accept_nth.is_some(),
+            deleted_items: Vec::new(),
+            pending_deletions: HashSet::new(),
+            on_delete_item: options.on_delete_item.clone(),
+            on_accept_attempt: options.on_accept_attempt.clone(),
+            mouse_enabled: !options.no_mouse,
+            region: None,
         };
         ret.parse_options(options);
         ret
     }
 
+    /// whether the terminal reports the mouse events, the mouse keys are ignored otherwise
+    pub fn mouse_enabled(mut self, enabled: bool) -> Self {
+        self.mouse_enabled = enabled;
+        self
+    }
+
+    /// the lines of the key bindings, shown by `show-help`
+    pub fn help(mut self, lines: Vec<String>) -> Self {
+        self.help = Help::new(lines).theme(self.theme.clone());
+        self
+    }
+
+    /// the events recorded (`--replay-events`), they are handled in order, each once the items are
+    /// all read and matched, so that the replay does not depend on the timing. `replaying` is
+    /// cleared once the last of them is sent, the live keys are to be dropped till then.
+    pub fn replay_events(mut self, events: Vec<Event>, replaying: Arc<AtomicBool>) -> Self {
+        replaying.store(!events.is_empty(), Ordering::SeqCst);
+        self.replay_events = events.into();
+        self.replaying = replaying;
+        self
+    }
+
+    /// draw in the area of the term only, e.g. a term shared with the other widgets
+    pub fn region(mut self, region: Option<Rectangle>) -> Self {
+        self.region = region;
+        self
+    }
+
+    /// show the given items instead of reading and matching, they are sorted by their ranks
+    pub fn pre_matched_items(mut self, items: Vec<MatchedItem>) -> Self {
+        self.pre_matched = true;
+        self.item_pool
+            .append(items.iter().map(|matched| matched.item.clone()).collect());
+        drop(self.item_pool.take());
+        self.num_options = items.len();
+        count_sources(&mut self.source_counts, &items);
+        self.selection.append_sorted_items(items);
+        self
+    }
+
     fn parse_options(&mut self, options: &SkimOptions) {
         if let Some(delimiter) = options.delimiter {
             self.delimiter = Regex::new(delimiter).unwrap_or_else(|_| Regex::new(DELIMITER_STR).unwrap());
@@ -178,6 +370,10 @@ impl Model {
             self.inline_info = true;
         }
 
+        if options.show_query_debug {
+            self.show_query_debug = true;
+        }
+
         if options.regex {
             self.use_regex = true;
         }
@@ -192,17 +388,50 @@ impl Model {
         self.preview_direction = preview_direction;
         self.preview_size = preview_size;
         self.preview_hidden = !preview_shown;
+        self.preview_separator = options
+            .preview_separator
+            .map(|separator| separator.chars().next().unwrap_or(' '));
+
+        #[cfg(feature = "async-preview")]
+        let async_preview = options.async_preview.is_some();
+        #[cfg(not(feature = "async-preview"))]
+        let async_preview = false;
 
-        if let Some(preview_cmd) = options.preview {
+        if options.preview.is_some() || options.preview_line_cmd || async_preview {
             let tx = Arc::new(SpinLock::new(self.tx.clone()));
-            self.previewer = Some(
-                Previewer::new(Some(preview_cmd.to_string()), move || {
-                    let _ = tx.lock().send(Event::EvHeartBeat);
-                })
-                .wrap(preview_wrap)
-                .delimiter(self.delimiter.clone()),
-            );
+            let previewer = Previewer::new(options.preview.map(|cmd| cmd.to_string()), move || {
+                let _ = tx.lock().send(Event::EvHeartBeat);
+            })
+            .wrap(preview_wrap)
+            .scroll_lines(preview_scroll_lines(options.mouse_scroll_lines))
+            .delimiter(self.delimiter.clone())
+            .expand_command(!options.no_expand)
+            .restrict_env(restrict_env_of(options))
+            .cache_size(options.preview_cache_size)
+            .stream(options.preview_stream)
+            .line_cmd(options.preview_line_cmd);
+            #[cfg(feature = "async-preview")]
+            let previewer = previewer.async_preview(options.async_preview.clone());
+            self.previewer = Some(previewer);
+        }
+
+        if options.preview_only && self.previewer.is_some() {
+            self.preview_only = true;
+            self.preview_hidden = false;
+        } else {
+            self.preview_inline = options.preview_inline.unwrap_or(0);
         }
+        self.update_inline_preview();
+    }
+
+    /// leave the rows under the current item for the inline preview, if it is shown
+    fn update_inline_preview(&mut self) {
+        let rows = if self.preview_hidden || self.previewer.is_none() {
+            0
+        } else {
+            self.preview_inline
+        };
+        self.selection.set_inline_preview_rows(rows);
     }
 
     // -> (direction, size, wrap, shown)
@@ -241,6 +470,34 @@ impl Model {
         (direction, size, wrap, shown)
     }
 
+    fn append_matched_items(&mut self, mut matched: Vec<MatchedItem>, reader_stopped: bool, env: &mut ModelEnv) {
+        if !self.pending_deletions.is_empty() {
+            matched.retain(|matched_item| !self.pending_deletions.contains(&matched_item.item.get_id()));
+        }
+
+        match env.clear_selection {
+            ClearStrategy::DontClear => {}
+            ClearStrategy::Clear => {
+                self.selection.clear();
+                env.clear_selection = ClearStrategy::DontClear;
+            }
+            ClearStrategy::ClearIfNotNull => {
+                if reader_stopped || !matched.is_empty() {
+                    self.selection.clear();
+                    env.clear_selection = ClearStrategy::DontClear;
+                }
+            }
+        };
+        self.num_options += matched.len();
+        count_sources(&mut self.source_counts, &matched);
+        if self.select_all_on_start {
+            for matched_item in matched.iter() {
+                self.selection.act_select_item(matched_item.item.clone());
+            }
+        }
+        self.selection.append_sorted_items(matched);
+    }
+
     fn act_heart_beat(&mut self, env: &mut ModelEnv) {
         // save the processed items
         let matcher_stopped = self
@@ -252,27 +509,35 @@ impl Model {
         if matcher_stopped {
             let reader_stopped = self.reader_control.as_ref().map(ReaderControl::is_done).unwrap_or(true);
             let ctrl = self.matcher_control.take().unwrap();
+            // the items left are matched by the next run, as the ones newly read
+            self.match_partial = ctrl.is_partial();
             let lock = ctrl.into_items();
             let mut items = lock.lock();
             let matched = mem::replace(&mut *items, Vec::new());
+            self.append_matched_items(matched, reader_stopped, env);
 
-            match env.clear_selection {
-                ClearStrategy::DontClear => {}
-                ClearStrategy::Clear => {
-                    self.selection.clear();
-                    env.clear_selection = ClearStrategy::DontClear;
+            // all the items are read and matched, place the cursor (the top if none satisfies)
+            if reader_stopped && self.item_pool.num_not_taken() == 0 {
+                if let Some(predicate) = self.initial_cursor.take() {
+                    self.selection
+                        .act_move_cursor_to_first(|item| predicate(&*item.get_inner()));
                 }
-                ClearStrategy::ClearIfNotNull => {
-                    if reader_stopped || !matched.is_empty() {
-                        self.selection.clear();
-                        env.clear_selection = ClearStrategy::DontClear;
-                    }
+                self.selection.drop_filtered_selected();
+
+                if self.auto_accept_single && self.query_changed && self.num_options == 1 {
+                    let _ = self.tx.send(Event::EvActAccept(None));
                 }
-            };
-            self.num_options += matched.len();
-            self.selection.append_sorted_items(matched);
+                self.query_changed = false;
+                self.select_all_on_start = false;
+            }
+        } else if let Some(head) = self.matcher_control.as_mut().and_then(MatcherControl::take_head) {
+            // show the head first, the tail is appended once the matcher stops
+            let reader_stopped = self.reader_control.as_ref().map(ReaderControl::is_done).unwrap_or(true);
+            self.append_matched_items(head, reader_stopped, env);
         }
 
+        self.remove_deleted_from_pool();
+
         let items_consumed = self.item_pool.num_not_taken() == 0;
         let reader_stopped = self.reader_control.as_ref().map(|c| c.is_done()).unwrap_or(true);
         let processed = reader_stopped && items_consumed;
@@ -291,6 +556,13 @@ impl Model {
                         let _ = tx.send(Event::EvHeartBeat);
                     });
             self.hb_timer_guard.replace(hb_timer_guard);
+        } else if let Some(ev) = self.replay_events.pop_front() {
+            // the heart beat after it waits for the matcher the event may restart
+            let _ = self.tx.send(ev);
+            let _ = self.tx.send(Event::EvHeartBeat);
+            if self.replay_events.is_empty() {
+                self.replaying.store(false, Ordering::SeqCst);
+            }
         }
     }
 
@@ -305,10 +577,32 @@ impl Model {
         env.clear_selection = ClearStrategy::Clear;
         self.item_pool.reset();
         self.num_options = 0;
+        self.source_counts.clear();
+        self.restart_matcher();
+    }
+
+    fn act_toggle_case(&mut self, env: &mut ModelEnv) {
+        self.case = self.case.rotate();
+        self.matcher.set_case(self.case);
+        self.regex_matcher.set_case(self.case);
+
+        // restart matcher, the query and the selections are kept
+        if let Some(ctrl) = self.matcher_control.take() {
+            ctrl.kill();
+        }
+
+        env.clear_selection = ClearStrategy::Clear;
+        self.item_pool.reset();
+        self.num_options = 0;
+        self.source_counts.clear();
         self.restart_matcher();
     }
 
     fn on_cmd_query_change(&mut self, env: &mut ModelEnv) {
+        if self.pre_matched {
+            return;
+        }
+
         // stop matcher
         if let Some(ctrl) = self.reader_control.take() {
             ctrl.kill();
@@ -320,6 +614,7 @@ impl Model {
         env.clear_selection = ClearStrategy::ClearIfNotNull;
         self.item_pool.clear();
         self.num_options = 0;
+        self.source_counts.clear();
 
         // restart reader
         self.reader_control.replace(self.reader.run(&env.cmd));
@@ -328,6 +623,10 @@ impl Model {
     }
 
     fn on_query_change(&mut self, env: &mut ModelEnv) {
+        if self.pre_matched {
+            return;
+        }
+
         // restart matcher
         if let Some(ctrl) = self.matcher_control.take() {
             ctrl.kill();
@@ -335,9 +634,19 @@ impl Model {
         env.clear_selection = ClearStrategy::Clear;
         self.item_pool.reset();
         self.num_options = 0;
+        self.source_counts.clear();
+        self.status_hint = None;
+        self.query_changed = true;
         self.restart_matcher();
     }
 
+    /// ring the terminal bell, e.g. when an action could not be done
+    fn bell(&self) {
+        if let Ok(mut tty) = OpenOptions::new().write(true).open("/dev/tty") {
+            let _ = tty.write_all(b"\x07");
+        }
+    }
+
     fn act_execute(&mut self, cmd: &str) {
         let item = self.selection.get_current_item();
         if depends_on_items(cmd) && item.is_none() {
@@ -346,9 +655,13 @@ impl Model {
             return;
         }
 
-        let _ = self.term.pause();
+        if let Some(term) = self.term() {
+            let _ = term.pause();
+        }
         self.act_execute_silent(cmd);
-        let _ = self.term.restart();
+        if let Some(term) = self.term() {
+            let _ = term.restart();
+        }
     }
 
     fn act_execute_silent(&mut self, cmd: &str) {
@@ -359,6 +672,110 @@ impl Model {
             return;
         }
 
+        let cmd = self.inject_command(cmd);
+        let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        let mut command = Command::new(shell);
+        let _ = restrict_env(&mut command, self.restrict_env.as_deref())
+            .arg("-c")
+            .arg(cmd)
+            .status();
+    }
+
+    /// pipe the output of the command (the current item as a path by default) into the pager, the
+    /// term is paused meanwhile. The state is kept regardless of how the pager exits.
+    fn act_page(&mut self, cmd: Option<&str>) {
+        let cmd = cmd.unwrap_or("cat {}");
+        if depends_on_items(cmd) && self.selection.get_current_item().is_none() {
+            debug!("act_page: command refers to items and there is no item for now");
+            debug!("command to execute: [{}]", cmd);
+            return;
+        }
+
+        let cmd = self.inject_command(cmd);
+        let pager = env::var("PAGER")
+            .ok()
+            .filter(|pager| !pager.trim().is_empty())
+            .unwrap_or_else(|| "less -R".to_string());
+        let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+
+        // the pager draws on the term even if the stdout of skim is redirected, e.g. `vim $(sk)`
+        let mut command = Command::new(shell);
+        restrict_env(&mut command, self.restrict_env.as_deref())
+            .arg("-c")
+            .arg(format!("({}) | {}", cmd, pager));
+        if let Ok(tty) = OpenOptions::new().write(true).open("/dev/tty") {
+            command.stdout(tty);
+        }
+
+        if let Some(term) = self.term() {
+            let _ = term.pause();
+        }
+        match command.status() {
+            Ok(status) if !status.success() => debug!("act_page: the pager exited with {}: [{}]", status, cmd),
+            Err(err) => debug!("act_page: failed to run the pager: {}", err),
+            _ => {}
+        }
+        if let Some(term) = self.term() {
+            let _ = term.restart();
+        }
+    }
+
+    /// move the cursor to the next/previous item whose text contains `pattern`, with `{q}` replaced
+    /// by the query. The query and the matches are kept, ring the bell if there is no such item.
+    fn act_jump(&mut self, pattern: &str, forward: bool) {
+        let pattern = pattern.replace("{q}", &self.query.get_query());
+        let found = self
+            .selection
+            .act_jump(forward, |item| item.text().contains(pattern.as_str()));
+        if !found {
+            self.bell();
+        }
+    }
+
+    /// run the command with the query on its stdin, replace the query with its output (the trailing
+    /// newlines are trimmed). The query is kept if the command fails.
+    fn act_transform_query(&mut self, cmd: &str) {
+        self.status_hint = None;
+        if depends_on_items(cmd) && self.selection.get_current_item().is_none() {
+            debug!("act_transform_query: command refers to items and there is no item for now");
+            debug!("command to execute: [{}]", cmd);
+            return;
+        }
+
+        let cmd = self.inject_command(cmd);
+        let query = self.query.get_query();
+        let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        let mut command = Command::new(shell);
+        let output = restrict_env(&mut command, self.restrict_env.as_deref())
+            .arg("-c")
+            .arg(&cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .and_then(|mut child| {
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(query.as_bytes());
+                }
+                child.wait_with_output()
+            });
+
+        match output {
+            Ok(output) if output.status.success() => {
+                let transformed = String::from_utf8_lossy(&output.stdout);
+                self.query.act_set_query(transformed.trim_end_matches(['\n', '\r']));
+            }
+            _ => {
+                debug!("act_transform_query: command failed: [{}]", cmd);
+                self.status_hint = Some("transform-query failed".to_string());
+                self.bell();
+            }
+        }
+    }
+
+    /// inject the fields of the current item, the selections and the queries into the command
+    fn inject_command(&self, cmd: &str) -> String {
+        let item = self.selection.get_current_item();
         let current_selection = item
             .as_ref()
             .map(|item| item.output())
@@ -381,9 +798,169 @@ impl Model {
             cmd_query: &cmd_query,
         };
 
-        let cmd = inject_command(cmd, context).to_string();
-        let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
-        let _ = Command::new(shell).arg("-c").arg(cmd).status();
+        let cmd = if self.expand_command {
+            expand_command_in(cmd, self.restrict_env.as_deref())
+        } else {
+            cmd.into()
+        };
+        inject_command(&cmd, context).to_string()
+    }
+
+    /// the command run for the command query, expanded as the reader does (`show_cmd`)
+    fn expanded_cmd(&self) -> String {
+        let cmd = self.query.get_cmd();
+        if self.expand_command {
+            expand_command(&cmd).into_owned()
+        } else {
+            cmd
+        }
+    }
+
+    /// whether `on_accept_attempt` rejects accepting the selected items (all the matched ones if
+    /// `all`, the item of the action menu if it is shown), its message is then shown in the info
+    /// line and skim keeps running
+    fn is_accept_rejected(&mut self, all: bool) -> bool {
+        let on_accept_attempt = match self.on_accept_attempt.clone() {
+            Some(on_accept_attempt) => on_accept_attempt,
+            None => return false,
+        };
+
+        let items = match self.action_menu {
+            Some(ref action_menu) => vec![action_menu.item().clone()],
+            None if all => self.selection.get_matched_items(),
+            None => self.selection.get_selected_items(),
+        };
+        match on_accept_attempt(&items) {
+            Ok(()) => false,
+            Err(msg) => {
+                debug!("accept rejected: {}", msg);
+                self.status_hint = Some(msg);
+                self.bell();
+                true
+            }
+        }
+    }
+
+    /// stop reading/matching and return the selected items, or all the matched items if `all`
+    fn act_accept(&mut self, accept_key: Option<String>, all: bool) -> SkimOutput {
+        if let Some(ctrl) = self.reader_control.take() {
+            ctrl.kill();
+        }
+        if let Some(ctrl) = self.matcher_control.take() {
+            ctrl.kill();
+        }
+
+        let matched_items = if self.output_matched_items {
+            self.selection.get_matched_items()
+        } else {
+            Vec::new()
+        };
+
+        let (selected_items, selected_scores) = if all {
+            (self.selection.get_matched_items(), self.selection.get_matched_scores())
+        } else {
+            (
+                self.selection.get_selected_items(),
+                self.selection.get_selected_scores(),
+            )
+        };
+
+        let selected_outputs = self.selected_outputs(&selected_items, accept_key.as_deref());
+
+        SkimOutput {
+            accept_key,
+            query: self.query.get_fz_query(),
+            cmd: self.query.get_cmd_query(),
+            selected_items,
+            selected_scores,
+            matched_items,
+            selected_outputs,
+            is_abort: false,
+            deleted_items: self.deleted_items.clone(),
+            item_action: None,
+        }
+    }
+
+    /// accept (see `act_accept`) unless `on_accept_attempt` rejects it
+    fn act_accept_unless_rejected(&mut self, accept_key: Option<String>, all: bool) -> Option<SkimOutput> {
+        if self.is_accept_rejected(all) {
+            None
+        } else {
+            Some(self.act_accept(accept_key, all))
+        }
+    }
+
+    /// accept the item of the action menu only, with the action chosen
+    fn act_accept_item_action(&mut self, accept_key: Option<String>) -> SkimOutput {
+        let (item, action) = self.action_menu.take().unwrap().into_chosen();
+        let mut output = self.act_accept(accept_key, false);
+        output.selected_items = vec![item.clone()];
+        output.selected_scores = vec![self.selection.get_current_score()];
+        output.selected_outputs = self.selected_outputs(&output.selected_items, output.accept_key.as_deref());
+        output.item_action = Some((item, action));
+        output
+    }
+
+    /// stop reading/matching and return the current item only, flagged as aborted
+    fn act_abort_with_current(&mut self) -> SkimOutput {
+        let mut output = self.act_accept(None, false);
+        output.selected_items = self
+            .selection
+            .get_current_item()
+            .map(|item| vec![item.get_inner()])
+            .unwrap_or_default();
+        output.selected_scores = output
+            .selected_items
+            .iter()
+            .map(|_| self.selection.get_current_score())
+            .collect();
+        output.selected_outputs = self.selected_outputs(&output.selected_items, None);
+        output.is_abort = true;
+        output
+    }
+
+    /// the outputs of the accepted items, only computed if they could differ from `output()`
+    fn selected_outputs(&self, items: &[Arc<dyn SkimItem>], accept_key: Option<&str>) -> Vec<String> {
+        if self.expect_outputs.is_empty() && self.output_transform.is_none() && !self.accept_nth {
+            Vec::new()
+        } else {
+            outputs_of(items, accept_key, &self.expect_outputs, self.output_transform.as_ref())
+        }
+    }
+
+    /// remove the current item from the list and the item pool, it is reported by
+    /// `SkimOutput::deleted_items` and `SkimOptions::on_delete_item`
+    fn act_delete_item(&mut self) {
+        let item = match self.selection.remove_current_item() {
+            Some(item) => item,
+            None => return self.bell(),
+        };
+
+        self.num_options = self.num_options.saturating_sub(1);
+        if let Some(source) = item.source() {
+            if let Some((_, count)) = self.source_counts.iter_mut().find(|(label, _)| *label == source) {
+                *count = count.saturating_sub(1);
+            }
+        }
+
+        self.pending_deletions.insert(item.get_id());
+        self.remove_deleted_from_pool();
+
+        if let Some(on_delete_item) = self.on_delete_item.as_ref() {
+            on_delete_item(item.get_inner());
+        }
+        self.deleted_items.push(item.get_inner());
+    }
+
+    /// remove the deleted items from the item pool. It waits till the matcher stops, which holds the
+    /// pool and could still report the deleted items (they are filtered out till then).
+    fn remove_deleted_from_pool(&mut self) {
+        if self.pending_deletions.is_empty() || self.matcher_control.is_some() {
+            return;
+        }
+
+        self.item_pool.remove(&self.pending_deletions);
+        self.pending_deletions.clear();
     }
 
     #[allow(clippy::trivial_regex)]
@@ -413,19 +990,94 @@ impl Model {
             clear_selection: ClearStrategy::DontClear,
         };
 
-        self.reader_control = Some(self.reader.run(&env.cmd));
+        if !self.pre_matched {
+            self.reader_control = Some(self.reader.run(&env.cmd));
+        }
+
+        // wake up to draw once the startup delay passed, in case there are no other events
+        self.started_at = Instant::now();
+        if self.startup_delay > Duration::from_millis(0) {
+            let tx = self.tx.clone();
+            let delay = TimerDuration::milliseconds(self.startup_delay.as_millis() as i64);
+            let guard = self.timer.schedule_with_delay(delay, move || {
+                let _ = tx.send(Event::EvHeartBeat);
+            });
+            self.startup_timer_guard.replace(guard);
+        }
 
-        // In the event loop, there might need
-        let mut next_event = Some(Event::EvHeartBeat);
+        // In the event loop, there might be pending events (e.g. the actions chained by
+        // `if-query-empty(...)`)
+        // They are executed in order before the events from the channel. Once an action terminates
+        // skim (e.g. `accept`), the rest of them are skipped.
+        let mut next_events = VecDeque::new();
+        next_events.push_back(Event::EvHeartBeat);
         loop {
-            let ev = next_event.take().or_else(|| self.rx.recv().ok())?;
+            let ev = next_events.pop_front().or_else(|| self.rx.recv().ok())?;
+
+            // the help is scrolled by the moving actions, any other key closes it, the other events
+            // (e.g. the heartbeats) are handled as usual
+            let ev = match ev {
+                _ if !self.help_shown => ev,
+                Event::EvActUp(_) | Event::EvActDown(_) | Event::EvActPageUp(_) | Event::EvActPageDown(_) => {
+                    self.help.handle(&ev);
+                    Event::EvInputInvalid
+                }
+                _ if !is_key_event(&ev) => ev,
+                _ => {
+                    self.help_shown = false;
+                    Event::EvInputInvalid
+                }
+            };
+
+            // the action menu is moved by up/down and accepted by `accept`, any other key closes it
+            let ev = match ev {
+                _ if self.action_menu.is_none() => ev,
+                Event::EvHeartBeat | Event::EvActRedraw => ev,
+                Event::EvActUp(_) | Event::EvActDown(_) => {
+                    self.action_menu.as_mut().unwrap().handle(&ev);
+                    Event::EvInputInvalid
+                }
+                Event::EvActAccept(accept_key) => {
+                    if !self.is_accept_rejected(false) {
+                        return Some(self.act_accept_item_action(accept_key));
+                    }
+                    Event::EvInputInvalid
+                }
+                _ => {
+                    self.action_menu = None;
+                    Event::EvInputInvalid
+                }
+            };
+            let ev = if self.preview_only {
+                scroll_preview_instead(ev)
+            } else {
+                ev
+            };
 
             debug!("handle event: {:?}", ev);
 
+            // waiting for the confirmation of accept: `y` confirms, other keys cancel
+            let ev = match ev {
+                _ if self.pending_accept.is_none() => ev,
+                Event::EvHeartBeat | Event::EvActRedraw => ev,
+                Event::EvActAddChar('y') | Event::EvActAddChar('Y') => {
+                    let (accept_key, all) = self.pending_accept.take().unwrap();
+                    let output = self.act_accept_unless_rejected(accept_key, all);
+                    if output.is_some() {
+                        return output;
+                    }
+                    Event::EvInputInvalid
+                }
+                _ => {
+                    self.pending_accept = None;
+                    Event::EvInputInvalid
+                }
+            };
+
             match ev {
                 Event::EvHeartBeat => {
                     // consume following HeartBeat event
-                    next_event = self.consume_additional_event(&Event::EvHeartBeat);
+                    next_events.extend(self.consume_additional_event(&Event::EvHeartBeat));
                     self.act_heart_beat(&mut env);
                 }
 
@@ -433,47 +1085,69 @@ impl Model {
                     let matched =
                         self.num_options + self.matcher_control.as_ref().map(|c| c.get_num_matched()).unwrap_or(0);
                     if matched == 0 {
-                        next_event = parse_action_arg(arg_str);
+                        push_front_all(&mut next_events, parse_action_arg(arg_str));
                         continue;
                     }
                 }
 
                 Event::EvActIfQueryEmpty(ref arg_str) => {
                     if env.query.is_empty() {
-                        next_event = parse_action_arg(arg_str);
+                        push_front_all(&mut next_events, parse_action_arg(arg_str));
                         continue;
                     }
                 }
 
                 Event::EvActIfQueryNotEmpty(ref arg_str) => {
                     if !env.query.is_empty() {
-                        next_event = parse_action_arg(arg_str);
+                        push_front_all(&mut next_events, parse_action_arg(arg_str));
                         continue;
                     }
                 }
 
-                Event::EvActTogglePreview => {
+                Event::EvActTogglePreview if !self.preview_only => {
                     self.preview_hidden = !self.preview_hidden;
+                    self.update_inline_preview();
+                }
+
+                Event::EvActRefreshPreview => {
+                    // the previewer re-runs the command on the next draw, ask for one at once
+                    let _ = self.tx.send(Event::EvActRedraw);
+                }
+
+                Event::EvActRedraw if self.is_ui_shown() => {
+                    // the terminal is resized, draw once so that the widgets could pick up their
+                    // new sizes before they re-layout themselves.
+                    self.draw_ui();
                 }
 
                 Event::EvActRotateMode => {
                     self.act_rotate_mode(&mut env);
                 }
 
-                Event::EvActAccept(accept_key) => {
-                    if let Some(ctrl) = self.reader_control.take() {
-                        ctrl.kill();
-                    }
-                    if let Some(ctrl) = self.matcher_control.take() {
-                        ctrl.kill();
+                Event::EvActToggleCase => {
+                    self.act_toggle_case(&mut env);
+                }
+
+                Event::EvActAccept(ref accept_key) if self.confirm_accept.is_some() => {
+                    self.pending_accept = Some((accept_key.clone(), false));
+                }
+
+                Event::EvActAccept(ref accept_key) => {
+                    let output = self.act_accept_unless_rejected(accept_key.clone(), false);
+                    if output.is_some() {
+                        return output;
                     }
+                }
 
-                    return Some(SkimOutput {
-                        accept_key,
-                        query: self.query.get_fz_query(),
-                        cmd: self.query.get_cmd_query(),
-                        selected_items: self.selection.get_selected_items(),
-                    });
+                Event::EvActAcceptAll(ref accept_key) if self.confirm_accept.is_some() => {
+                    self.pending_accept = Some((accept_key.clone(), true));
+                }
+
+                Event::EvActAcceptAll(ref accept_key) => {
+                    let output = self.act_accept_unless_rejected(accept_key.clone(), true);
+                    if output.is_some() {
+                        return output;
+                    }
                 }
 
                 Event::EvActAbort => {
@@ -486,9 +1160,13 @@ impl Model {
                     return None;
                 }
 
+                Event::EvActAbortWithCurrent => {
+                    return Some(self.act_abort_with_current());
+                }
+
                 Event::EvActDeleteCharEOF => {
                     if env.query.is_empty() {
-                        next_event = Some(Event::EvActAbort);
+                        next_events.push_front(Event::EvActAbort);
                         continue;
                     }
                 }
@@ -501,14 +1179,70 @@ impl Model {
                     self.act_execute_silent(cmd);
                 }
 
+                Event::EvActActionMenu => {
+                    let menu = self
+                        .selection
+                        .get_current_item()
+                        .and_then(|item| ActionMenu::new(item.get_inner()));
+                    match menu {
+                        Some(menu) => self.action_menu = Some(menu.theme(self.theme.clone())),
+                        None => self.bell(),
+                    }
+                }
+
+                Event::EvActShowHelp => {
+                    self.help_shown = true;
+                }
+
+                Event::EvActPage(ref cmd) => {
+                    self.act_page(cmd.as_deref());
+                }
+
+                Event::EvActTransformQuery(ref cmd) => {
+                    self.act_transform_query(cmd);
+                }
+
+                Event::EvActGotoMark(mark) => {
+                    let found = self.selection.act_goto_mark(mark);
+                    if !found {
+                        self.bell();
+                    }
+                }
+
+                Event::EvActJumpNext(ref pattern) => {
+                    self.act_jump(pattern, true);
+                }
+
+                Event::EvActJumpPrev(ref pattern) => {
+                    self.act_jump(pattern, false);
+                }
+
+                Event::EvActCustom(index) => {
+                    let context = ActionContext {
+                        query: self.query.get_fz_query(),
+                        cmd_query: self.query.get_cmd_query(),
+                        current_item: self.selection.get_current_item().map(|item| item.get_inner()),
+                        selected_items: self.selection.get_selected_items(),
+                    };
+                    let action_chain = fire_custom_action(&self.custom_actions, index, &context);
+                    push_front_all(&mut next_events, action_chain);
+                    continue;
+                }
+
+                Event::EvActDeleteItem => {
+                    self.act_delete_item();
+                }
+
                 Event::EvActAppendAndSelect => {
                     self.act_append_and_select(&mut env);
                 }
 
+                Event::EvInputKey(key) if !self.mouse_enabled && is_mouse_key(key) => {}
+
                 Event::EvInputKey(key) => {
                     // dispatch key(normally the mouse keys) to sub-widgets
                     self.do_with_widget(|root| {
-                        let (width, height) = self.term.term_size().unwrap();
+                        let (width, height) = self.screen_size();
                         let rect = Rectangle {
                             top: 0,
                             left: 0,
@@ -566,11 +1300,88 @@ impl Model {
                 }
             }
 
-            let _ = self.do_with_widget(|root| self.term.draw(&root));
-            let _ = self.term.present();
+            if self.is_ui_shown() {
+                self.draw_ui();
+                if let Some(term) = self.term() {
+                    let _ = term.present();
+                }
+
+                // the headless model quits once everything is drawn, and the events sent meanwhile
+                // (e.g. the keys of a test) are handled
+                #[cfg(any(test, feature = "test-render"))]
+                {
+                    if self.term().is_none() && self.is_settled() && next_events.is_empty() {
+                        match self.rx.try_recv() {
+                            Ok(ev) => next_events.push_back(ev),
+                            Err(_) => return None,
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// the term drawn on, `None` if headless
+    fn term(&self) -> Option<&Arc<Term>> {
+        match self.output {
+            Output::Term(ref term) => Some(term),
+            #[cfg(any(test, feature = "test-render"))]
+            Output::Screen(_) => None,
+        }
+    }
+
+    fn screen_size(&self) -> (usize, usize) {
+        match self.output {
+            Output::Term(ref term) => term.term_size().unwrap_or((0, 0)),
+            #[cfg(any(test, feature = "test-render"))]
+            Output::Screen(ref screen) => {
+                let screen = screen.lock();
+                (screen.width(), screen.height())
+            }
+        }
+    }
+
+    fn draw_ui(&self) {
+        match self.output {
+            Output::Term(ref term) => {
+                let _ = self.do_with_widget(|root| term.draw(&root));
+            }
+            #[cfg(any(test, feature = "test-render"))]
+            Output::Screen(ref screen) => {
+                let _ = self.do_with_widget(|root| {
+                    let mut screen = screen.lock();
+                    let _ = screen.clear();
+                    root.draw(&mut *screen)
+                });
+            }
+        }
+    }
+
+    /// all the items are read and matched, and the preview (if any) is shown
+    #[cfg(any(test, feature = "test-render"))]
+    fn is_settled(&self) -> bool {
+        let reader_stopped = self.reader_control.as_ref().map(ReaderControl::is_done).unwrap_or(true);
+        let preview_pending = self.previewer.as_ref().map(Previewer::is_pending).unwrap_or(false);
+        reader_stopped && self.item_pool.num_not_taken() == 0 && self.matcher_control.is_none() && !preview_pending
+    }
+
+    /// run the headless model till all the items are read and matched (no keys are read), return
+    /// the screen drawn last
+    #[cfg(feature = "test-render")]
+    pub fn render(mut self) -> Screen {
+        let _ = self.start();
+        match self.output {
+            Output::Screen(ref screen) => mem::replace(&mut *screen.lock(), Screen::new(0, 0)),
+            Output::Term(_) => panic!("model: render is only for the headless model"),
         }
     }
 
+    /// the UI is not drawn in the startup delay, so that skim could exit without a flicker if it
+    /// is accepted/aborted in the meantime
+    fn is_ui_shown(&self) -> bool {
+        self.started_at.elapsed() >= self.startup_delay
+    }
+
     fn consume_additional_event(&self, target_event: &Event) -> Option<Event> {
         // consume additional HeartBeat event
         let mut rx_try_iter = self.rx.try_iter().peekable();
@@ -586,6 +1397,10 @@ impl Model {
     }
 
     fn restart_matcher(&mut self) {
+        if self.pre_matched {
+            return;
+        }
+
         self.matcher_timer = Instant::now();
         let query = self.query.get_fz_query();
 
@@ -611,13 +1426,30 @@ impl Model {
             &self.matcher
         };
 
+        // match till the first screen is filled, then the rest in the background
+        let head_size = if self.lazy_tail {
+            match self.selection.get_height() {
+                0 => Some(LAZY_TAIL_HEAD_SIZE),
+                height => Some(height),
+            }
+        } else {
+            None
+        };
+
+        let query_debug = if self.show_query_debug {
+            Some(matcher.describe(&query))
+        } else {
+            None
+        };
+
         let tx = self.tx.clone();
-        let new_matcher_control = matcher.run(&query, self.item_pool.clone(), move |_| {
+        let new_matcher_control = matcher.run(&query, self.item_pool.clone(), head_size, move |_| {
             // notify refresh immediately
             let _ = tx.send(Event::EvHeartBeat);
         });
 
         self.matcher_control.replace(new_matcher_control);
+        self.query_debug = query_debug;
     }
 
     /// construct the widget tree
@@ -626,11 +1458,23 @@ impl Model {
         F: Fn(Box<dyn Widget<Event> + '_>) -> R,
     {
         let total = self.item_pool.len();
-        let matcher_mode = if self.use_regex {
+        let mut matcher_mode = if self.use_regex {
             "RE".to_string()
         } else {
             "".to_string()
         };
+        // e.g. `RE/ignore-case`, the default (smart) is not shown
+        let case_mode = match self.case {
+            CaseMatching::Respect => "respect-case",
+            CaseMatching::Ignore => "ignore-case",
+            CaseMatching::Smart => "",
+        };
+        if !case_mode.is_empty() {
+            if !matcher_mode.is_empty() {
+                matcher_mode.push('/');
+            }
+            matcher_mode.push_str(case_mode);
+        }
 
         let matched = self.num_options + self.matcher_control.as_ref().map(|c| c.get_num_matched()).unwrap_or(0);
         let matcher_running = self.item_pool.num_not_taken() != 0 || matched != self.num_options;
@@ -645,8 +1489,19 @@ impl Model {
             matched,
             processed,
             matcher_running,
+            partial: self.match_partial && matcher_running,
+            query_truncated: self
+                .max_query_length
+                .map(|len| self.query.get_fz_query().chars().count() > len)
+                .unwrap_or(false),
+            matching_tail: self
+                .matcher_control
+                .as_ref()
+                .map(|c| c.matching_tail())
+                .unwrap_or(false),
             multi_selection: self.selection.is_multi_selection(),
             selected: self.selection.get_num_selected(),
+            hidden_selected: self.selection.get_num_hidden_selected(),
             current_item_idx: self.selection.get_current_item_idx(),
             reading: !self.reader_control.as_ref().map(|c| c.is_done()).unwrap_or(true),
             time_since_read: self.reader_timer.elapsed(),
@@ -654,10 +1509,27 @@ impl Model {
             matcher_mode,
             theme: self.theme.clone(),
             inline_info: self.inline_info,
+            source_counts: if self.source_counts.len() > 1 {
+                self.source_counts.clone()
+            } else {
+                Vec::new()
+            },
+            hint: self.status_hint.clone(),
+            query_debug: self.query_debug.clone(),
+            confirm: self.pending_accept.as_ref().and(self.confirm_accept.clone()),
         };
         let status_inline = status.clone();
 
-        let win_selection = Win::new(&self.selection);
+        // the list takes the rows left, the header taller than the screen is cut (and scrolled by
+        // `header-up`/`header-down`)
+        let selection: Box<dyn Widget<Event>> = match self.previewer.as_ref() {
+            Some(previewer) if self.preview_inline > 0 => Box::new(InlinePreview {
+                selection: &self.selection,
+                previewer,
+            }),
+            _ => Box::new(&self.selection),
+        };
+        let win_selection = Win::new(selection).basis(0).shrink(0);
         let win_query = Win::new(&self.query)
             .basis(if self.inline_info { 0 } else { 1 })
             .grow(0)
@@ -666,7 +1538,23 @@ impl Model {
             .basis(if self.inline_info { 0 } else { 1 })
             .grow(0)
             .shrink(0);
-        let win_header = Win::new(&self.header).grow(0).shrink(0);
+        let win_header = Win::new(&self.header).grow(0).shrink(1);
+        let cmd_line = CmdLine {
+            cmd: if self.show_cmd && self.query.in_cmd_mode() {
+                Some(self.expanded_cmd())
+            } else {
+                None
+            },
+            attr: self.theme.info(),
+        };
+        let win_cmd = Win::new(cmd_line)
+            .basis(if self.show_cmd && self.query.in_cmd_mode() {
+                1
+            } else {
+                0
+            })
+            .grow(0)
+            .shrink(0);
         let win_query_status = HSplit::default()
             .basis(if self.inline_info { 1 } else { 0 })
             .grow(0)
@@ -679,6 +1567,7 @@ impl Model {
             "reverse" => VSplit::default()
                 .split(win_query_status)
                 .split(win_query)
+                .split(win_cmd)
                 .split(win_status)
                 .split(win_header)
                 .split(win_selection),
@@ -686,17 +1575,21 @@ impl Model {
                 .split(win_selection)
                 .split(win_header)
                 .split(win_status)
+                .split(win_cmd)
                 .split(win_query)
                 .split(win_query_status),
             _ => VSplit::default()
                 .split(win_selection)
                 .split(win_header)
                 .split(win_status)
+                .split(win_cmd)
                 .split(win_query)
                 .split(win_query_status),
         };
 
-        let screen: Box<dyn Widget<Event>> = if !self.preview_hidden && self.previewer.is_some() {
+        let screen: Box<dyn Widget<Event>> = if self.preview_only {
+            Box::new(Win::new(self.previewer.as_ref().unwrap()))
+        } else if !self.preview_hidden && self.preview_inline == 0 && self.previewer.is_some() {
             let previewer = self.previewer.as_ref().unwrap();
             let win = Win::new(previewer)
                 .basis(self.preview_size)
@@ -704,22 +1597,48 @@ impl Model {
                 .shrink(0)
                 .border_attr(self.theme.border());
 
-            let win_preview = match self.preview_direction {
-                Direction::Up => win.border_bottom(true),
-                Direction::Right => win.border_left(true),
-                Direction::Down => win.border_top(true),
-                Direction::Left => win.border_right(true),
-            };
-
-            match self.preview_direction {
-                Direction::Up => Box::new(VSplit::default().split(win_preview).split(win_main)),
-                Direction::Right => Box::new(HSplit::default().split(win_main).split(win_preview)),
-                Direction::Down => Box::new(VSplit::default().split(win_main).split(win_preview)),
-                Direction::Left => Box::new(HSplit::default().split(win_preview).split(win_main)),
+            match self.preview_separator {
+                // the border of the preview window
+                None => {
+                    let win_preview = match self.preview_direction {
+                        Direction::Up => win.border_bottom(true),
+                        Direction::Right => win.border_left(true),
+                        Direction::Down => win.border_top(true),
+                        Direction::Left => win.border_right(true),
+                    };
+
+                    match self.preview_direction {
+                        Direction::Up => Box::new(VSplit::default().split(win_preview).split(win_main)),
+                        Direction::Right => Box::new(HSplit::default().split(win_main).split(win_preview)),
+                        Direction::Down => Box::new(VSplit::default().split(win_main).split(win_preview)),
+                        Direction::Left => Box::new(HSplit::default().split(win_preview).split(win_main)),
+                    }
+                }
+                Some(separator) => {
+                    let separator = Separator {
+                        ch: separator,
+                        attr: self.theme.border(),
+                    };
+                    let win_separator = Win::new(separator).basis(1).grow(0).shrink(0);
+
+                    match self.preview_direction {
+                        Direction::Up => Box::new(VSplit::default().split(win).split(win_separator).split(win_main)),
+                        Direction::Right => Box::new(HSplit::default().split(win_main).split(win_separator).split(win)),
+                        Direction::Down => Box::new(VSplit::default().split(win_main).split(win_separator).split(win)),
+                        Direction::Left => Box::new(HSplit::default().split(win).split(win_separator).split(win_main)),
+                    }
+                }
             }
         } else {
             Box::new(win_main)
         };
+        let screen: Box<dyn Widget<Event>> = if let Some(ref menu) = self.action_menu {
+            Box::new(Win::new(menu))
+        } else if self.help_shown {
+            Box::new(Win::new(&self.help))
+        } else {
+            screen
+        };
 
         let root = Win::new(screen)
             .margin_top(self.margin_top)
@@ -727,10 +1646,73 @@ impl Model {
             .margin_bottom(self.margin_bottom)
             .margin_left(self.margin_left);
 
-        action(Box::new(root))
+        match self.region {
+            // the area out of the region is left as is
+            Some(region) => {
+                let (width, height) = self.screen_size();
+                let root = Win::new(root)
+                    .margin_top(Size::Fixed(region.top))
+                    .margin_right(Size::Fixed(width.saturating_sub(region.left + region.width)))
+                    .margin_bottom(Size::Fixed(height.saturating_sub(region.top + region.height)))
+                    .margin_left(Size::Fixed(region.left));
+                action(Box::new(root))
+            }
+            None => action(Box::new(root)),
+        }
+    }
+}
+
+/// fold both the query and the items with `options.match_transform` (if any) before matching
+fn with_match_transform(factory: Rc<dyn MatchEngineFactory>, options: &SkimOptions) -> Rc<dyn MatchEngineFactory> {
+    let factory: Rc<dyn MatchEngineFactory> = match options.match_transform.as_ref() {
+        Some(transform) => Rc::new(TransformEngineFactory::new(factory, transform.clone())),
+        None => factory,
+    };
+
+    // outermost, so that the display is what is transformed
+    if options.match_display {
+        Rc::new(DisplayEngineFactory::new(factory))
+    } else {
+        factory
+    }
+}
+
+/// whether the event could be given by a key: a bindable action, a char typed or an unbound key
+fn is_key_event(ev: &Event) -> bool {
+    matches!(ev, Event::EvInputKey(_) | Event::EvActAddChar(_)) || action_name(ev).is_some()
+}
+
+fn is_mouse_key(key: Key) -> bool {
+    matches!(key, Key::MousePress(..) | Key::MouseRelease(..) | Key::MouseHold(..))
+}
+
+/// push the chain of events to the front of the queue, keeping their order
+fn push_front_all(queue: &mut VecDeque<Event>, events: Vec<Event>) {
+    for ev in events.into_iter().rev() {
+        queue.push_front(ev);
+    }
+}
+
+/// in preview only mode, the keys that move the cursor scroll the preview window instead
+fn scroll_preview_instead(ev: Event) -> Event {
+    match ev {
+        Event::EvActUp(diff) => Event::EvActPreviewUp(diff),
+        Event::EvActDown(diff) => Event::EvActPreviewDown(diff),
+        Event::EvActPageUp(diff) => Event::EvActPreviewPageUp(diff),
+        Event::EvActPageDown(diff) => Event::EvActPreviewPageDown(diff),
+        Event::EvActLeft(diff) => Event::EvActPreviewLeft(diff),
+        Event::EvActRight(diff) => Event::EvActPreviewRight(diff),
+        ev => ev,
     }
 }
 
+/// where the model draws: the term, or a screen in memory to be captured (`test-render`)
+enum Output {
+    Term(Arc<Term>),
+    #[cfg(any(test, feature = "test-render"))]
+    Screen(SpinLock<Screen>),
+}
+
 struct ModelEnv {
     pub cmd: String,
     pub query: String,
@@ -744,8 +1726,12 @@ struct Status {
     matched: usize,
     processed: usize,
     matcher_running: bool,
+    matching_tail: bool,   // the head is shown, the tail is still being matched
+    query_truncated: bool, // only a prefix of the query is matched
+    partial: bool,         // the matching ran out of its budget, the rest is matched next
     multi_selection: bool,
     selected: usize,
+    hidden_selected: usize, // the selected items not matched now
     current_item_idx: usize,
     reading: bool,
     time_since_read: Duration,
@@ -753,6 +1739,10 @@ struct Status {
     matcher_mode: String,
     theme: Arc<ColorTheme>,
     inline_info: bool,
+    source_counts: Vec<(String, usize)>, // empty unless the items come from multiple sources
+    hint: Option<String>,
+    query_debug: Option<String>,
+    confirm: Option<String>, // the prompt to confirm the accept, shown instead of the info
 }
 
 #[allow(unused_assignments)]
@@ -771,6 +1761,11 @@ impl Draw for Status {
         canvas.clear()?;
         let (screen_width, _) = canvas.size()?;
 
+        if let Some(ref confirm) = self.confirm {
+            canvas.print_with_attr(0, 0, confirm, self.theme.prompt())?;
+            return Ok(());
+        }
+
         let info_attr = self.theme.info();
         let info_attr_bold = Attr {
             effect: Effect::BOLD,
@@ -800,13 +1795,27 @@ impl Draw for Status {
         }
 
         // display matched/total number
-        col += canvas.print_with_attr(0, col, format!(" {}/{}", self.matched, self.total).as_ref(), info_attr)?;
+        col += canvas.print_with_attr(0, col, format!(" {}", self.matched).as_ref(), info_attr)?;
+        if self.matching_tail {
+            col += canvas.put_char_with_attr(0, col, '+', info_attr)?;
+        }
+        col += canvas.print_with_attr(0, col, format!("/{}", self.total).as_ref(), info_attr)?;
 
         // display the matcher mode
         if !self.matcher_mode.is_empty() {
             col += canvas.print_with_attr(0, col, format!("/{}", &self.matcher_mode).as_ref(), info_attr)?;
         }
 
+        // only a prefix of the query is matched
+        if self.query_truncated {
+            col += canvas.print_with_attr(0, col, " (query truncated)", info_attr)?;
+        }
+
+        // not all the items are matched in the budget (`--match-budget`)
+        if self.partial {
+            col += canvas.print_with_attr(0, col, " (partial)", info_attr)?;
+        }
+
         // display the percentage of the number of processed items
         if self.matcher_running && a_while_since_match {
             col += canvas.print_with_attr(
@@ -817,9 +1826,33 @@ impl Draw for Status {
             )?;
         }
 
+        // the number of matches per source, e.g. ` (files: 12  recent: 3)`
+        if !self.source_counts.is_empty() {
+            let counts: Vec<String> = self
+                .source_counts
+                .iter()
+                .map(|(source, count)| format!("{}: {}", source, count))
+                .collect();
+            col += canvas.print_with_attr(0, col, format!(" ({})", counts.join("  ")).as_ref(), info_attr)?;
+        }
+
         // selected number
         if self.multi_selection && self.selected > 0 {
             col += canvas.print_with_attr(0, col, format!(" [{}]", self.selected).as_ref(), info_attr_bold)?;
+            if self.hidden_selected > 0 {
+                let hidden = format!(" ({} hidden)", self.hidden_selected);
+                col += canvas.print_with_attr(0, col, hidden.as_ref(), info_attr)?;
+            }
+        }
+
+        // e.g. an action failed
+        if let Some(ref hint) = self.hint {
+            col += canvas.print_with_attr(0, col, format!(" ({})", hint).as_ref(), info_attr)?;
+        }
+
+        // the parsed query (`--show-query-debug`)
+        if let Some(ref query_debug) = self.query_debug {
+            col += canvas.print_with_attr(0, col, format!(" {}", query_debug).as_ref(), info_attr)?;
         }
 
         // item cursor
@@ -836,6 +1869,98 @@ impl Draw for Status {
 
 impl Widget<Event> for Status {}
 
+/// the lines to scroll the preview per tick of the mouse wheel, `M` of `N[,M]`, or `N` if not given
+fn preview_scroll_lines(scroll_lines: Option<&str>) -> i32 {
+    scroll_lines
+        .and_then(|lines| lines.rsplit(',').next())
+        .and_then(|lines| lines.trim().parse().ok())
+        .unwrap_or(1)
+}
+
+/// the only env vars passed to the preview and execute commands (`--restrict-env`), all if `None`
+fn restrict_env_of(options: &SkimOptions) -> Option<Vec<String>> {
+    options
+        .restrict_env
+        .as_ref()
+        .map(|vars| vars.iter().map(|var| var.to_string()).collect())
+}
+
+/// count the matched items by their sources
+fn count_sources(counts: &mut Vec<(String, usize)>, matched: &[MatchedItem]) {
+    for matched_item in matched.iter() {
+        if let Some(source) = matched_item.item.source() {
+            match counts.iter_mut().find(|(label, _)| *label == source) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((source.into_owned(), 1)),
+            }
+        }
+    }
+}
+
+/// The line between the item list and the preview window, filled with the glyph
+struct Separator {
+    ch: char,
+    attr: Attr,
+}
+
+impl Draw for Separator {
+    fn draw(&self, canvas: &mut dyn Canvas) -> Result<()> {
+        let (width, height) = canvas.size()?;
+        for row in 0..height {
+            for col in 0..width {
+                canvas.put_char_with_attr(row, col, self.ch, self.attr)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Widget<Event> for Separator {}
+
+/// The command run for the command query (`show_cmd`), e.g. `$ rg -n foo`
+struct CmdLine {
+    cmd: Option<String>,
+    attr: Attr,
+}
+
+impl Draw for CmdLine {
+    fn draw(&self, canvas: &mut dyn Canvas) -> Result<()> {
+        canvas.clear()?;
+        if let Some(ref cmd) = self.cmd {
+            canvas.print_with_attr(0, 0, &format!("$ {}", cmd), self.attr)?;
+        }
+        Ok(())
+    }
+}
+
+impl Widget<Event> for CmdLine {}
+
+/// The item list with the preview drawn in the rows left under the current item (`preview_inline`)
+struct InlinePreview<'a> {
+    selection: &'a Selection,
+    previewer: &'a Previewer,
+}
+
+impl<'a> Draw for InlinePreview<'a> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> Result<()> {
+        self.selection.draw(canvas)?;
+        if let Some((top, rows)) = self.selection.inline_preview_area() {
+            // indented to the text of the items, after the cursor label
+            let (width, _height) = canvas.size()?;
+            let indent = min(2, width);
+            let mut area = BoundedCanvas::new(top, indent, width - indent, rows, canvas);
+            self.previewer.draw(&mut area)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Widget<Event> for InlinePreview<'a> {
+    fn on_event(&self, event: TermEvent, rect: Rectangle) -> Vec<Event> {
+        self.selection.on_event(event, rect)
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Copy)]
 enum Direction {
     Up,
@@ -850,3 +1975,233 @@ enum ClearStrategy {
     Clear,
     ClearIfNotNull,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::Action;
+    use crate::options::SkimOptionsBuilder;
+    use crate::util::screen_row;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::channel;
+
+    /// run the headless model on the items, with the events sent before it starts, till it quits
+    fn run_headless(options: &SkimOptions, items: &[&str], events: Vec<Event>) -> (Model, Option<SkimOutput>) {
+        let mut model = headless_model(options, items, events);
+        let output = model.start();
+        (model, output)
+    }
+
+    fn headless_model(options: &SkimOptions, items: &[&str], events: Vec<Event>) -> Model {
+        let (tx_item, rx_item) = crossbeam::channel::unbounded::<Arc<dyn SkimItem>>();
+        for item in items {
+            let _ = tx_item.send(Arc::new(item.to_string()));
+        }
+        drop(tx_item);
+
+        let (tx, rx) = channel();
+        for ev in events {
+            tx.send(ev).unwrap();
+        }
+        let reader = Reader::with_options(options).source(Some(rx_item));
+        Model::headless(rx, tx, reader, 40, 10, options)
+    }
+
+    /// the rows drawn last by the headless model, without the blanks around them
+    fn screen_rows(model: &Model) -> Vec<String> {
+        match model.output {
+            Output::Screen(ref screen) => {
+                let screen = screen.lock();
+                (0..screen.height()).map(|row| screen_row(&screen, row)).collect()
+            }
+            Output::Term(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_custom_action() {
+        let fired = Arc::new(AtomicUsize::new(0));
+        let fired_clone = fired.clone();
+        let custom_actions: Vec<CustomAction> = vec![
+            Arc::new(|_: &ActionContext| vec![]),
+            Arc::new(move |context: &ActionContext| {
+                fired_clone.fetch_add(1, Ordering::SeqCst);
+                vec![Action::Put(format!(" {}", context.query)), Action::Accept(None)]
+            }),
+        ];
+        let options = SkimOptionsBuilder::default()
+            .query(Some("query"))
+            .custom_actions(custom_actions)
+            .build()
+            .unwrap();
+
+        // the actions returned are run after it
+        let (_, output) = run_headless(&options, &["a"], vec![Event::EvActCustom(1)]);
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+        assert_eq!(output.unwrap().query, "query query");
+
+        // not provided
+        let (_, output) = run_headless(&options, &["a"], vec![Event::EvActCustom(5)]);
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+        assert!(output.is_none());
+    }
+
+    #[test]
+    fn test_replay_events() {
+        let options = SkimOptions::default();
+
+        // each event waits for the items to be matched, e.g. the accept for the query typed
+        let events = vec![Event::EvActAddChar('b'), Event::EvActToggle, Event::EvActAccept(None)];
+        let items = ["apple", "banana", "bacon"];
+        let replaying = Arc::new(AtomicBool::new(false));
+        let mut model = headless_model(&options, &items, vec![]).replay_events(events, replaying.clone());
+        assert!(replaying.load(Ordering::SeqCst));
+        let output = model.start().unwrap();
+        assert!(!replaying.load(Ordering::SeqCst));
+        assert_eq!(output.query, "b");
+        assert_eq!(output.selected_items[0].text(), "banana");
+    }
+
+    #[test]
+    fn test_help_closed_by_keys() {
+        let options = SkimOptions::default();
+
+        // scrolled by the moving actions, kept by the events not given by the keys
+        let events = vec![Event::EvActShowHelp, Event::EvActDown(1), Event::EvActSelectRow(0)];
+        let (model, _) = run_headless(&options, &["a"], events);
+        assert!(model.help_shown);
+
+        let (model, _) = run_headless(&options, &["a"], vec![Event::EvActShowHelp, Event::EvActToggle]);
+        assert!(!model.help_shown);
+        let (model, _) = run_headless(&options, &["a"], vec![Event::EvActShowHelp, Event::EvActAddChar('a')]);
+        assert!(!model.help_shown);
+    }
+
+    #[test]
+    fn test_accept_rejected() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = attempts.clone();
+        let on_accept_attempt: AcceptValidator = Arc::new(move |_: &[Arc<dyn SkimItem>]| {
+            attempts_clone.fetch_add(1, Ordering::SeqCst);
+            Err("not yet".to_string())
+        });
+        let options = SkimOptionsBuilder::default()
+            .on_accept_attempt(Some(on_accept_attempt))
+            .build()
+            .unwrap();
+
+        // skim keeps running till there is no more event, the message is shown
+        let events = vec![Event::EvActAccept(None), Event::EvActAcceptAll(None)];
+        let (model, output) = run_headless(&options, &["a"], events);
+        assert!(output.is_none());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(model.status_hint.as_deref(), Some("not yet"));
+    }
+
+    #[test]
+    fn test_confirm_accept() {
+        let options = SkimOptionsBuilder::default()
+            .confirm_accept(Some("accept?"))
+            .build()
+            .unwrap();
+        let items = ["apple", "banana"];
+
+        // the accept waits for `y`, any other key cancels it and is not typed
+        let events = vec![Event::EvActAccept(None), Event::EvActAddChar('n')];
+        let (model, output) = run_headless(&options, &items, events);
+        assert!(output.is_none());
+        assert!(model.pending_accept.is_none());
+        assert_eq!(model.query.get_fz_query(), "");
+
+        // accepted once confirmed after a cancel
+        let events = vec![
+            Event::EvActAccept(None),
+            Event::EvActAddChar('n'),
+            Event::EvActAccept(None),
+            Event::EvActAddChar('y'),
+        ];
+        let replaying = Arc::new(AtomicBool::new(false));
+        let mut model = headless_model(&options, &items, vec![]).replay_events(events, replaying);
+        let output = model.start().unwrap();
+        assert_eq!(output.query, "");
+        assert_eq!(output.selected_items[0].text(), "apple");
+    }
+
+    #[test]
+    fn test_auto_accept_single() {
+        let options = SkimOptionsBuilder::default().auto_accept_single(true).build().unwrap();
+        let items = ["apple", "banana", "bacon"];
+        let replaying = Arc::new(AtomicBool::new(false));
+
+        // the only match is accepted once the query is typed
+        let mut model =
+            headless_model(&options, &items, vec![]).replay_events(vec![Event::EvActAddChar('p')], replaying.clone());
+        let output = model.start().unwrap();
+        assert_eq!(output.query, "p");
+        assert_eq!(output.selected_items[0].text(), "apple");
+
+        // not if there are more matches
+        let mut model =
+            headless_model(&options, &items, vec![]).replay_events(vec![Event::EvActAddChar('b')], replaying);
+        assert!(model.start().is_none());
+        assert_eq!(model.num_options, 2);
+    }
+
+    #[test]
+    fn test_startup_delay() {
+        let options = SkimOptionsBuilder::default().startup_delay_ms(200).build().unwrap();
+
+        // nothing is drawn if it is accepted in the delay
+        let (model, output) = run_headless(&options, &["apple"], vec![Event::EvActAccept(None)]);
+        assert!(output.is_some());
+        assert!(screen_rows(&model).iter().all(String::is_empty));
+
+        // drawn once the delay passed
+        let started_at = Instant::now();
+        let (model, output) = run_headless(&options, &["apple"], vec![]);
+        assert!(output.is_none());
+        assert!(started_at.elapsed() >= Duration::from_millis(200));
+        assert!(screen_rows(&model).iter().any(|row| row.ends_with("apple")));
+
+        // drawn at once without the delay
+        let (model, _) = run_headless(&SkimOptions::default(), &["apple"], vec![Event::EvActAccept(None)]);
+        assert!(!screen_rows(&model).iter().all(String::is_empty));
+    }
+
+    #[test]
+    fn test_select_all_on_start() {
+        let options = SkimOptionsBuilder::default().select_all_on_start(true).build().unwrap();
+        let items = ["apple", "banana", "bacon"];
+
+        // the items matched by the first run are selected
+        let (model, _) = run_headless(&options, &items, vec![]);
+        assert_eq!(model.selection.get_num_selected(), 3);
+        assert!(!model.select_all_on_start);
+
+        // the ones matched later are not
+        let events = vec![
+            Event::EvActAddChar('b'),
+            Event::EvActToggle,
+            Event::EvActBackwardDeleteChar,
+        ];
+        let replaying = Arc::new(AtomicBool::new(false));
+        let mut model = headless_model(&options, &items, vec![]).replay_events(events, replaying);
+        let _ = model.start();
+        assert_eq!(model.selection.get_num_selected(), 2);
+    }
+
+    #[test]
+    fn test_abort_with_current() {
+        let options = SkimOptions::default();
+
+        // the current item only, the selected ones are dropped
+        let events = vec![Event::EvActSelectAll, Event::EvActUp(1), Event::EvActAbortWithCurrent];
+        let replaying = Arc::new(AtomicBool::new(false));
+        let mut model =
+            headless_model(&options, &["apple", "banana", "bacon"], vec![]).replay_events(events, replaying);
+        let output = model.start().unwrap();
+        assert!(output.is_abort);
+        assert_eq!(output.selected_items.len(), 1);
+        assert_eq!(output.selected_items[0].text(), "banana");
+    }
+}
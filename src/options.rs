@@ -1,55 +1,122 @@
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use derive_builder::Builder;
 
-use crate::{CaseMatching, FuzzyAlgorithm, MatchEngineFactory};
+#[cfg(feature = "async-preview")]
+use crate::AsyncPreview;
+use crate::{
+    AcceptValidator, CaseMatching, ColorScheme, CrLf, CustomAction, DiagnosticsCallback, FinalOutputTransform,
+    FuzzyAlgorithm, ItemCallback, ItemPredicate, Keymap, MatchEngineFactory, MatchTransform, OutputTransform,
+    PresortKey, QueryCase, ScoreFn,
+};
 
 #[derive(Builder)]
 #[builder(build_fn(name = "final_build"))]
 #[builder(default)]
 pub struct SkimOptions<'a> {
     pub bind: Vec<&'a str>,
+    pub keymap: Keymap,
+    pub custom_actions: Vec<CustomAction>,
+    pub custom_action_labels: Vec<String>,
     pub multi: bool,
+    pub select_all_on_start: bool,
+    pub output_select_order: bool,
+    pub retain_filtered_selection: bool,
     pub prompt: Option<&'a str>,
     pub cmd_prompt: Option<&'a str>,
+    pub placeholder: Option<&'a str>,
     pub expect: Option<String>,
+    pub expect_outputs: HashMap<String, OutputTransform>,
+    pub output_transform: Option<FinalOutputTransform>,
+    pub confirm_accept: Option<&'a str>,
+    pub auto_accept_single: bool,
+    pub record_events_file: Option<&'a str>,
+    pub replay_events_file: Option<&'a str>,
     pub tac: bool,
     pub tiebreak: Option<String>,
     pub ansi: bool,
     pub exact: bool,
+    pub word_match: bool,
     pub cmd: Option<&'a str>,
     pub interactive: bool,
+    pub show_cmd: bool,
     pub query: Option<&'a str>,
     pub cmd_query: Option<&'a str>,
     pub regex: bool,
     pub delimiter: Option<&'a str>,
     pub nth: Option<&'a str>,
+    pub match_prefix_chars: Option<usize>,
+    pub match_skip: Option<&'a str>,
     pub with_nth: Option<&'a str>,
+    pub accept_nth: Option<&'a str>,
     pub replstr: Option<&'a str>,
     pub color: Option<&'a str>,
+    pub color_scheme: ColorScheme,
     pub margin: Option<&'a str>,
     pub no_height: bool,
     pub min_height: Option<&'a str>,
     pub height: Option<&'a str>,
     pub preview: Option<&'a str>,
     pub preview_window: Option<&'a str>,
+    pub preview_only: bool,
+    pub preview_separator: Option<&'a str>,
+    pub preview_stream: bool,
+    pub preview_line_cmd: bool,
+    pub preview_inline: Option<usize>,
+    pub preview_cache_size: usize,
     pub reverse: bool,
     pub read0: bool,
+    pub crlf: CrLf,
+    pub trim: bool,
+    pub dedup: bool,
+    pub memory_budget_bytes: Option<usize>,
     pub print0: bool,
+    pub no_trailing_newline: bool,
     pub tabstop: Option<&'a str>,
     pub print_query: bool,
     pub print_cmd: bool,
     pub print_score: bool,
+    pub print_score_after: bool,
     pub no_hscroll: bool,
     pub no_mouse: bool,
+    pub mouse_scroll_lines: Option<&'a str>,
+    pub no_expand: bool,
+    pub restrict_env: Option<Vec<&'a str>>,
     pub inline_info: bool,
+    pub show_query_debug: bool,
     pub header: Option<&'a str>,
     pub header_lines: usize,
     pub layout: &'a str,
+    pub display_direction: Option<&'a str>,
+    pub grid: bool,
+    pub show_trailing_whitespace: bool,
+    pub line_numbers: bool,
+    pub pin_top: bool,
+    pub follow: bool,
+    pub scrollbar_heatmap: bool,
+    pub max_rendered_rows: Option<usize>,
+    pub startup_delay_ms: u64,
     pub filter: &'a str,
     pub algorithm: FuzzyAlgorithm,
     pub case: CaseMatching,
+    pub query_case: QueryCase,
+    pub single_thread: bool,
+    pub lazy_tail: bool,
+    pub max_query_length: Option<usize>,
+    pub match_budget_ms: Option<u64>,
     pub engine_factory: Option<Rc<dyn MatchEngineFactory>>,
+    pub score_fn: Option<ScoreFn>,
+    pub match_transform: Option<MatchTransform>,
+    pub match_display: bool,
+    pub initial_cursor: Option<ItemPredicate>,
+    pub on_delete_item: Option<ItemCallback>,
+    pub on_accept_attempt: Option<AcceptValidator>,
+    pub presort_key: Option<PresortKey>,
+    #[cfg(feature = "async-preview")]
+    pub async_preview: Option<AsyncPreview>,
+    pub output_matched_items: bool,
+    pub diagnostics: Option<DiagnosticsCallback>,
     pub query_history: &'a [String],
     pub cmd_history: &'a [String],
 }
@@ -58,47 +125,107 @@ impl<'a> Default for SkimOptions<'a> {
     fn default() -> Self {
         Self {
             bind: vec![],
+            keymap: Keymap::new(),
+            custom_actions: Vec::new(),
+            custom_action_labels: Vec::new(),
             multi: false,
+            select_all_on_start: false,
+            output_select_order: false,
+            retain_filtered_selection: true,
             prompt: Some("> "),
             cmd_prompt: Some("c> "),
+            placeholder: None,
             expect: None,
+            expect_outputs: HashMap::new(),
+            output_transform: None,
+            confirm_accept: None,
+            auto_accept_single: false,
+            record_events_file: None,
+            replay_events_file: None,
             tac: false,
             tiebreak: None,
             ansi: false,
             exact: false,
+            word_match: false,
             cmd: None,
             interactive: false,
+            show_cmd: false,
             query: None,
             cmd_query: None,
             regex: false,
             delimiter: None,
             nth: None,
+            match_prefix_chars: None,
+            match_skip: None,
             with_nth: None,
+            accept_nth: None,
             replstr: Some("{}"),
             color: None,
+            color_scheme: ColorScheme::Dark,
             margin: Some("0,0,0,0"),
             no_height: false,
             min_height: Some("10"),
             height: Some("100%"),
             preview: None,
             preview_window: Some("right:50%"),
+            preview_only: false,
+            preview_separator: None,
+            preview_stream: false,
+            preview_inline: None,
+            preview_line_cmd: false,
+            preview_cache_size: 0,
             reverse: false,
             read0: false,
+            crlf: CrLf::default(),
+            trim: false,
+            dedup: false,
+            memory_budget_bytes: None,
             print0: false,
+            no_trailing_newline: false,
             tabstop: None,
             print_query: false,
             print_cmd: false,
             print_score: false,
+            print_score_after: false,
             no_hscroll: false,
             no_mouse: false,
+            mouse_scroll_lines: None,
+            no_expand: false,
+            restrict_env: None,
             inline_info: false,
+            show_query_debug: false,
             header: None,
             header_lines: 0,
             layout: "",
+            display_direction: None,
+            grid: false,
+            show_trailing_whitespace: false,
+            line_numbers: false,
+            pin_top: false,
+            follow: false,
+            scrollbar_heatmap: false,
+            max_rendered_rows: None,
+            startup_delay_ms: 0,
             filter: "",
             algorithm: FuzzyAlgorithm::default(),
             case: CaseMatching::default(),
+            query_case: QueryCase::default(),
+            single_thread: false,
+            lazy_tail: false,
+            max_query_length: None,
+            match_budget_ms: None,
             engine_factory: None,
+            score_fn: None,
+            match_transform: None,
+            match_display: false,
+            initial_cursor: None,
+            on_delete_item: None,
+            on_accept_attempt: None,
+            presort_key: None,
+            #[cfg(feature = "async-preview")]
+            async_preview: None,
+            output_matched_items: false,
+            diagnostics: None,
             query_history: &[],
             cmd_history: &[],
         }
@@ -118,3 +245,40 @@ impl<'a> SkimOptionsBuilder<'a> {
         self.final_build()
     }
 }
+
+/// The environment variable of the default options, e.g. `--ansi --prompt 'files> '`
+pub const DEFAULT_OPTIONS_ENV: &str = "SKIM_DEFAULT_OPTIONS";
+
+/// Split the default options (set by `SKIM_DEFAULT_OPTIONS`) into args like a shell does, they are
+/// to be placed before the args given explicitly, so that the later ones override them.
+pub fn default_options_args() -> Vec<String> {
+    std::env::var(DEFAULT_OPTIONS_ENV)
+        .ok()
+        .map(|val| split_options(&val))
+        .unwrap_or_default()
+}
+
+fn split_options(options: &str) -> Vec<String> {
+    shlex::split(options).unwrap_or_else(|| {
+        warn!(
+            "options: failed to split {}, unbalanced quotes: {}",
+            DEFAULT_OPTIONS_ENV, options
+        );
+        Vec::new()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_options() {
+        assert_eq!(
+            split_options(r#"--ansi --prompt 'files> ' --bind="ctrl-k:kill-line""#),
+            vec!["--ansi", "--prompt", "files> ", "--bind=ctrl-k:kill-line"]
+        );
+        assert!(split_options("").is_empty());
+        assert!(split_options("--prompt 'unbalanced").is_empty());
+    }
+}
@@ -11,6 +11,7 @@ const ORDERED_SIZE: usize = 300;
 pub struct OrderedVec<T: Send> {
     vec: Vec<T>,
     compare: CompareFunction<T>,
+    parallel: bool, // sort with rayon
 }
 
 impl<T: Send> OrderedVec<T> {
@@ -18,18 +19,33 @@ impl<T: Send> OrderedVec<T> {
         OrderedVec {
             vec: Vec::with_capacity(ORDERED_SIZE),
             compare,
+            parallel: true,
         }
     }
 
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
     pub fn append_ordered(&mut self, mut items: Vec<T>) {
         self.vec.append(&mut items);
-        self.vec.par_sort_unstable_by(self.compare.as_ref());
+        if self.parallel {
+            self.vec.par_sort_unstable_by(self.compare.as_ref());
+        } else {
+            self.vec.sort_unstable_by(self.compare.as_ref());
+        }
     }
 
     pub fn get(&self, index: usize) -> Option<&T> {
         self.vec.get(index)
     }
 
+    /// remove the item at `index`, the others are kept in order
+    pub fn remove(&mut self, index: usize) -> T {
+        self.vec.remove(index)
+    }
+
     pub fn len(&self) -> usize {
         self.vec.len()
     }
@@ -1,9 +1,156 @@
 use crate::SkimItem;
+use std::collections::HashMap;
+use std::io::{self, Write};
 use std::sync::Arc;
 
+/// Compute the output of an accepted item, in place of `SkimItem::output`
+pub type OutputTransform = Arc<dyn Fn(&dyn SkimItem) -> String + Send + Sync>;
+
+/// Post-process the outputs of the accepted items (e.g. quote them for the shell), after
+/// `accept_nth` and `expect_outputs`
+pub type FinalOutputTransform = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
 pub struct SkimOutput {
     pub accept_key: Option<String>,
     pub query: String,
     pub cmd: String,
+    /// the selected items, or all the matched items in display order if accepted by `accept-all`
     pub selected_items: Vec<Arc<dyn SkimItem>>,
+    /// the matching scores of `selected_items` (in the same order), higher is better. `None` if the
+    /// item is not matched by the final query.
+    pub selected_scores: Vec<Option<i64>>,
+    /// all the matched items in the final order, regardless of the selection. Only filled if
+    /// `SkimOptions::output_matched_items` is set, empty otherwise.
+    pub matched_items: Vec<Arc<dyn SkimItem>>,
+    /// the outputs of `selected_items` (in the same order), given by the transform registered in
+    /// `SkimOptions::expect_outputs` for the `accept_key`, or by `accepted_output()` if there is
+    /// none, then post-processed by `SkimOptions::output_transform`. Only filled if `expect_outputs`
+    /// is not empty or `output_transform` or `accept_nth` is set, empty otherwise.
+    pub selected_outputs: Vec<String>,
+    /// aborted by `abort-with-current`, `selected_items` is the current item (if any) then, which is
+    /// not chosen deliberately as by `accept`.
+    pub is_abort: bool,
+    /// the items removed by `delete-item`, in the order they are removed
+    pub deleted_items: Vec<Arc<dyn SkimItem>>,
+    /// the item and the action chosen in the menu of `SkimItem::actions` (by `action-menu`), the
+    /// item is the only one in `selected_items` then.
+    pub item_action: Option<(Arc<dyn SkimItem>, String)>,
+}
+
+/// the outputs of the items accepted by `accept_key`
+pub(crate) fn outputs_of(
+    items: &[Arc<dyn SkimItem>],
+    accept_key: Option<&str>,
+    expect_outputs: &HashMap<String, OutputTransform>,
+    output_transform: Option<&FinalOutputTransform>,
+) -> Vec<String> {
+    let outputs: Vec<String> = match accept_key.and_then(|key| expect_outputs.get(key)) {
+        Some(transform) => items.iter().map(|item| transform(item.as_ref())).collect(),
+        None => items.iter().map(|item| item.accepted_output().to_string()).collect(),
+    };
+
+    match output_transform {
+        Some(transform) => outputs.iter().map(|output| transform(output)).collect(),
+        None => outputs,
+    }
+}
+
+/// Writes the lines of the output, each ended by `ending` (e.g. `\n`, or `\0` by `--print0`). Unless
+/// `trailing`, the ending of a line is held till the next one is written, so that the last line is
+/// not ended (`--no-trailing-newline`, e.g. for the command substitution).
+pub struct OutputWriter<W: Write> {
+    out: W,
+    ending: &'static str,
+    trailing: bool,
+    ending_held: bool,
+}
+
+impl<W: Write> OutputWriter<W> {
+    pub fn new(out: W, ending: &'static str, trailing: bool) -> Self {
+        Self {
+            out,
+            ending,
+            trailing,
+            ending_held: false,
+        }
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.ending_held {
+            self.out.write_all(self.ending.as_bytes())?;
+        }
+        self.out.write_all(line.as_bytes())?;
+        if self.trailing {
+            self.out.write_all(self.ending.as_bytes())
+        } else {
+            self.ending_held = true;
+            Ok(())
+        }
+    }
+
+    /// flush the lines written so far, the held ending (if any) is not written
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_output_writer() {
+        let mut out = Vec::new();
+        let mut writer = OutputWriter::new(&mut out, "\n", true);
+        writer.write_line("a").unwrap();
+        writer.write_line("b").unwrap();
+        assert_eq!(out, b"a\nb\n");
+
+        let mut out = Vec::new();
+        let mut writer = OutputWriter::new(&mut out, "\0", false);
+        writer.write_line("a").unwrap();
+        writer.write_line("b").unwrap();
+        assert_eq!(out, b"a\0b");
+    }
+
+    #[test]
+    fn test_outputs_of() {
+        let items: Vec<Arc<dyn SkimItem>> = vec![Arc::new("src/lib.rs"), Arc::new("README.md")];
+        let mut expect_outputs: HashMap<String, OutputTransform> = HashMap::new();
+        expect_outputs.insert(
+            "ctrl-y".to_string(),
+            Arc::new(|item| item.text().rsplit('/').next().unwrap_or("").to_string()),
+        );
+
+        assert_eq!(
+            outputs_of(&items, None, &expect_outputs, None),
+            vec!["src/lib.rs", "README.md"]
+        );
+        assert_eq!(
+            outputs_of(&items, Some("ctrl-e"), &expect_outputs, None),
+            vec!["src/lib.rs", "README.md"]
+        );
+        assert_eq!(
+            outputs_of(&items, Some("ctrl-y"), &expect_outputs, None),
+            vec!["lib.rs", "README.md"]
+        );
+    }
+
+    #[test]
+    fn test_output_transform() {
+        let items: Vec<Arc<dyn SkimItem>> = vec![Arc::new("src/lib.rs"), Arc::new("it's")];
+        let mut expect_outputs: HashMap<String, OutputTransform> = HashMap::new();
+        expect_outputs.insert("ctrl-y".to_string(), Arc::new(|item| item.text().to_uppercase()));
+        let quote: FinalOutputTransform = Arc::new(|output| format!("'{}'", output.replace('\'', "'\\''")));
+
+        assert_eq!(
+            outputs_of(&items, None, &expect_outputs, Some(&quote)),
+            vec!["'src/lib.rs'", "'it'\\''s'"]
+        );
+        // applied after the transform of the accept key
+        assert_eq!(
+            outputs_of(&items, Some("ctrl-y"), &expect_outputs, Some(&quote)),
+            vec!["'SRC/LIB.RS'", "'IT'\\''S'"]
+        );
+    }
 }
@@ -1,10 +1,11 @@
 pub use crate::ansi::AnsiString;
 pub use crate::engine::{factory::*, fuzzy::FuzzyAlgorithm};
 pub use crate::item_collector::{read_and_collect_from_command, CollectorInput, CollectorOption, SkimItemReader};
-pub use crate::options::{SkimOptions, SkimOptionsBuilder};
+pub use crate::options::{default_options_args, SkimOptions, SkimOptionsBuilder};
 pub use crate::output::SkimOutput;
 pub use crate::*;
 pub use crossbeam::channel::{bounded, unbounded, Receiver, Sender};
 pub use std::borrow::Cow;
 pub use std::sync::atomic::{AtomicUsize, Ordering};
 pub use std::sync::Arc;
+pub use tuikit::key::Key;
@@ -1,24 +1,33 @@
 use crate::ansi::{ANSIParser, AnsiString};
+use crate::diagnostics;
 use crate::event::{Event, EventHandler, UpdateScreen};
 use crate::item::ItemWrapper;
 use crate::spinlock::SpinLock;
-use crate::util::{depends_on_items, inject_command, InjectContext};
+use crate::util::{depends_on_items, expand_command_in, inject_command, restrict_env, InjectContext};
+#[cfg(feature = "async-preview")]
+use crate::AsyncPreview;
 use crate::{ItemPreview, SkimItem};
 use derive_builder::Builder;
+#[cfg(feature = "async-preview")]
+use futures::future::{self, AbortHandle, BoxFuture};
 use nix::libc;
 use regex::Regex;
 use std::borrow::Cow;
 use std::cmp::{max, min};
+use std::collections::VecDeque;
 use std::env;
+use std::io::{BufRead, BufReader};
 use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use tuikit::prelude::{Event as TermEvent, *};
 
 const TAB_STOP: usize = 8;
+const STREAM_REFRESH_INTERVAL: Duration = Duration::from_millis(100);
 const DELIMITER_STR: &str = r"[\t\n ]+";
 
 pub struct Previewer {
@@ -30,14 +39,26 @@ pub struct Previewer {
     hscroll_offset: usize,
     vscroll_offset: usize,
     wrap: bool,
+    scroll_lines: i32, // the lines to scroll per tick of the mouse wheel
 
     prev_item: Option<Arc<ItemWrapper>>,
     prev_query: Option<String>,
     prev_cmd_query: Option<String>,
     prev_num_selected: usize,
+    prev_size: (usize, usize), // (columns, lines) of the last preview
+    refresh: bool,             // re-run the preview even if nothing changed
 
     preview_cmd: Option<String>,
+    line_cmd: bool, // run the lines of the items as the preview commands
+    cache: Arc<SpinLock<PreviewCache>>,
+    expand_command: bool,              // expand `~` and env vars in preview commands
+    restrict_env: Option<Vec<String>>, // the only env vars passed to the preview commands
+    stream: bool,
     delimiter: Regex,
+    #[cfg(feature = "async-preview")]
+    async_preview: Option<AsyncPreview>,
+    #[cfg(any(test, feature = "test-render"))]
+    pending: Arc<AtomicBool>, // a preview is asked for and its output has not come yet
     thread_previewer: Option<JoinHandle<()>>,
 }
 
@@ -49,9 +70,17 @@ impl Previewer {
         let content_lines = Arc::new(SpinLock::new(Vec::new()));
         let (tx_preview, rx_preview) = channel();
         let content_clone = content_lines.clone();
-        let thread_previewer = thread::spawn(move || {
-            run(rx_preview, move |lines| {
+        let cache = Arc::new(SpinLock::new(PreviewCache::new(0)));
+        let cache_clone = cache.clone();
+        #[cfg(any(test, feature = "test-render"))]
+        let pending = Arc::new(AtomicBool::new(false));
+        #[cfg(any(test, feature = "test-render"))]
+        let pending_clone = pending.clone();
+        let thread_previewer = diagnostics::spawn(move || {
+            run(rx_preview, cache_clone, move |lines| {
                 *content_clone.lock() = lines;
+                #[cfg(any(test, feature = "test-render"))]
+                pending_clone.store(false, Ordering::SeqCst);
                 callback();
             })
         });
@@ -65,14 +94,26 @@ impl Previewer {
             hscroll_offset: 0,
             vscroll_offset: 0,
             wrap: false,
+            scroll_lines: 1,
 
             prev_item: None,
             prev_query: None,
             prev_cmd_query: None,
             prev_num_selected: 0,
+            prev_size: (0, 0),
+            refresh: false,
 
             preview_cmd,
+            line_cmd: false,
+            cache,
+            expand_command: true,
+            restrict_env: None,
+            stream: false,
             delimiter: Regex::new(DELIMITER_STR).unwrap(),
+            #[cfg(feature = "async-preview")]
+            async_preview: None,
+            #[cfg(any(test, feature = "test-render"))]
+            pending,
             thread_previewer: Some(thread_previewer),
         }
     }
@@ -82,11 +123,55 @@ impl Previewer {
         self
     }
 
+    pub fn scroll_lines(mut self, scroll_lines: i32) -> Self {
+        self.scroll_lines = max(1, scroll_lines);
+        self
+    }
+
     pub fn delimiter(mut self, delimiter: Regex) -> Self {
         self.delimiter = delimiter;
         self
     }
 
+    pub fn expand_command(mut self, expand_command: bool) -> Self {
+        self.expand_command = expand_command;
+        self
+    }
+
+    /// pass only the environment variables `restrict_env` to the preview commands, all if `None`
+    pub fn restrict_env(mut self, restrict_env: Option<Vec<String>>) -> Self {
+        self.restrict_env = restrict_env;
+        self
+    }
+
+    /// show the output of preview commands as it is read (e.g. `tail -f`) instead of on exit, the
+    /// streamed outputs are not cached.
+    pub fn stream(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    /// preview the items (of the global preview) by running their lines (`output()`) as the commands,
+    /// as is, i.e. no fields or queries are injected. It takes precedence over the preview command.
+    pub fn line_cmd(mut self, line_cmd: bool) -> Self {
+        self.line_cmd = line_cmd;
+        self
+    }
+
+    /// preview the items (of the global preview) by the futures given by the function instead of the
+    /// commands, the pending one is dropped once the item changes. The outputs are not cached.
+    #[cfg(feature = "async-preview")]
+    pub fn async_preview(mut self, async_preview: Option<AsyncPreview>) -> Self {
+        self.async_preview = async_preview;
+        self
+    }
+
+    /// number of the outputs of preview commands to keep, 0 to disable the cache
+    pub fn cache_size(self, cache_size: usize) -> Self {
+        self.cache.lock().capacity = cache_size;
+        self
+    }
+
     pub fn on_item_change(
         &mut self,
         new_item: impl Into<Option<Arc<ItemWrapper>>>,
@@ -122,19 +207,48 @@ impl Previewer {
 
         let selected_items_changed = self.prev_num_selected != num_selected;
 
-        if !item_changed && !query_changed && !cmd_query_changed && !selected_items_changed {
+        let columns = self.width.load(Ordering::Relaxed);
+        let lines = self.height.load(Ordering::Relaxed);
+        let size_changed = self.prev_size != (columns, lines);
+
+        if !item_changed
+            && !query_changed
+            && !cmd_query_changed
+            && !selected_items_changed
+            && !size_changed
+            && !self.refresh
+        {
             return;
         }
 
+        let refresh = self.refresh;
+        self.refresh = false;
+
         self.prev_item = new_item.clone();
         self.prev_query = new_query;
         self.prev_cmd_query = new_cmd_query;
         self.prev_num_selected = num_selected;
+        self.prev_size = (columns, lines);
 
         let preview_event = match new_item {
             Some(item) => match item.preview() {
                 ItemPreview::Text(text) => PreviewEvent::PreviewPlainText(text),
                 ItemPreview::AnsiText(text) => PreviewEvent::PreviewAnsiText(text),
+                #[cfg(feature = "async-preview")]
+                ItemPreview::Global if self.async_preview.is_some() => {
+                    let async_preview = self.async_preview.as_ref().unwrap();
+                    PreviewEvent::PreviewFuture(async_preview(item.get_inner()))
+                }
+                ItemPreview::Global if self.line_cmd => {
+                    let preview_command = PreviewCommand {
+                        cmd: item.output().into_owned(),
+                        columns,
+                        lines,
+                        stream: self.stream,
+                        env: self.restrict_env.clone(),
+                    };
+                    PreviewEvent::PreviewCommand(preview_command, item.text().to_string(), refresh)
+                }
                 preview => {
                     let cmd = match preview {
                         ItemPreview::Command(cmd) => cmd,
@@ -142,6 +256,13 @@ impl Previewer {
                         ItemPreview::Text(_) | ItemPreview::AnsiText(_) => unreachable!(),
                     };
 
+                    // expand before the injection, so that the items' contents are kept as is
+                    let cmd = if self.expand_command {
+                        expand_command_in(&cmd, self.restrict_env.as_deref()).to_string()
+                    } else {
+                        cmd
+                    };
+
                     if depends_on_items(&cmd) && self.prev_item.is_none() {
                         debug!("the command for preview refers to items and currently there is no item");
                         debug!("command to execute: [{}]", cmd);
@@ -173,20 +294,35 @@ impl Previewer {
 
                     let cmd = inject_command(&cmd, context).to_string();
 
-                    let columns = self.width.load(Ordering::Relaxed);
-                    let lines = self.height.load(Ordering::Relaxed);
-                    let preview_command = PreviewCommand { cmd, columns, lines };
+                    let preview_command = PreviewCommand {
+                        cmd,
+                        columns,
+                        lines,
+                        stream: self.stream,
+                        env: self.restrict_env.clone(),
+                    };
 
-                    PreviewEvent::PreviewCommand(preview_command)
+                    PreviewEvent::PreviewCommand(preview_command, item.text().to_string(), refresh)
                 }
             },
             None => PreviewEvent::PreviewPlainText("".to_string()),
         };
 
+        #[cfg(any(test, feature = "test-render"))]
+        self.pending.store(true, Ordering::SeqCst);
         let _ = self.tx_preview.send(preview_event);
 
-        self.hscroll_offset = 0;
-        self.vscroll_offset = 0;
+        // keep the scroll position if only the size of the preview window changed or on refresh
+        if item_changed || query_changed || cmd_query_changed || selected_items_changed {
+            self.hscroll_offset = 0;
+            self.vscroll_offset = 0;
+        }
+    }
+
+    /// whether the output of the last preview asked for has not come yet
+    #[cfg(any(test, feature = "test-render"))]
+    pub fn is_pending(&self) -> bool {
+        self.pending.load(Ordering::SeqCst)
     }
 
     fn act_scroll_down(&mut self, diff: i32) {
@@ -216,6 +352,12 @@ impl Previewer {
     fn act_toggle_wrap(&mut self) {
         self.wrap = !self.wrap;
     }
+
+    /// The preview command is re-run on the next `on_item_change` bypassing the cache, the running
+    /// one is killed. The model sends a redraw for it.
+    fn act_refresh(&mut self) {
+        self.refresh = true;
+    }
 }
 
 impl Drop for Previewer {
@@ -231,6 +373,7 @@ impl EventHandler for Previewer {
         let height = self.height.load(Ordering::Relaxed);
         match event {
             EvActTogglePreviewWrap => self.act_toggle_wrap(),
+            EvActRefreshPreview => self.act_refresh(),
             EvActPreviewUp(diff) => self.act_scroll_down(-*diff),
             EvActPreviewDown(diff) => self.act_scroll_down(*diff),
             EvActPreviewLeft(diff) => self.act_scroll_right(-*diff),
@@ -290,31 +433,89 @@ impl Widget<Event> for Previewer {
     fn on_event(&self, event: TermEvent, _rect: Rectangle) -> Vec<Event> {
         let mut ret = vec![];
         match event {
-            TermEvent::Key(Key::MousePress(MouseButton::WheelUp, ..)) => ret.push(Event::EvActPreviewUp(1)),
-            TermEvent::Key(Key::MousePress(MouseButton::WheelDown, ..)) => ret.push(Event::EvActPreviewDown(1)),
+            TermEvent::Key(Key::MousePress(MouseButton::WheelUp, ..)) => {
+                ret.push(Event::EvActPreviewUp(self.scroll_lines))
+            }
+            TermEvent::Key(Key::MousePress(MouseButton::WheelDown, ..)) => {
+                ret.push(Event::EvActPreviewDown(self.scroll_lines))
+            }
             _ => {}
         }
         ret
     }
 }
 
-#[derive(Debug, Ord, PartialOrd, PartialEq, Eq)]
+#[derive(Debug, Clone, Ord, PartialOrd, PartialEq, Eq)]
 pub struct PreviewCommand {
     pub cmd: String,
     pub lines: usize,
     pub columns: usize,
+    pub stream: bool,             // show the output as it is read, till the command exits or is killed
+    pub env: Option<Vec<String>>, // the only env vars passed to the command, all if `None`
 }
 
-#[derive(Debug)]
 enum PreviewEvent {
-    PreviewCommand(PreviewCommand),
+    // (command, text of the item, bypass the cache)
+    PreviewCommand(PreviewCommand, String, bool),
     PreviewPlainText(String),
     PreviewAnsiText(String),
+    // the text (could contain ANSI codes) the future resolves to
+    #[cfg(feature = "async-preview")]
+    PreviewFuture(BoxFuture<'static, String>),
     Abort,
 }
 
+/// (text of the item, command), the command is included as it also depends on the query, etc.
+type PreviewCacheKey = (String, PreviewCommand);
+
+/// The outputs of the recent preview commands, the least recently used one is evicted first.
+struct PreviewCache {
+    capacity: usize,
+    entries: VecDeque<(PreviewCacheKey, Vec<AnsiString<'static>>)>, // most recently used at the back
+}
+
+impl PreviewCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    fn get(&mut self, key: &PreviewCacheKey) -> Option<Vec<AnsiString<'static>>> {
+        let pos = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(pos)?;
+        let lines = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(lines)
+    }
+
+    fn put(&mut self, key: PreviewCacheKey, lines: &[AnsiString<'static>]) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.remove(&key);
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, lines.to_vec()));
+    }
+
+    fn remove(&mut self, key: &PreviewCacheKey) {
+        self.entries.retain(|(k, _)| k != key);
+    }
+}
+
+/// The thread waiting for the preview, of the command process or the future
+enum PreviewTask {
+    Process(u32),
+    #[cfg(feature = "async-preview")]
+    Future(AbortHandle),
+}
+
 struct PreviewThread {
-    pid: u32,
+    task: PreviewTask,
     thread: thread::JoinHandle<()>,
     stopped: Arc<AtomicBool>,
 }
@@ -322,13 +523,19 @@ struct PreviewThread {
 impl PreviewThread {
     fn kill(self) {
         if !self.stopped.load(Ordering::Relaxed) {
-            unsafe { libc::kill(self.pid as i32, libc::SIGKILL) };
+            match self.task {
+                PreviewTask::Process(pid) => unsafe {
+                    libc::kill(pid as i32, libc::SIGKILL);
+                },
+                #[cfg(feature = "async-preview")]
+                PreviewTask::Future(abort_handle) => abort_handle.abort(),
+            }
         }
         self.thread.join().expect("Failed to join Preview process");
     }
 }
 
-fn run<C>(rx_preview: Receiver<PreviewEvent>, on_return: C)
+fn run<C>(rx_preview: Receiver<PreviewEvent>, cache: Arc<SpinLock<PreviewCache>>, on_return: C)
 where
     C: Fn(Vec<AnsiString<'static>>) + Send + Sync + 'static,
 {
@@ -354,20 +561,35 @@ where
         }
 
         match event {
-            PreviewEvent::PreviewCommand(preview_cmd) => {
+            PreviewEvent::PreviewCommand(preview_cmd, item_text, refresh) => {
                 let cmd = &preview_cmd.cmd;
                 if cmd == "" {
                     continue;
                 }
 
+                let key = (item_text, preview_cmd.clone());
+                if preview_cmd.stream {
+                    // live, nothing to cache
+                } else if refresh {
+                    cache.lock().remove(&key);
+                } else if let Some(lines) = cache.lock().get(&key) {
+                    callback(lines);
+                    continue;
+                }
+
                 let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
-                let spawned = Command::new(shell)
+                let mut command = Command::new(shell);
+                let spawned = restrict_env(&mut command, preview_cmd.env.as_deref())
                     .env("LINES", preview_cmd.lines.to_string())
                     .env("COLUMNS", preview_cmd.columns.to_string())
                     .arg("-c")
                     .arg(&cmd)
                     .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
+                    .stderr(if preview_cmd.stream {
+                        Stdio::null()
+                    } else {
+                        Stdio::piped()
+                    })
                     .spawn();
 
                 match spawned {
@@ -381,13 +603,29 @@ where
                         let stopped = Arc::new(AtomicBool::new(false));
                         let stopped_clone = stopped.clone();
                         let callback_clone = callback.clone();
-                        let thread = thread::spawn(move || {
-                            wait(spawned, move |lines| {
+                        let cache_clone = cache.clone();
+                        let thread = if preview_cmd.stream {
+                            diagnostics::spawn(move || {
+                                stream(spawned, move |lines| callback_clone(lines));
                                 stopped_clone.store(true, Ordering::SeqCst);
-                                callback_clone(lines);
                             })
+                        } else {
+                            diagnostics::spawn(move || {
+                                wait(spawned, move |lines, success| {
+                                    stopped_clone.store(true, Ordering::SeqCst);
+                                    // the killed or failed ones are not cached
+                                    if success {
+                                        cache_clone.lock().put(key, &lines);
+                                    }
+                                    callback_clone(lines);
+                                })
+                            })
+                        };
+                        preview_thread = Some(PreviewThread {
+                            task: PreviewTask::Process(pid),
+                            thread,
+                            stopped,
                         });
-                        preview_thread = Some(PreviewThread { pid, thread, stopped });
                     }
                 }
             }
@@ -399,6 +637,27 @@ where
                 let color_lines = text.lines().map(|line| parser.parse_ansi(line)).collect();
                 callback(color_lines);
             }
+            #[cfg(feature = "async-preview")]
+            PreviewEvent::PreviewFuture(preview) => {
+                // driven by its own thread, aborted (i.e. dropped) by the next preview
+                let (preview, abort_handle) = future::abortable(preview);
+                let stopped = Arc::new(AtomicBool::new(false));
+                let stopped_clone = stopped.clone();
+                let callback_clone = callback.clone();
+                let thread = diagnostics::spawn(move || {
+                    let text = futures::executor::block_on(preview);
+                    stopped_clone.store(true, Ordering::SeqCst);
+                    if let Ok(text) = text {
+                        let mut parser = ANSIParser::default();
+                        callback_clone(text.lines().map(|line| parser.parse_ansi(line)).collect());
+                    }
+                });
+                preview_thread = Some(PreviewThread {
+                    task: PreviewTask::Future(abort_handle),
+                    thread,
+                    stopped,
+                });
+            }
             PreviewEvent::Abort => return,
         };
     }
@@ -406,7 +665,7 @@ where
 
 fn wait<C>(spawned: std::process::Child, callback: C)
 where
-    C: Fn(Vec<AnsiString<'static>>),
+    C: FnOnce(Vec<AnsiString<'static>>, bool),
 {
     let output = spawned.wait_with_output();
 
@@ -424,6 +683,38 @@ where
     });
 
     let lines = out_str.lines().map(AnsiString::parse).collect();
+    callback(lines, output.status.success());
+}
+
+/// read the output line by line, the lines read so far are given to the callback whenever the
+/// available output is consumed (or a while passed since the last time).
+fn stream<C>(mut spawned: std::process::Child, callback: C)
+where
+    C: Fn(Vec<AnsiString<'static>>),
+{
+    let stdout = match spawned.stdout.take() {
+        Some(stdout) => stdout,
+        None => return,
+    };
+
+    let mut reader = BufReader::new(stdout);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut last_update = Instant::now();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => lines.push(AnsiString::parse(line.trim_end_matches(&['\n', '\r'][..]))),
+        }
+
+        if reader.buffer().is_empty() || last_update.elapsed() >= STREAM_REFRESH_INTERVAL {
+            callback(lines.clone());
+            last_update = Instant::now();
+        }
+    }
+
+    let _ = spawned.wait();
     callback(lines);
 }
 
@@ -524,3 +815,113 @@ impl Printer {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(text: &str) -> PreviewCacheKey {
+        let cmd = PreviewCommand {
+            cmd: format!("cat {}", text),
+            lines: 10,
+            columns: 80,
+            stream: false,
+            env: None,
+        };
+        (text.to_string(), cmd)
+    }
+
+    fn cached(cache: &mut PreviewCache, text: &str) -> Option<String> {
+        cache
+            .get(&key(text))
+            .map(|lines| lines.iter().map(|line| line.stripped().to_string()).collect())
+    }
+
+    #[test]
+    fn test_preview_cache() {
+        let mut cache = PreviewCache::new(0);
+        cache.put(key("a"), &["A".into()]);
+        assert_eq!(cached(&mut cache, "a"), None);
+
+        let mut cache = PreviewCache::new(2);
+        cache.put(key("a"), &["A".into()]);
+        cache.put(key("b"), &["B".into()]);
+        assert_eq!(cached(&mut cache, "a"), Some("A".to_string()));
+
+        // "b" is the least recently used one
+        cache.put(key("c"), &["C".into()]);
+        assert_eq!(cached(&mut cache, "b"), None);
+        assert_eq!(cached(&mut cache, "a"), Some("A".to_string()));
+        assert_eq!(cached(&mut cache, "c"), Some("C".to_string()));
+
+        cache.remove(&key("a"));
+        assert_eq!(cached(&mut cache, "a"), None);
+    }
+
+    #[cfg(feature = "async-preview")]
+    #[test]
+    fn test_preview_future() {
+        struct Dropped(Arc<AtomicBool>);
+
+        impl Drop for Dropped {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let (tx_preview, rx_preview) = channel();
+        let previews = Arc::new(SpinLock::new(Vec::new()));
+        let previews_clone = previews.clone();
+        let thread_previewer = thread::spawn(move || {
+            run(
+                rx_preview,
+                Arc::new(SpinLock::new(PreviewCache::new(0))),
+                move |lines| {
+                    let text: Vec<String> = lines.iter().map(|line| line.stripped().to_string()).collect();
+                    previews_clone.lock().push(text.join("\n"));
+                },
+            )
+        });
+
+        // the pending one is dropped by the next preview
+        let dropped = Arc::new(AtomicBool::new(false));
+        let guard = Dropped(dropped.clone());
+        let pending = async move {
+            let _guard = guard;
+            future::pending::<()>().await;
+            "pending".to_string()
+        };
+        tx_preview.send(PreviewEvent::PreviewFuture(Box::pin(pending))).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        tx_preview
+            .send(PreviewEvent::PreviewFuture(Box::pin(future::ready("a\nb".to_string()))))
+            .unwrap();
+        thread::sleep(Duration::from_millis(50));
+        tx_preview.send(PreviewEvent::Abort).unwrap();
+        thread_previewer.join().unwrap();
+
+        assert!(dropped.load(Ordering::SeqCst));
+        assert_eq!(*previews.lock(), vec!["a\nb".to_string()]);
+    }
+
+    #[test]
+    fn test_stream() {
+        let spawned = Command::new("sh")
+            .arg("-c")
+            .arg("printf 'a\\nb\\r\\n'; sleep 0.1; printf 'c'")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let updates = Arc::new(SpinLock::new(Vec::new()));
+        let updates_clone = updates.clone();
+        stream(spawned, move |lines| {
+            let lines: Vec<String> = lines.iter().map(|line| line.stripped().to_string()).collect();
+            updates_clone.lock().push(lines.join(","));
+        });
+
+        let updates = updates.lock();
+        assert!(updates.contains(&"a,b".to_string()));
+        assert_eq!(updates.last(), Some(&"a,b,c".to_string()));
+    }
+}
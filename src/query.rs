@@ -26,6 +26,7 @@ pub struct Query {
     replstr: String,
     query_prompt: String,
     cmd_prompt: String,
+    placeholder: Option<String>, // shown after the prompt while the query is empty
 
     cmd_history_before: Vec<String>,
     cmd_history_after: Vec<String>,
@@ -49,6 +50,7 @@ impl Query {
             replstr: "{}".to_string(),
             query_prompt: "> ".to_string(),
             cmd_prompt: "c> ".to_string(),
+            placeholder: None,
 
             cmd_history_before: Vec::new(),
             cmd_history_after: Vec::new(),
@@ -127,6 +129,10 @@ impl Query {
             self.cmd_prompt = cmd_prompt.to_string();
         }
 
+        if let Some(placeholder) = options.placeholder {
+            self.placeholder = Some(placeholder.to_string());
+        }
+
         self.fz_query_history_before = options.query_history.to_vec();
         self.cmd_history_before = options.cmd_history.to_vec();
     }
@@ -157,7 +163,13 @@ impl Query {
             .collect()
     }
 
-    fn get_query(&mut self) -> String {
+    /// the query is the one of the command (interactive mode)
+    pub fn in_cmd_mode(&self) -> bool {
+        self.mode == QueryMode::CMD
+    }
+
+    /// the query of the current mode
+    pub fn get_query(&self) -> String {
         match self.mode {
             QueryMode::QUERY => self.get_fz_query(),
             QueryMode::CMD => self.get_cmd_query(),
@@ -185,6 +197,16 @@ impl Query {
         }
     }
 
+    /// the placeholder to show instead of the query, if the query is empty (not the command)
+    fn get_placeholder(&self) -> Option<&str> {
+        match self.mode {
+            QueryMode::QUERY if self.fz_query_before.is_empty() && self.fz_query_after.is_empty() => {
+                self.placeholder.as_deref()
+            }
+            _ => None,
+        }
+    }
+
     fn get_query_ref(&mut self) -> (&mut Vec<char>, &mut Vec<char>) {
         match self.mode {
             QueryMode::QUERY => (&mut self.fz_query_before, &mut self.fz_query_after),
@@ -228,6 +250,19 @@ impl Query {
         before.push(ch);
     }
 
+    pub fn act_put(&mut self, text: &str) {
+        let (before, _) = self.get_query_ref();
+        before.extend(text.chars());
+    }
+
+    /// replace the query (of the current mode), the cursor is placed at the end
+    pub fn act_set_query(&mut self, query: &str) {
+        let (before, after) = self.get_query_ref();
+        before.clear();
+        after.clear();
+        before.extend(query.chars());
+    }
+
     pub fn act_backward_delete_char(&mut self) {
         let (before, _) = self.get_query_ref();
         let _ = before.pop();
@@ -439,6 +474,10 @@ impl EventHandler for Query {
                 self.act_add_char(*ch);
             }
 
+            EvActPut(text) => {
+                self.act_put(text);
+            }
+
             EvActDeleteChar | EvActDeleteCharEOF => {
                 self.act_delete_char();
             }
@@ -527,6 +566,9 @@ impl Draw for Query {
         let before_width = canvas.print_with_attr(0, prompt_width, &before, self.theme.query())?;
         let col = prompt_width + before_width;
         canvas.print_with_attr(0, col, &after, self.theme.query())?;
+        if let Some(placeholder) = self.get_placeholder() {
+            canvas.print_with_attr(0, col, placeholder, self.theme.placeholder())?;
+        }
         canvas.set_cursor(0, col)?;
         canvas.show_cursor(true)?;
         Ok(())
@@ -538,13 +580,18 @@ impl Widget<Event> for Query {
         let before = self.get_before();
         let after = self.get_after();
         let prompt = self.get_prompt();
-        (Some(prompt.width() + before.width() + after.width() + 1), None)
+        let placeholder = self.get_placeholder().unwrap_or("");
+        (
+            Some(prompt.width() + before.width() + after.width() + placeholder.width() + 1),
+            None,
+        )
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::Query;
+    use crate::options::SkimOptionsBuilder;
 
     #[test]
     fn test_new_query() {
@@ -566,6 +613,26 @@ mod test {
         assert_eq!(query1.get_fz_query(), "ab中");
     }
 
+    #[test]
+    fn test_put() {
+        let mut query = Query::builder().fz_query("ab").build();
+        query.act_backward_char();
+        query.act_put("中 x");
+        assert_eq!(query.get_fz_query(), "a中 xb");
+        query.act_add_char('!');
+        assert_eq!(query.get_fz_query(), "a中 x!b");
+    }
+
+    #[test]
+    fn test_set_query() {
+        let mut query = Query::builder().fz_query("gs").build();
+        query.act_backward_char();
+        query.act_set_query("git status");
+        assert_eq!(query.get_fz_query(), "git status");
+        query.act_add_char('!');
+        assert_eq!(query.get_query(), "git status!");
+    }
+
     #[test]
     fn test_backward_delete_char() {
         let mut query = Query::builder().fz_query("AB中c").build();
@@ -586,4 +653,42 @@ mod test {
         query.act_backward_delete_char();
         assert_eq!(query.get_fz_query(), "");
     }
+
+    #[test]
+    fn test_prompt_of_mode() {
+        let query = Query::builder().build();
+        assert_eq!(query.get_prompt(), "> ");
+
+        let mut query = Query::builder().build();
+        query.act_query_toggle_interactive();
+        assert_eq!(query.get_prompt(), "c> ");
+
+        let options = SkimOptionsBuilder::default()
+            .prompt(Some("q: "))
+            .cmd_prompt(Some("$ "))
+            .build()
+            .unwrap();
+        let mut query = Query::from_options(&options).build();
+        assert_eq!(query.get_prompt(), "q: ");
+        query.act_query_toggle_interactive();
+        assert_eq!(query.get_prompt(), "$ ");
+    }
+
+    #[test]
+    fn test_placeholder() {
+        let options = SkimOptionsBuilder::default()
+            .placeholder(Some("type to filter..."))
+            .build()
+            .unwrap();
+        let mut query = Query::from_options(&options).build();
+        assert_eq!(query.get_placeholder(), Some("type to filter..."));
+
+        query.act_add_char('a');
+        assert_eq!(query.get_placeholder(), None);
+        assert_eq!(query.get_fz_query(), "a");
+
+        query.act_backward_delete_char();
+        query.act_query_toggle_interactive();
+        assert_eq!(query.get_placeholder(), None);
+    }
 }
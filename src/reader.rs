@@ -1,6 +1,7 @@
 ///! Reader is used for reading items from datasource (e.g. stdin or command output)
 ///!
 ///! After reading in a line, reader will save an item into the pool(items)
+use crate::diagnostics;
 use crate::item::ItemWrapper;
 use crate::item_collector::{read_and_collect_from_command, CollectorInput, CollectorOption};
 use crate::options::SkimOptions;
@@ -10,7 +11,6 @@ use crossbeam::channel::{bounded, select, Sender};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
-use std::thread;
 
 const CHANNEL_SIZE: usize = 1024;
 
@@ -120,7 +120,7 @@ fn collect_item(
 
     let started = Arc::new(AtomicBool::new(false));
     let started_clone = started.clone();
-    thread::spawn(move || {
+    diagnostics::spawn(move || {
         debug!("reader: collect_item start");
         components_to_stop.fetch_add(1, Ordering::SeqCst);
         started_clone.store(true, Ordering::SeqCst); // notify parent that it is started
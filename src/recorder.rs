@@ -0,0 +1,248 @@
+//! Record the events of the input to a file and replay them later (`--record-events` and
+//! `--replay-events`), to reproduce the bugs of the UI.
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+use tuikit::prelude::{Key, MouseButton};
+
+use crate::event::{action_arg, action_name, parse_event, Event};
+
+/// Write the events translated from the input (by the key bindings), a line each: the action (as
+/// in `--bind`) and its argument if any, e.g. `down\t"2"`, or the key that is not bound, e.g.
+/// `key\tCtrl('x')`. They are replayed as is, whatever the key bindings of the replay.
+pub struct EventRecorder {
+    file: File,
+}
+
+impl EventRecorder {
+    pub fn create(path: &str) -> std::io::Result<Self> {
+        Ok(Self {
+            file: File::create(path)?,
+        })
+    }
+
+    pub fn record(&mut self, event: &Event) {
+        let line = match format_event(event) {
+            Some(line) => line,
+            None => return,
+        };
+
+        // written as is, so that the events till a crash are kept
+        if let Err(err) = writeln!(self.file, "{}", line) {
+            warn!("recorder: failed to record the event {:?}: {}", event, err);
+        }
+    }
+}
+
+/// the line of the event, `None` for the events not recorded (e.g. an invalid input)
+fn format_event(event: &Event) -> Option<String> {
+    match event {
+        Event::EvInputKey(key) => Some(format!("key\t{:?}", key)),
+        Event::EvActAddChar(ch) => Some(format!("add-char\t{:?}", ch)),
+        Event::EvActRedraw => Some("redraw".to_string()),
+        event => {
+            let name = action_name(event)?;
+            Some(match action_arg(event) {
+                Some(arg) => format!("{}\t{:?}", name, arg),
+                None => name.to_string(),
+            })
+        }
+    }
+}
+
+/// read the events recorded by `EventRecorder`, in the order recorded
+pub fn read_recorded_events(path: &str) -> Result<Vec<Event>, Box<dyn Error>> {
+    let file = File::open(path).map_err(|err| format!("failed to open {}: {}", path, err))?;
+    let mut events = Vec::new();
+    for (line_no, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+
+        let event =
+            parse_recorded_line(&line).ok_or_else(|| format!("{}:{}: invalid event: {}", path, line_no + 1, line))?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+fn parse_recorded_line(line: &str) -> Option<Event> {
+    let mut fields = line.splitn(2, '\t');
+    let name = fields.next()?;
+    match (name, fields.next()) {
+        ("key", Some(key)) => parse_key(key).map(Event::EvInputKey),
+        ("add-char", Some(ch)) => parse_char(ch).map(Event::EvActAddChar),
+        ("redraw", None) => Some(Event::EvActRedraw),
+        (name, None) => parse_event(name, None),
+        (name, Some(arg)) => parse_event(name, Some(parse_quoted(arg, '"')?)),
+    }
+}
+
+#[rustfmt::skip]
+fn parse_key(text: &str) -> Option<Key> {
+    use Key::*;
+
+    let (name, args) = match text.find('(') {
+        Some(idx) => (&text[..idx], Some(text[idx + 1..].strip_suffix(")")?)),
+        None => (text, None),
+    };
+
+    let args = match args {
+        None => {
+            return match name {
+                "Null"          => Some(Null),
+                "ESC"           => Some(ESC),
+                "Tab"           => Some(Tab),
+                "Enter"         => Some(Enter),
+                "BackTab"       => Some(BackTab),
+                "Backspace"     => Some(Backspace),
+                "AltBackTab"    => Some(AltBackTab),
+                "Up"            => Some(Up),
+                "Down"          => Some(Down),
+                "Left"          => Some(Left),
+                "Right"         => Some(Right),
+                "Home"          => Some(Home),
+                "End"           => Some(End),
+                "Insert"        => Some(Insert),
+                "Delete"        => Some(Delete),
+                "PageUp"        => Some(PageUp),
+                "PageDown"      => Some(PageDown),
+                "CtrlUp"        => Some(CtrlUp),
+                "CtrlDown"      => Some(CtrlDown),
+                "CtrlLeft"      => Some(CtrlLeft),
+                "CtrlRight"     => Some(CtrlRight),
+                "ShiftUp"       => Some(ShiftUp),
+                "ShiftDown"     => Some(ShiftDown),
+                "ShiftLeft"     => Some(ShiftLeft),
+                "ShiftRight"    => Some(ShiftRight),
+                "AltUp"         => Some(AltUp),
+                "AltDown"       => Some(AltDown),
+                "AltLeft"       => Some(AltLeft),
+                "AltRight"      => Some(AltRight),
+                "AltHome"       => Some(AltHome),
+                "AltEnd"        => Some(AltEnd),
+                "AltPageUp"     => Some(AltPageUp),
+                "AltPageDown"   => Some(AltPageDown),
+                "AltShiftUp"    => Some(AltShiftUp),
+                "AltShiftDown"  => Some(AltShiftDown),
+                "AltShiftLeft"  => Some(AltShiftLeft),
+                "AltShiftRight" => Some(AltShiftRight),
+                "AltEnter"      => Some(AltEnter),
+                "AltBackspace"  => Some(AltBackspace),
+                "AltTab"        => Some(AltTab),
+                _               => None,
+            };
+        }
+        Some(args) => args,
+    };
+
+    let position = |args: &str| {
+        let mut fields = args.splitn(2, ", ");
+        let row = fields.next()?.parse::<u16>().ok()?;
+        let col = fields.next()?.parse::<u16>().ok()?;
+        Some((row, col))
+    };
+
+    match name {
+        "Ctrl"         => parse_char(args).map(Ctrl),
+        "CtrlAlt"      => parse_char(args).map(CtrlAlt),
+        "Alt"          => parse_char(args).map(Alt),
+        "Char"         => parse_char(args).map(Char),
+        "F"            => args.parse().ok().map(F),
+        "CursorPos"    => position(args).map(|(row, col)| CursorPos(row, col)),
+        "MouseRelease" => position(args).map(|(row, col)| MouseRelease(row, col)),
+        "MouseHold"    => position(args).map(|(row, col)| MouseHold(row, col)),
+        "MousePress"   => {
+            let mut fields = args.splitn(2, ", ");
+            let button = match fields.next()? {
+                "Left"      => MouseButton::Left,
+                "Right"     => MouseButton::Right,
+                "Middle"    => MouseButton::Middle,
+                "WheelUp"   => MouseButton::WheelUp,
+                "WheelDown" => MouseButton::WheelDown,
+                _           => return None,
+            };
+            position(fields.next()?).map(|(row, col)| MousePress(button, row, col))
+        }
+        _ => None,
+    }
+}
+
+/// parse a char literal formatted by `Debug`, e.g. `'a'`, `'\''` or `'\u{301}'`
+fn parse_char(text: &str) -> Option<char> {
+    let text = parse_quoted(text, '\'')?;
+    let mut chars = text.chars();
+    let ch = chars.next()?;
+    if chars.next().is_some() {
+        None
+    } else {
+        Some(ch)
+    }
+}
+
+/// parse a char or a string literal formatted by `Debug`, e.g. `"echo \"{}\""`
+fn parse_quoted(text: &str, quote: char) -> Option<String> {
+    let text = text.strip_prefix(quote)?.strip_suffix(quote)?;
+    let mut ret = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        let ch = match ch {
+            '\\' => match chars.next()? {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                '0' => '\0',
+                'u' => {
+                    let rest = chars.as_str().strip_prefix('{')?;
+                    let end = rest.find('}')?;
+                    chars = rest[end + 1..].chars();
+                    std::char::from_u32(u32::from_str_radix(&rest[..end], 16).ok()?)?
+                }
+                ch => ch, // \\, \' and \"
+            },
+            ch => ch,
+        };
+        ret.push(ch);
+    }
+    Some(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_recorded_line() {
+        let events = vec![
+            Event::EvActAddChar('a'),
+            Event::EvActAddChar('\''),
+            Event::EvActAddChar('\\'),
+            Event::EvActAddChar('\u{301}'),
+            Event::EvInputKey(Key::Ctrl('x')),
+            Event::EvInputKey(Key::Alt(',')),
+            Event::EvInputKey(Key::F(12)),
+            Event::EvInputKey(Key::ShiftUp),
+            Event::EvInputKey(Key::MousePress(MouseButton::WheelDown, 3, 14)),
+            Event::EvInputKey(Key::MouseRelease(3, 14)),
+            Event::EvActRedraw,
+            Event::EvActAccept(None),
+            Event::EvActAccept(Some("ctrl-y".to_string())),
+            Event::EvActDown(2),
+            Event::EvActToggleAll,
+            Event::EvActExecute("echo \"{}\"\tdone\n".to_string()),
+        ];
+
+        for event in events {
+            let line = format_event(&event).unwrap();
+            assert_eq!(parse_recorded_line(&line), Some(event));
+        }
+
+        assert_eq!(format_event(&Event::EvInputInvalid), None);
+        assert_eq!(parse_recorded_line("add-char\t'ab'"), None);
+        assert_eq!(parse_recorded_line("key\tUnknown"), None);
+        assert_eq!(parse_recorded_line("down\t2"), None);
+        assert_eq!(parse_recorded_line("unknown"), None);
+    }
+}
@@ -1,4 +1,5 @@
 ///! Handle the selections of items
+use crate::diagnostics;
 use crate::event::{Event, EventHandler, UpdateScreen};
 use crate::item::{parse_criteria, ItemIndex, RankCriteria};
 use crate::item::{ItemWrapper, MatchedItem, MatchedRange};
@@ -6,18 +7,24 @@ use crate::orderedvec::CompareFunction;
 use crate::orderedvec::OrderedVec;
 use crate::spinlock::SpinLock;
 use crate::theme::{ColorTheme, DEFAULT_THEME};
-use crate::util::{print_item, reshape_string, LinePrinter};
+use crate::util::{accumulate_text_width, cluster_char_widths, print_item, reshape_string, LinePrinter};
 use crate::{SkimItem, SkimOptions};
+use std::borrow::Cow;
 use std::cmp::max;
 use std::cmp::min;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
+use tuikit::canvas::BoundedCanvas;
 use tuikit::prelude::{Event as TermEvent, *};
+use unicode_width::UnicodeWidthChar;
 
 const DOUBLE_CLICK_DURATION: u128 = 300;
 
+// the background of the scrollbar heatmap, from the weakest matches to the strongest
+const HEAT_COLORS: [u8; 10] = [236, 238, 240, 242, 58, 94, 130, 166, 202, 196];
+
 lazy_static! {
     static ref DEFAULT_CRITERION: Vec<RankCriteria> = vec![
         RankCriteria::Score,
@@ -29,8 +36,11 @@ lazy_static! {
 
 pub struct Selection {
     criterion: Vec<RankCriteria>,
-    items: OrderedVec<MatchedItem>, // all items
-    selected: HashMap<ItemIndex, Arc<ItemWrapper>>,
+    items: OrderedVec<MatchedItem>,                          // all items
+    selected: HashMap<ItemIndex, (usize, Arc<ItemWrapper>)>, // id -> (the order selected, item)
+    select_seq: usize,                                       // the order of the next selection
+    num_selected_shown: usize,                               // the selected items among `items`
+    marks: HashMap<usize, ItemIndex>,                        // mark No. -> the id of the marked item
 
     //
     // |>------ items[items.len()-1]
@@ -47,14 +57,38 @@ pub struct Selection {
     line_cursor: usize, // line No.
     hscroll_offset: usize,
     height: AtomicUsize,
+    width: AtomicUsize,
     tabstop: usize,
 
     // Options
     multi_selection: bool,
+    select_order: bool,              // output the selected items in the order they are selected
+    retain_filtered_selection: bool, // keep the selected items that are no longer matched
     reverse: bool,
+    first_far: bool, // the first item is drawn farthest from the prompt, ignored by the grid layout
+    max_rendered_rows: Option<usize>,
+    render_offset: AtomicUsize, // rows above the drawn ones, due to `max_rendered_rows`
+    inline_preview_rows: usize, // the rows left under the current item for the preview, 0 if none
+    inline_preview_area: SpinLock<Option<(usize, usize)>>, // (first row, rows) left in the last draw
     no_hscroll: bool,
+    show_trailing_whitespace: bool,
+    single_thread: bool,
+    line_numbers: bool,
+    match_display: bool,     // the items are matched by their (stripped) display
+    scroll_lines: i32,       // the rows to move per tick of the mouse wheel
+    pin_top: bool,           // keep the top item on the first row when the list is scrolled
+    follow: bool,            // keep the cursor on its item when the new ones come, unless on the top
+    scrollbar_heatmap: bool, // draw a scrollbar colored by the scores of the items it covers
+    heatmap: SpinLock<Option<(usize, Vec<usize>)>>, // (rows, heat level of each row) of the last draw
+    failed_items: SpinLock<HashSet<ItemIndex>>, // the items that panicked when drawn, not drawn again
+    max_line_number: usize,  // the largest line number of items, decides the gutter width
+    max_icon_width: usize,   // the width of the widest icon of items, 0 if none has an icon
     theme: Arc<ColorTheme>,
 
+    // grid layout: the rows are packed with items, `line_cursor` is the offset of the item on screen
+    grid: bool,
+    grid_cell_width: usize, // width of the widest item
+
     // used to detect double click(two consecutive press) event.
     last_click_row: AtomicUsize,
     last_click_time: SpinLock<Instant>,
@@ -66,16 +100,42 @@ impl Selection {
             criterion: DEFAULT_CRITERION.clone(),
             items: OrderedVec::new(build_compare_function(DEFAULT_CRITERION.clone())),
             selected: HashMap::new(),
+            select_seq: 0,
+            num_selected_shown: 0,
+            marks: HashMap::new(),
             item_cursor: 0,
             line_cursor: 0,
             hscroll_offset: 0,
             height: AtomicUsize::new(0),
+            width: AtomicUsize::new(0),
             tabstop: 8,
             multi_selection: false,
+            select_order: false,
+            retain_filtered_selection: true,
             reverse: false,
+            first_far: false,
+            max_rendered_rows: None,
+            render_offset: AtomicUsize::new(0),
+            inline_preview_rows: 0,
+            inline_preview_area: SpinLock::new(None),
             no_hscroll: false,
+            show_trailing_whitespace: false,
+            single_thread: false,
+            line_numbers: false,
+            match_display: false,
+            scroll_lines: 1,
+            pin_top: false,
+            follow: false,
+            scrollbar_heatmap: false,
+            heatmap: SpinLock::new(None),
+            failed_items: SpinLock::new(HashSet::new()),
+            max_line_number: 0,
+            max_icon_width: 0,
             theme: Arc::new(*DEFAULT_THEME),
 
+            grid: false,
+            grid_cell_width: 0,
+
             last_click_row: AtomicUsize::new(0),
             last_click_time: SpinLock::new(Instant::now()),
         }
@@ -88,42 +148,78 @@ impl Selection {
     }
 
     fn parse_options(&mut self, options: &SkimOptions) {
-        if options.multi {
+        if options.multi || options.select_all_on_start {
             self.multi_selection = true;
         }
 
+        if options.match_display {
+            self.match_display = true;
+        }
+
+        if options.output_select_order {
+            self.select_order = true;
+        }
+
+        if !options.retain_filtered_selection {
+            self.retain_filtered_selection = false;
+        }
+
         if options.layout.starts_with("reverse") {
             self.reverse = true;
         }
 
+        if options.display_direction == Some("toward-prompt") {
+            self.first_far = true;
+        }
+
         if options.no_hscroll {
             self.no_hscroll = true;
         }
 
+        if options.grid {
+            self.grid = true;
+        }
+
+        if options.show_trailing_whitespace {
+            self.show_trailing_whitespace = true;
+        }
+
+        if let Some(max_rows) = options.max_rendered_rows {
+            self.max_rendered_rows = Some(max(1, max_rows));
+        }
+
+        if let Some(scroll_lines) = options.mouse_scroll_lines.and_then(|lines| lines.split(',').next()) {
+            self.scroll_lines = max(1, scroll_lines.trim().parse::<i32>().unwrap_or(1));
+        }
+
         if let Some(tabstop_str) = options.tabstop {
             let tabstop = tabstop_str.parse::<usize>().unwrap_or(8);
             self.tabstop = max(1, tabstop);
         }
 
-        if let Some(ref tie_breaker) = options.tiebreak {
-            let criterion = tie_breaker.split(',').filter_map(parse_criteria).collect();
-            self.criterion = criterion;
+        self.criterion = rank_criteria(options);
+
+        if options.single_thread {
+            self.single_thread = true;
         }
 
-        if options.tac {
-            let criterion = self
-                .criterion
-                .iter()
-                .map(|&criteria| match criteria {
-                    RankCriteria::Index => RankCriteria::NegIndex,
-                    RankCriteria::NegIndex => RankCriteria::Index,
-                    criteria => criteria,
-                })
-                .collect();
-            self.criterion = criterion;
+        if options.line_numbers {
+            self.line_numbers = true;
+        }
+
+        if options.pin_top {
+            self.pin_top = true;
         }
 
-        self.items = OrderedVec::new(build_compare_function(self.criterion.clone()));
+        if options.follow {
+            self.follow = true;
+        }
+
+        if options.scrollbar_heatmap {
+            self.scrollbar_heatmap = true;
+        }
+
+        self.items = OrderedVec::new(build_compare_function(self.criterion.clone())).parallel(!self.single_thread);
     }
 
     pub fn theme(mut self, theme: Arc<ColorTheme>) -> Self {
@@ -131,8 +227,79 @@ impl Selection {
         self
     }
 
+    /// leave `rows` under the current item for the preview (`preview_inline`), 0 to leave none. The
+    /// grid layout leaves none.
+    pub fn set_inline_preview_rows(&mut self, rows: usize) {
+        self.inline_preview_rows = rows;
+    }
+
+    /// the rows left for the inline preview in the last draw, (the first row, the number of rows)
+    pub fn inline_preview_area(&self) -> Option<(usize, usize)> {
+        let render_offset = self.render_offset.load(Ordering::Relaxed);
+        self.inline_preview_area
+            .lock()
+            .map(|(top, rows)| (top + render_offset, rows))
+    }
+
     pub fn append_sorted_items(&mut self, items: Vec<MatchedItem>) {
+        // with `follow`, the cursor on the top shows the best match, otherwise it stays on its item
+        let followed = match self.get_current_item() {
+            Some(item) if self.follow && self.get_current_item_idx() > 0 => {
+                Some((self.get_current_item_idx(), item.get_id()))
+            }
+            _ => None,
+        };
+
+        if self.grid {
+            let tabstop = self.tabstop;
+            let max_width = items
+                .iter()
+                .filter_map(|matched| accumulate_text_width(&matched.item.text(), tabstop).last().cloned())
+                .max()
+                .unwrap_or(0);
+            self.grid_cell_width = max(self.grid_cell_width, max_width);
+        }
+
+        if self.line_numbers {
+            let max_line_number = items.iter().map(|matched| matched.item.get_index() + 1).max();
+            self.max_line_number = max(self.max_line_number, max_line_number.unwrap_or(0));
+        }
+
+        let max_icon_width = items
+            .iter()
+            .filter_map(|matched| matched.item.icon())
+            .map(|icon| icon.width().unwrap_or(2))
+            .max();
+        self.max_icon_width = max(self.max_icon_width, max_icon_width.unwrap_or(0));
+
+        if !self.selected.is_empty() {
+            let selected = &self.selected;
+            self.num_selected_shown += items
+                .iter()
+                .filter(|matched| selected.contains_key(&matched.item.get_id()))
+                .count();
+        }
+
         self.items.append_ordered(items);
+        *self.heatmap.lock() = None;
+
+        if let Some((current, id)) = followed {
+            // the new items ranked before it push it down
+            let moved_to = self.items.iter().position(|matched| matched.item.get_id() == id);
+            let diff = moved_to.unwrap_or(current) - current;
+            if self.grid {
+                self.act_move_grid_cursor(diff as i32);
+            } else {
+                self.item_cursor += diff;
+                self.keep_cursor_off_pinned_row();
+            }
+            return;
+        }
+
+        if self.grid {
+            self.act_move_grid_cursor(0);
+            return;
+        }
 
         let height = self.height.load(Ordering::Relaxed);
         if self.items.len() <= self.line_cursor {
@@ -142,45 +309,359 @@ impl Selection {
 
         if self.items.len() <= self.line_cursor + self.item_cursor {
             // if not enough items, scroll the cursor a page down
-            self.item_cursor = max(self.items.len(), height) - height;
+            self.item_cursor = match self.items.len() {
+                0 => 0,
+                len => self.scroll_start(len - 1, height),
+            };
         }
+
+        // the multi-row items take more rows
+        let num_fit = self.num_fit(self.item_cursor, height);
+        self.line_cursor = min(self.line_cursor, max(num_fit, 1) - 1);
+        self.keep_cursor_off_pinned_row();
     }
 
     pub fn clear(&mut self) {
         self.items.clear();
+        self.num_selected_shown = 0;
+        *self.heatmap.lock() = None;
+        self.grid_cell_width = 0;
+        self.max_line_number = 0;
+        self.max_icon_width = 0;
     }
 
-    pub fn act_move_line_cursor(&mut self, diff: i32) {
-        let diff = if self.reverse { -diff } else { diff };
-
-        let mut line_cursor = self.line_cursor as i32;
-        let mut item_cursor = self.item_cursor as i32;
-        let item_len = self.items.len() as i32;
-
-        let height = self.height.load(Ordering::Relaxed) as i32;
-
-        line_cursor += diff;
-        if line_cursor >= height {
-            item_cursor += line_cursor - height + 1;
-            item_cursor = max(0, min(item_cursor, item_len - height));
-            line_cursor = min(height - 1, item_len - item_cursor);
-        } else if line_cursor < 0 {
-            item_cursor += line_cursor;
-            item_cursor = max(item_cursor, 0);
-            line_cursor = 0;
+    /// width of the line number gutter (including the gap after it), 0 if not shown
+    fn gutter_width(&self) -> usize {
+        if !self.line_numbers || self.grid {
+            return 0;
+        }
+        self.max_line_number.to_string().len() + 1
+    }
+
+    /// width of the icon gutter (including the gap after it), 0 if no item has an icon
+    fn icon_width(&self) -> usize {
+        if self.max_icon_width == 0 || self.grid {
+            return 0;
+        }
+        self.max_icon_width + 1
+    }
+
+    /// number of items in a screen row, always 1 unless in grid layout
+    fn columns(&self) -> usize {
+        if !self.grid {
+            return 1;
+        }
+
+        // the cursor label, the selected marker and a gap are printed along with the item
+        let cell_width = self.grid_cell_width + 3;
+        max(1, self.width.load(Ordering::Relaxed) / cell_width)
+    }
+
+    /// whether the items of larger indices are drawn below, i.e. moving up decreases the index
+    fn is_top_down(&self) -> bool {
+        self.reverse != (self.first_far && !self.grid)
+    }
+
+    /// the number of rows between the item at `line_cursor` and the prompt, given the rows of the
+    /// items drawn (see `rows_of_drawn`)
+    fn rows_to_prompt(&self, rows: &[usize], line_cursor: usize) -> usize {
+        if self.first_far && !self.grid {
+            rows[line_cursor + 1..].iter().sum()
+        } else {
+            rows[..line_cursor].iter().sum()
+        }
+    }
+
+    /// the rows taken by the item at `item_idx`, see `SkimItem::rows`, plus the separator line
+    fn rows_of_item(&self, item_idx: usize) -> usize {
+        if self.grid {
+            return 1;
+        }
+        let rows = self
+            .items
+            .get(item_idx)
+            .map(|matched| max(1, matched.item.rows()))
+            .unwrap_or(1);
+        if self.is_last_pinned(item_idx) {
+            rows + 1
+        } else {
+            rows
+        }
+    }
+
+    /// whether the item at `item_idx` is the last pinned one followed by the others, the separator
+    /// line between them is drawn next to it
+    fn is_last_pinned(&self, item_idx: usize) -> bool {
+        if self.grid || item_idx + 1 >= self.items.len() {
+            return false;
+        }
+        let pinned = |idx| {
+            self.items
+                .get(idx)
+                .map(|matched| matched.item.pinned())
+                .unwrap_or(false)
+        };
+        pinned(item_idx) && !pinned(item_idx + 1)
+    }
+
+    /// the item drawn at `line_cursor`, see `item_idx_at`
+    fn item_idx_of_line(&self, line_cursor: usize) -> usize {
+        self.item_idx_at(self.item_cursor, line_cursor)
+    }
+
+    /// the item drawn at `line_cursor` once scrolled to `item_cursor`, the first lines show the
+    /// pinned items whatever the scroll (see `num_fixed_lines`)
+    fn item_idx_at(&self, item_cursor: usize, line_cursor: usize) -> usize {
+        if item_cursor > 0 && line_cursor < self.num_fixed_lines() {
+            line_cursor
+        } else {
+            item_cursor + line_cursor
+        }
+    }
+
+    /// the number of the first lines kept once the list is scrolled: the pinned items (which are
+    /// ranked first) that leave a row for the others at least, the top item with `pin_top`
+    fn num_fixed_lines(&self) -> usize {
+        let height = self.height.load(Ordering::Relaxed);
+        if self.grid || height < 2 {
+            return 0;
+        }
+
+        let mut lines = 0;
+        let mut rows = 0;
+        while let Some(matched) = self.items.get(lines) {
+            rows += self.rows_of_item(lines);
+            if !matched.item.pinned() || rows >= height {
+                break;
+            }
+            lines += 1;
+        }
+
+        if self.pin_top {
+            max(lines, 1)
+        } else {
+            lines
+        }
+    }
+
+    /// the first lines that show the fixed items instead of the ones scrolled to them
+    fn num_covered_lines(&self) -> usize {
+        if self.item_cursor > 0 {
+            self.num_fixed_lines()
         } else {
-            line_cursor = min(line_cursor, item_len - 1 - item_cursor);
+            0
+        }
+    }
+
+    /// the rows taken by each item drawn on the screen of `screen_height` rows, from the one at
+    /// `item_cursor`, the last one is cut if there is no room for all of its rows
+    fn rows_of_drawn(&self, screen_height: usize) -> Vec<usize> {
+        let mut rows = Vec::new();
+        let mut rest = screen_height;
+        while rest > 0 && self.item_cursor + rows.len() < self.items.len() {
+            let item_rows = min(rest, self.rows_of_item(self.item_idx_of_line(rows.len())));
+            rest -= item_rows;
+            rows.push(item_rows);
+        }
+        rows
+    }
+
+    /// the number of lines that fit in `height` rows once scrolled to the item at `from`, an item
+    /// taller than the screen still counts
+    fn num_fit(&self, from: usize, height: usize) -> usize {
+        let mut num = 0;
+        let mut rows = 0;
+        for line_cursor in 0..self.items.len().saturating_sub(from) {
+            rows += self.rows_of_item(self.item_idx_at(from, line_cursor));
+            if num > 0 && rows > height {
+                break;
+            }
+            num += 1;
+        }
+        num
+    }
+
+    /// the first item to draw for the item at `item_idx` to be the last one on the screen
+    fn scroll_start(&self, item_idx: usize, height: usize) -> usize {
+        // shown once scrolled to `start`, not on the lines of the fixed items
+        let fits = |start: usize| {
+            let covered = if start > 0 { self.num_fixed_lines() } else { 0 };
+            item_idx >= start + covered && item_idx < start + self.num_fit(start, height)
+        };
+
+        let mut start = item_idx;
+        while start > 0 && !fits(start) {
+            start -= 1;
+        }
+        while start > 0 && fits(start - 1) {
+            start -= 1;
+        }
+        start
+    }
+
+    pub fn act_move_line_cursor(&mut self, diff: i32) {
+        let diff = if self.is_top_down() { -diff } else { diff };
+
+        if self.grid {
+            self.act_move_grid_cursor(diff);
+            return;
+        }
+
+        if self.items.is_empty() {
+            self.line_cursor = 0;
+            return;
         }
 
-        line_cursor = max(0, line_cursor);
+        let height = self.height.load(Ordering::Relaxed);
+        let current = (self.item_cursor + self.line_cursor) as i32 + diff;
+        let current = max(0, min(current, self.items.len() as i32 - 1)) as usize;
+
+        // scroll by items, a multi-row item is skipped as a whole
+        if current < self.item_cursor {
+            self.item_cursor = current;
+        } else if current >= self.item_cursor + self.num_fit(self.item_cursor, height) {
+            self.item_cursor = self.scroll_start(current, height);
+        }
+
+        self.line_cursor = current - self.item_cursor;
+        self.keep_cursor_off_pinned_row();
+    }
+
+    /// once scrolled, the first lines show the fixed items (see `num_fixed_lines`), thus the items
+    /// scrolled to them are hidden and the cursor should not stay on them: scroll back to show it.
+    fn keep_cursor_off_pinned_row(&mut self) {
+        let covered = self.num_covered_lines();
+        if self.line_cursor < covered {
+            let shift = min(covered - self.line_cursor, self.item_cursor);
+            self.item_cursor -= shift;
+            self.line_cursor += shift;
+        }
+    }
+
+    /// move the cursor by `diff` items in the grid layout, scroll by rows if needed
+    pub fn act_move_grid_cursor(&mut self, diff: i32) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        let columns = self.columns();
+        let height = max(1, self.height.load(Ordering::Relaxed));
+
+        let current = (self.item_cursor + self.line_cursor) as i32 + diff;
+        let current = max(0, min(current, self.items.len() as i32 - 1)) as usize;
+
+        let row = current / columns;
+        let mut top_row = self.item_cursor / columns;
+        if row < top_row {
+            top_row = row;
+        } else if row >= top_row + height {
+            top_row = row + 1 - height;
+        }
+
+        self.item_cursor = top_row * columns;
+        self.line_cursor = current - self.item_cursor;
+    }
+
+    /// re-layout after the screen size changed, keep the cursor on the same item
+    pub fn act_relayout(&mut self) {
+        if self.grid {
+            self.act_move_grid_cursor(0);
+            return;
+        }
+
+        let height = self.height.load(Ordering::Relaxed);
+        if height == 0 || self.items.is_empty() {
+            return;
+        }
 
-        self.item_cursor = item_cursor as usize;
-        self.line_cursor = line_cursor as usize;
+        let current = min(self.item_cursor + self.line_cursor, self.items.len() - 1);
+
+        // scroll back if the screen could show more items than it does now
+        self.item_cursor = min(self.item_cursor, self.scroll_start(self.items.len() - 1, height));
+
+        // scroll forward if the cursor is outside the (shrunk) screen
+        if current >= self.item_cursor + self.num_fit(self.item_cursor, height) {
+            self.item_cursor = self.scroll_start(current, height);
+        }
+
+        self.line_cursor = current - self.item_cursor;
+        self.keep_cursor_off_pinned_row();
+    }
+
+    /// remember the current item as mark `mark`
+    pub fn act_mark(&mut self, mark: usize) {
+        if let Some(item) = self.get_current_item() {
+            self.marks.insert(mark, item.get_id());
+        }
+    }
+
+    /// move the cursor to the item of mark `mark`.
+    /// return false if the mark is not set or the marked item is not matched now.
+    pub fn act_goto_mark(&mut self, mark: usize) -> bool {
+        let id = match self.marks.get(&mark) {
+            Some(&id) => id,
+            None => return false,
+        };
+
+        self.act_move_cursor_to_first(|item| item.get_id() == id)
+    }
+
+    /// move the cursor to the first item that satisfies `predicate`.
+    /// return false(and the cursor is not moved) if there is none.
+    pub fn act_move_cursor_to_first(&mut self, predicate: impl Fn(&ItemWrapper) -> bool) -> bool {
+        let target = self.items.iter().position(|matched| predicate(&matched.item));
+        match target {
+            Some(target) => {
+                let diff = target as i32 - self.get_current_item_idx() as i32;
+                self.act_move_line_cursor(if self.is_top_down() { -diff } else { diff });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// move the cursor to the next (or the previous if not `forward`) item after the current one
+    /// that satisfies `predicate`, the list is not wrapped around.
+    /// return false(and the cursor is not moved) if there is none.
+    pub fn act_jump(&mut self, forward: bool, predicate: impl Fn(&ItemWrapper) -> bool) -> bool {
+        let current = self.get_current_item_idx();
+        let target = if forward {
+            (current + 1..self.items.len()).find(|&idx| predicate(&self.items.get(idx).unwrap().item))
+        } else {
+            (0..min(current, self.items.len()))
+                .rev()
+                .find(|&idx| predicate(&self.items.get(idx).unwrap().item))
+        };
+        match target {
+            Some(target) => {
+                let diff = target as i32 - current as i32;
+                self.act_move_line_cursor(if self.is_top_down() { -diff } else { diff });
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn act_select_screen_row(&mut self, rows_to_top: usize) {
+        let render_offset = self.render_offset.load(Ordering::Relaxed);
+        if rows_to_top < render_offset {
+            // clicked above the drawn rows
+            return;
+        }
+        let mut rows_to_top = rows_to_top - render_offset;
+
+        if let Some((top, rows)) = *self.inline_preview_area.lock() {
+            if rows_to_top >= top + rows {
+                // the rows below the inline preview
+                rows_to_top -= rows;
+            } else if rows_to_top >= top {
+                // clicked the inline preview
+                return;
+            }
+        }
+
         let height = self.height.load(Ordering::Relaxed);
-        self.line_cursor = if self.reverse {
+        let row = if self.reverse {
             // rows from top
             rows_to_top
         } else {
@@ -188,6 +669,33 @@ impl Selection {
             let fallback = rows_to_top + 1;
             max(height, fallback) - rows_to_top - 1
         };
+
+        if self.grid {
+            // select the first item of the row
+            self.line_cursor = row * self.columns();
+            self.act_move_grid_cursor(0);
+            return;
+        }
+
+        // the item whose rows contain the row
+        let rows = self.rows_of_drawn(height);
+        let line_cursor = (0..rows.len()).find(|&line_cursor| {
+            let rows_to_prompt = self.rows_to_prompt(&rows, line_cursor);
+            row >= rows_to_prompt && row < rows_to_prompt + rows[line_cursor]
+        });
+        let line_cursor = match line_cursor {
+            Some(line_cursor) => line_cursor,
+            // beyond the items
+            None => return,
+        };
+
+        if line_cursor < self.num_covered_lines() {
+            // a fixed item, it is on the same line on the top
+            self.item_cursor = 0;
+            self.line_cursor = line_cursor;
+        } else {
+            self.line_cursor = line_cursor;
+        }
     }
 
     #[allow(clippy::map_entry)]
@@ -203,12 +711,18 @@ impl Selection {
             .unwrap_or_else(|| panic!("model:act_toggle: failed to get item {}", cursor));
         let index = current_item.item.get_id();
         if !self.selected.contains_key(&index) {
-            self.selected.insert(index, current_item.item.clone());
+            self.selected
+                .insert(index, (self.select_seq, current_item.item.clone()));
+            self.select_seq += 1;
+            self.num_selected_shown += 1;
         } else {
             self.selected.remove(&index);
+            self.num_selected_shown -= 1;
         }
     }
 
+    /// invert the selection of the matched items, the selected items that are not matched now are
+    /// left untouched.
     #[allow(clippy::map_entry)]
     pub fn act_toggle_all(&mut self) {
         if !self.multi_selection || self.items.is_empty() {
@@ -218,9 +732,13 @@ impl Selection {
         for current_item in self.items.iter() {
             let index = current_item.item.get_id();
             if !self.selected.contains_key(&index) {
-                self.selected.insert(index, current_item.item.clone());
+                self.selected
+                    .insert(index, (self.select_seq, current_item.item.clone()));
+                self.select_seq += 1;
+                self.num_selected_shown += 1;
             } else {
                 self.selected.remove(&index);
+                self.num_selected_shown -= 1;
             }
         }
     }
@@ -230,7 +748,13 @@ impl Selection {
             return;
         }
 
-        self.selected.insert(item.get_id(), item);
+        let id = item.get_id();
+        if !self.selected.contains_key(&id) && self.items.iter().any(|matched| matched.item.get_id() == id) {
+            self.num_selected_shown += 1;
+        }
+        let seq = self.select_seq;
+        self.selected.entry(id).or_insert((seq, item));
+        self.select_seq += 1;
     }
 
     pub fn act_select_all(&mut self) {
@@ -240,12 +764,51 @@ impl Selection {
 
         for current_item in self.items.iter() {
             let item = current_item.item.clone();
-            self.selected.insert(item.get_id(), item);
+            self.selected.entry(item.get_id()).or_insert((self.select_seq, item));
+            self.select_seq += 1;
         }
+        self.num_selected_shown = self.items.len();
     }
 
     pub fn act_deselect_all(&mut self) {
         self.selected.clear();
+        self.num_selected_shown = 0;
+    }
+
+    /// deselect the selected items that are not matched (unless `retain_filtered_selection`), to be
+    /// called once all the items are matched
+    pub fn drop_filtered_selected(&mut self) {
+        if self.retain_filtered_selection || self.selected.is_empty() {
+            return;
+        }
+
+        let matched: HashSet<ItemIndex> = self.items.iter().map(|matched| matched.item.get_id()).collect();
+        self.selected.retain(|index, _| matched.contains(index));
+    }
+
+    /// remove the current item from the matched items and the selection. The cursor stays on the
+    /// same row, i.e. on the next item, or on the previous one if the last item is removed.
+    pub fn remove_current_item(&mut self) -> Option<Arc<ItemWrapper>> {
+        let current = self.get_current_item_idx();
+        if current >= self.items.len() {
+            return None;
+        }
+
+        let item = self.items.remove(current).item;
+        *self.heatmap.lock() = None;
+        let id = item.get_id();
+        if self.selected.remove(&id).is_some() {
+            self.num_selected_shown -= 1;
+        }
+        self.marks.retain(|_, marked| *marked != id);
+
+        if self.items.is_empty() {
+            self.item_cursor = 0;
+            self.line_cursor = 0;
+        } else {
+            self.act_relayout();
+        }
+        Some(item)
     }
 
     pub fn act_scroll(&mut self, offset: i32) {
@@ -258,7 +821,7 @@ impl Selection {
     pub fn get_selected_wrapped_items(&self) -> Vec<Arc<ItemWrapper>> {
         // select the current one
         let select_cursor = !self.multi_selection || self.selected.is_empty();
-        let mut selected: Vec<Arc<ItemWrapper>> = self.selected.values().cloned().collect();
+        let mut selected: Vec<(usize, Arc<ItemWrapper>)> = self.selected.values().cloned().collect();
 
         if select_cursor && !self.items.is_empty() {
             let cursor = self.item_cursor + self.line_cursor;
@@ -267,11 +830,15 @@ impl Selection {
                 .get(cursor)
                 .unwrap_or_else(|| panic!("model:act_output: failed to get item {}", cursor));
             let item = current_item.item.clone();
-            selected.push(item);
+            selected.push((self.select_seq, item));
         }
 
-        selected.sort_by_key(|item| item.get_id());
-        selected
+        if self.select_order {
+            selected.sort_by_key(|&(seq, _)| seq);
+        } else {
+            selected.sort_by_key(|(_, item)| item.get_id());
+        }
+        selected.into_iter().map(|(_, item)| item).collect()
     }
 
     pub fn get_selected_items(&self) -> Vec<Arc<dyn SkimItem>> {
@@ -281,6 +848,29 @@ impl Selection {
             .collect()
     }
 
+    /// the scores of the selected items (same order as `get_selected_items`), higher is better
+    pub fn get_selected_scores(&self) -> Vec<Option<i64>> {
+        let scores: HashMap<ItemIndex, i64> = self
+            .items
+            .iter()
+            .map(|matched| (matched.item.get_id(), -matched.rank.score))
+            .collect();
+        self.get_selected_wrapped_items()
+            .iter()
+            .map(|item| scores.get(&item.get_id()).cloned())
+            .collect()
+    }
+
+    /// all the matched items, in display order
+    pub fn get_matched_items(&self) -> Vec<Arc<dyn SkimItem>> {
+        self.items.iter().map(|matched| matched.item.get_inner()).collect()
+    }
+
+    /// the scores of all the matched items (same order as `get_matched_items`)
+    pub fn get_matched_scores(&self) -> Vec<Option<i64>> {
+        self.items.iter().map(|matched| Some(-matched.rank.score)).collect()
+    }
+
     pub fn get_num_of_selected_exclude_current(&self) -> usize {
         self.selected.len()
     }
@@ -289,14 +879,30 @@ impl Selection {
         self.item_cursor + self.line_cursor
     }
 
+    /// the number of rows drawn, 0 before the first draw
+    pub fn get_height(&self) -> usize {
+        self.height.load(Ordering::Relaxed)
+    }
+
     pub fn get_num_selected(&self) -> usize {
         self.selected.len()
     }
 
+    /// the number of the selected items that are not matched now
+    pub fn get_num_hidden_selected(&self) -> usize {
+        self.selected.len() - self.num_selected_shown
+    }
+
     pub fn is_multi_selection(&self) -> bool {
         self.multi_selection
     }
 
+    /// the matching score of the current item, higher is better
+    pub fn get_current_score(&self) -> Option<i64> {
+        let item_idx = self.get_current_item_idx();
+        self.items.get(item_idx).map(|item| -item.rank.score)
+    }
+
     pub fn get_current_item(&self) -> Option<Arc<ItemWrapper>> {
         let item_idx = self.get_current_item_idx();
         self.items.get(item_idx).map(|item| item.item.clone())
@@ -306,12 +912,13 @@ impl Selection {
 impl EventHandler for Selection {
     fn handle(&mut self, event: &Event) -> UpdateScreen {
         use crate::event::Event::*;
+        let columns = self.columns() as i32;
         match event {
             EvActUp(diff) => {
-                self.act_move_line_cursor(*diff);
+                self.act_move_line_cursor(*diff * columns);
             }
             EvActDown(diff) => {
-                self.act_move_line_cursor(-*diff);
+                self.act_move_line_cursor(-*diff * columns);
             }
             EvActToggle => {
                 self.act_toggle();
@@ -319,19 +926,33 @@ impl EventHandler for Selection {
             EvActToggleAll => {
                 self.act_toggle_all();
             }
+            EvActToggleIn => {
+                self.act_toggle();
+                self.act_move_line_cursor(if self.is_top_down() { 1 } else { -1 });
+            }
+            EvActToggleOut => {
+                self.act_toggle();
+                self.act_move_line_cursor(if self.is_top_down() { -1 } else { 1 });
+            }
             EvActSelectAll => {
                 self.act_select_all();
             }
             EvActDeselectAll => {
                 self.act_deselect_all();
             }
+            EvActLeft(diff) if self.grid => {
+                self.act_move_grid_cursor(-*diff);
+            }
+            EvActRight(diff) if self.grid => {
+                self.act_move_grid_cursor(*diff);
+            }
             EvActPageDown(diff) => {
                 let height = 1 - (self.height.load(Ordering::Relaxed) as i32);
-                self.act_move_line_cursor(height * *diff);
+                self.act_move_line_cursor(height * *diff * columns);
             }
             EvActPageUp(diff) => {
                 let height = (self.height.load(Ordering::Relaxed) as i32) - 1;
-                self.act_move_line_cursor(height * *diff);
+                self.act_move_line_cursor(height * *diff * columns);
             }
             EvActSelectRow(row) => {
                 self.act_select_screen_row(*row);
@@ -342,6 +963,12 @@ impl EventHandler for Selection {
             EvActScrollRight(diff) => {
                 self.act_scroll(*diff);
             }
+            EvActRedraw => {
+                self.act_relayout();
+            }
+            EvActMark(mark) => {
+                self.act_mark(*mark);
+            }
             _ => return UpdateScreen::DONT_REDRAW,
         }
         UpdateScreen::REDRAW
@@ -349,6 +976,33 @@ impl EventHandler for Selection {
 }
 
 impl Selection {
+    /// draw the item, a panic in the item's `display()` or `text()` is caught and logged so that a
+    /// bad item only loses its own row instead of the whole UI, it is not drawn again
+    fn draw_item_guarded(
+        &self,
+        canvas: &mut dyn Canvas,
+        row: usize,
+        matched_item: &MatchedItem,
+        is_current: bool,
+    ) -> Result<()> {
+        let index = matched_item.item.get_index();
+        let id = matched_item.item.get_id();
+        if self.failed_items.lock().contains(&id) {
+            return Err(format!("failed to draw item {}", index).into());
+        }
+
+        diagnostics::catch_unwind_quietly(|| self.draw_item(canvas, row, matched_item, is_current)).unwrap_or_else(
+            |_| {
+                warn!(
+                    "selection: failed to draw item {}, its display()/text() panicked",
+                    index
+                );
+                self.failed_items.lock().insert(id);
+                Err(format!("failed to draw item {}", index).into())
+            },
+        )
+    }
+
     fn draw_item(
         &self,
         canvas: &mut dyn Canvas,
@@ -358,14 +1012,22 @@ impl Selection {
     ) -> Result<()> {
         let (screen_width, screen_height) = canvas.size()?;
 
-        // update item heights
-        self.height.store(screen_height, Ordering::Relaxed);
+        // the rows of a multi-row item, cut by the bottom of the screen
+        let rows = if self.grid || matched_item.item.rows() <= 1 {
+            None
+        } else {
+            Some(min(matched_item.item.rows(), screen_height.saturating_sub(row)))
+        };
 
-        if screen_width < 3 {
+        let line_number_width = self.gutter_width();
+        let icon_width = self.icon_width();
+        let gutter_width = line_number_width + icon_width;
+        if screen_width < 3 + gutter_width {
             return Err("screen width is too small".into());
         }
 
         let index = matched_item.item.get_id();
+        let row_attr = matched_item.item.row_style();
 
         let default_attr = if is_current {
             self.theme.current()
@@ -379,6 +1041,17 @@ impl Selection {
             self.theme.matched()
         };
 
+        // the theme is drawn over the style of the row
+        let (default_attr, matched_attr) = match row_attr {
+            Some(row_attr) => {
+                for row in row..row + rows.unwrap_or(1) {
+                    let _ = canvas.print_with_attr(row, 0, &" ".repeat(screen_width), row_attr.extend(default_attr));
+                }
+                (row_attr.extend(default_attr), row_attr.extend(matched_attr))
+            }
+            None => (default_attr, matched_attr),
+        };
+
         // print selection cursor
         if self.selected.contains_key(&index) {
             let _ = canvas.print_with_attr(row, 1, ">", default_attr.extend(self.theme.selected()));
@@ -387,7 +1060,28 @@ impl Selection {
         }
 
         let item = &matched_item.item;
-        let text = item.text();
+
+        // print the original line number, right aligned
+        if line_number_width > 0 {
+            let line_number = format!("{:>width$} ", item.get_index() + 1, width = line_number_width - 1);
+            let _ = canvas.print_with_attr(row, 2, &line_number, default_attr.extend(self.theme.line_number()));
+        }
+
+        // print the icon, padded to the widest one
+        if icon_width > 0 {
+            let col = 2 + line_number_width;
+            let icon = item.icon().map(|icon| icon.to_string()).unwrap_or_default();
+            let printed = canvas.print_with_attr(row, col, &icon, default_attr).unwrap_or(0);
+            let padding = " ".repeat(icon_width.saturating_sub(printed));
+            let _ = canvas.print_with_attr(row, col + printed, &padding, default_attr);
+        }
+
+        // the matched ranges are of the display if it is matched instead of the text
+        let text = if self.match_display {
+            Cow::Owned(item.display().stripped().to_string())
+        } else {
+            item.text()
+        };
         let (match_start_char, match_end_char) = match matched_item.matched_range {
             Some(MatchedRange::Chars(ref matched_indices)) => {
                 if !matched_indices.is_empty() {
@@ -404,13 +1098,22 @@ impl Selection {
             None => (0, 0),
         };
 
-        let container_width = screen_width - 2;
-        let (shift, full_width) =
-            reshape_string(&text, container_width, match_start_char, match_end_char, self.tabstop);
+        let container_width = screen_width - 2 - gutter_width;
+        let (shift, full_width) = if rows.is_some() {
+            // the rows are not shifted to the matches, but are scrolled together
+            let widest = text
+                .split('\n')
+                .filter_map(|line| accumulate_text_width(line, self.tabstop).last().cloned())
+                .max();
+            (0, widest.unwrap_or(0))
+        } else {
+            reshape_string(&text, container_width, match_start_char, match_end_char, self.tabstop)
+        };
 
         let mut printer = LinePrinter::builder()
             .row(row)
-            .col(2)
+            .rows(rows)
+            .col(2 + gutter_width)
             .tabstop(self.tabstop)
             .container_width(container_width)
             .shift(if self.no_hscroll { 0 } else { shift })
@@ -423,65 +1126,256 @@ impl Selection {
 
         // print the highlighted content
         printer.reset();
+        let widths = cluster_char_widths(&text);
         match matched_item.matched_range {
             Some(MatchedRange::Chars(ref matched_indices)) => {
                 let mut matched_indices_iter = matched_indices.iter().peekable();
 
-                for (ch_idx, ch) in text.chars().enumerate() {
+                for (ch_idx, (ch, &width)) in text.chars().zip(widths.iter()).enumerate() {
                     match matched_indices_iter.peek() {
                         Some(&&match_idx) if ch_idx == match_idx => {
-                            printer.print_char(canvas, ch, matched_attr, false);
+                            printer.print_char_of_width(canvas, ch, width, matched_attr, false);
                             let _ = matched_indices_iter.next();
                         }
                         Some(_) | None => {
-                            printer.print_char(canvas, ch, default_attr, true);
+                            printer.print_char_of_width(canvas, ch, width, default_attr, true);
                         }
                     }
                 }
             }
 
             Some(MatchedRange::ByteRange(start, end)) => {
-                for (idx, ch) in text.char_indices() {
-                    printer.print_char(canvas, ch, matched_attr, !(idx >= start && idx < end));
+                for ((idx, ch), &width) in text.char_indices().zip(widths.iter()) {
+                    printer.print_char_of_width(canvas, ch, width, matched_attr, !(idx >= start && idx < end));
                 }
             }
 
             _ => {}
         }
 
+        // mark the trailing whitespace
+        if self.show_trailing_whitespace {
+            let whitespace_start = trailing_whitespace_start(&text);
+            let whitespace_attr = default_attr.extend(self.theme.trailing_whitespace());
+            printer.reset();
+            for (ch_idx, (ch, &width)) in text.chars().zip(widths.iter()).enumerate() {
+                printer.print_char_of_width(canvas, ch, width, whitespace_attr, ch_idx < whitespace_start);
+            }
+        }
+
         Ok(())
     }
 }
 
-impl Draw for Selection {
-    fn draw(&self, canvas: &mut dyn Canvas) -> Result<()> {
-        let (_screen_width, screen_height) = canvas.size()?;
-        canvas.clear()?;
+/// the char index where the trailing spaces/tabs start, equals to the char count if there are none
+fn trailing_whitespace_start(text: &str) -> usize {
+    let trimmed = text.trim_end_matches([' ', '\t']);
+    trimmed.chars().count()
+}
+
+impl Selection {
+    fn draw_grid(&self, canvas: &mut dyn Canvas) -> Result<()> {
+        let (screen_width, screen_height) = canvas.size()?;
+        self.height.store(screen_height, Ordering::Relaxed);
+
+        let columns = self.columns();
+        let cell_width = min(screen_width, self.grid_cell_width + 3);
 
         let item_idx_lower = self.item_cursor;
-        let max_upper = self.item_cursor + screen_height;
-        let item_idx_upper = min(max_upper, self.items.len());
+        let item_idx_upper = min(self.item_cursor + screen_height * columns, self.items.len());
 
         for item_idx in item_idx_lower..item_idx_upper {
             let line_cursor = item_idx - item_idx_lower;
+            let (row, col) = (line_cursor / columns, line_cursor % columns);
             let line_no = if self.reverse {
                 // top down
-                line_cursor
+                row
             } else {
                 // bottom up
-                screen_height - 1 - line_cursor
+                screen_height - 1 - row
             };
 
+            let mut cell = BoundedCanvas::new(0, col * cell_width, cell_width, screen_height, canvas);
+
             // print the cursor label
             let label = if line_cursor == self.line_cursor { ">" } else { " " };
-            let _next_col = canvas.print_with_attr(line_no, 0, label, self.theme.cursor()).unwrap();
+            let _next_col = cell.print_with_attr(line_no, 0, label, self.theme.cursor()).unwrap();
 
+            let item = self
+                .items
+                .get(item_idx)
+                .unwrap_or_else(|| panic!("model:draw_grid: failed to get item at {}", item_idx));
+
+            let _ = self.draw_item_guarded(&mut cell, line_no, item, line_cursor == self.line_cursor);
+        }
+
+        Ok(())
+    }
+}
+
+impl Draw for Selection {
+    fn draw(&self, canvas: &mut dyn Canvas) -> Result<()> {
+        let (screen_width, screen_height) = canvas.size()?;
+        canvas.clear()?;
+        self.width.store(screen_width, Ordering::Relaxed);
+
+        match self.max_rendered_rows {
+            Some(max_rows) if max_rows < screen_height => {
+                // only the rows next to the prompt are drawn
+                let top = if self.reverse { 0 } else { screen_height - max_rows };
+                self.render_offset.store(top, Ordering::Relaxed);
+                self.draw_list(&mut BoundedCanvas::new(top, 0, screen_width, max_rows, canvas))
+            }
+            _ => {
+                self.render_offset.store(0, Ordering::Relaxed);
+                self.draw_list(canvas)
+            }
+        }
+    }
+}
+
+impl Selection {
+    /// draw the items, and the scrollbar heatmap on the last column if enabled
+    fn draw_list(&self, canvas: &mut dyn Canvas) -> Result<()> {
+        let (screen_width, screen_height) = canvas.size()?;
+        if !self.scrollbar_heatmap || self.items.is_empty() || screen_width < 2 {
+            return self.draw_rows(canvas);
+        }
+
+        self.draw_rows(&mut BoundedCanvas::new(0, 0, screen_width - 1, screen_height, canvas))?;
+
+        // the items drawn, known after drawing them
+        let height = self.height.load(Ordering::Relaxed);
+        let num_drawn = if self.grid {
+            height * self.columns()
+        } else {
+            self.rows_of_drawn(height).len()
+        };
+        let len = self.items.len();
+        let bucket_of = |item_idx: usize| item_idx * screen_height / len;
+        let thumb = bucket_of(self.item_cursor)..=bucket_of(min(self.item_cursor + num_drawn, len) - 1);
+
+        let levels = self.heat_levels(screen_height);
+        for (bucket, &level) in levels.iter().enumerate() {
+            let row = if self.is_top_down() {
+                bucket
+            } else {
+                screen_height - 1 - bucket
+            };
+            let ch = if thumb.contains(&bucket) { '┃' } else { ' ' };
+            let attr = Attr {
+                bg: Color::AnsiValue(HEAT_COLORS[level]),
+                ..self.theme.normal()
+            };
+            let _ = canvas.put_cell(row, screen_width - 1, Cell::default().ch(ch).attribute(attr));
+        }
+
+        Ok(())
+    }
+
+    /// the heat level of each row of the scrollbar, by the best score of the items it covers,
+    /// relative to the scores of all the items. Cached until the items change.
+    fn heat_levels(&self, rows: usize) -> Vec<usize> {
+        if let Some((cached_rows, ref levels)) = *self.heatmap.lock() {
+            if cached_rows == rows {
+                return levels.clone();
+            }
+        }
+
+        let mut best = vec![None; rows];
+        let len = self.items.len();
+        for (item_idx, matched) in self.items.iter().enumerate() {
+            let bucket = &mut best[item_idx * rows / len];
+            *bucket = max(*bucket, Some(-matched.rank.score));
+        }
+
+        let scores: Vec<i64> = best.iter().filter_map(|score| *score).collect();
+        let lowest = scores.iter().min().cloned().unwrap_or(0);
+        let highest = scores.iter().max().cloned().unwrap_or(0);
+        let top_level = HEAT_COLORS.len() - 1;
+        let levels: Vec<usize> = best
+            .iter()
+            .map(|score| match *score {
+                Some(score) if highest > lowest => ((score - lowest) * top_level as i64 / (highest - lowest)) as usize,
+                Some(_) => top_level,
+                None => 0,
+            })
+            .collect();
+
+        *self.heatmap.lock() = Some((rows, levels.clone()));
+        levels
+    }
+
+    fn draw_rows(&self, canvas: &mut dyn Canvas) -> Result<()> {
+        let (screen_width, screen_height) = canvas.size()?;
+        *self.inline_preview_area.lock() = None;
+
+        if self.grid {
+            return self.draw_grid(canvas);
+        }
+
+        // the inline preview pushes the items below the current one down, at least an item is shown
+        let preview_rows = if self.items.is_empty() {
+            0
+        } else {
+            min(self.inline_preview_rows, screen_height.saturating_sub(1))
+        };
+        let list_height = screen_height - preview_rows;
+        self.height.store(list_height, Ordering::Relaxed);
+
+        // the first row of the item, the rows of a multi-row item are drawn top down in any layout
+        let rows = self.rows_of_drawn(list_height);
+        let row_of = |line_cursor: usize| {
+            let rows_to_prompt = self.rows_to_prompt(&rows, line_cursor);
+            if self.reverse {
+                // top down
+                rows_to_prompt
+            } else {
+                // bottom up
+                list_height - rows_to_prompt - rows[line_cursor]
+            }
+        };
+
+        let cursor_row = if preview_rows > 0 && self.line_cursor < rows.len() {
+            let cursor_row = row_of(self.line_cursor);
+            let preview_top = cursor_row + rows[self.line_cursor];
+            *self.inline_preview_area.lock() = Some((preview_top, preview_rows));
+            Some(cursor_row)
+        } else {
+            None
+        };
+
+        for (line_cursor, &item_rows) in rows.iter().enumerate() {
+            let line_no = match (row_of(line_cursor), cursor_row) {
+                (row, Some(cursor_row)) if row > cursor_row => row + preview_rows,
+                (row, _) => row,
+            };
+
+            let item_idx = self.item_idx_of_line(line_cursor);
             let item = self
                 .items
                 .get(item_idx)
                 .unwrap_or_else(|| panic!("model:draw_items: failed to get item at {}", item_idx));
 
-            let _ = self.draw_item(canvas, line_no, &item, line_cursor == self.line_cursor);
+            // the separator faces the items after the pinned ones, shown unless the item is cut
+            let line_no = if self.is_last_pinned(item_idx) && item_rows == self.rows_of_item(item_idx) {
+                let (item_row, separator_row) = if self.is_top_down() {
+                    (line_no, line_no + item_rows - 1)
+                } else {
+                    (line_no + 1, line_no)
+                };
+                let separator = "─".repeat(screen_width);
+                let _ = canvas.print_with_attr(separator_row, 0, &separator, self.theme.border());
+                item_row
+            } else {
+                line_no
+            };
+
+            // print the cursor label
+            let label = if line_cursor == self.line_cursor { ">" } else { " " };
+            let _next_col = canvas.print_with_attr(line_no, 0, label, self.theme.cursor()).unwrap();
+
+            let _ = self.draw_item_guarded(canvas, line_no, item, line_cursor == self.line_cursor);
         }
 
         Ok(())
@@ -492,8 +1386,10 @@ impl Widget<Event> for Selection {
     fn on_event(&self, event: TermEvent, _rect: Rectangle) -> Vec<Event> {
         let mut ret = vec![];
         match event {
-            TermEvent::Key(Key::MousePress(MouseButton::WheelUp, ..)) => ret.push(Event::EvActUp(1)),
-            TermEvent::Key(Key::MousePress(MouseButton::WheelDown, ..)) => ret.push(Event::EvActDown(1)),
+            TermEvent::Key(Key::MousePress(MouseButton::WheelUp, ..)) => ret.push(Event::EvActUp(self.scroll_lines)),
+            TermEvent::Key(Key::MousePress(MouseButton::WheelDown, ..)) => {
+                ret.push(Event::EvActDown(self.scroll_lines))
+            }
             TermEvent::Key(Key::MousePress(MouseButton::Left, row, _)) => {
                 let row = row as usize;
                 if self.last_click_row.load(Ordering::SeqCst) == row
@@ -518,9 +1414,41 @@ impl Widget<Event> for Selection {
     }
 }
 
+/// The criteria of `--tiebreak` (score,begin,end,index by default), with the index reversed by `--tac`
+fn rank_criteria(options: &SkimOptions) -> Vec<RankCriteria> {
+    let criterion: Vec<RankCriteria> = match options.tiebreak {
+        Some(ref tie_breaker) => tie_breaker.split(',').filter_map(parse_criteria).collect(),
+        None => DEFAULT_CRITERION.clone(),
+    };
+
+    if !options.tac {
+        return criterion;
+    }
+
+    criterion
+        .into_iter()
+        .map(|criteria| match criteria {
+            RankCriteria::Index => RankCriteria::NegIndex,
+            RankCriteria::NegIndex => RankCriteria::Index,
+            criteria => criteria,
+        })
+        .collect()
+}
+
+/// Compare the matched items the way the list ranks them with the given options (best first)
+pub fn compare_matched_items(options: &SkimOptions) -> CompareFunction<MatchedItem> {
+    build_compare_function(rank_criteria(options))
+}
+
 fn build_compare_function(criterion: Vec<RankCriteria>) -> CompareFunction<MatchedItem> {
     use std::cmp::Ordering as CmpOrd;
     Box::new(move |a: &MatchedItem, b: &MatchedItem| {
+        // the pinned items go first whatever the criteria
+        let (a_pinned, b_pinned) = (a.item.pinned(), b.item.pinned());
+        if a_pinned != b_pinned {
+            return b_pinned.cmp(&a_pinned);
+        }
+
         for &criteria in criterion.iter() {
             match criteria {
                 RankCriteria::Begin => {
@@ -551,6 +1479,20 @@ fn build_compare_function(criterion: Vec<RankCriteria>) -> CompareFunction<Match
                         return b.rank.end.cmp(&a.rank.end);
                     }
                 }
+                RankCriteria::External => {
+                    if a.rank.external == b.rank.external {
+                        continue;
+                    } else {
+                        return a.rank.external.cmp(&b.rank.external);
+                    }
+                }
+                RankCriteria::NegExternal => {
+                    if a.rank.external == b.rank.external {
+                        continue;
+                    } else {
+                        return b.rank.external.cmp(&a.rank.external);
+                    }
+                }
                 RankCriteria::Index => {
                     if a.rank.index == b.rank.index {
                         continue;
@@ -584,3 +1526,844 @@ fn build_compare_function(criterion: Vec<RankCriteria>) -> CompareFunction<Match
         CmpOrd::Equal
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::item::Rank;
+    use crate::util::screen_row;
+    use crate::AnsiString;
+    use std::borrow::Cow;
+    use tuikit::screen::Screen;
+
+    /// the items matched in the order given (ranked by their indices)
+    fn matched_items_of(items: Vec<Arc<dyn SkimItem>>) -> Vec<MatchedItem> {
+        items
+            .into_iter()
+            .enumerate()
+            .map(|(idx, item)| {
+                let item = Arc::new(ItemWrapper::new(item, (0, idx as u32)));
+                let rank = Rank {
+                    index: idx as i64,
+                    ..Rank::default()
+                };
+                MatchedItem::builder(item).rank(rank).build()
+            })
+            .collect()
+    }
+
+    fn matched_items(num: usize) -> Vec<MatchedItem> {
+        matched_items_of(
+            (0..num)
+                .map(|idx| Arc::new(format!("item {}", idx)) as Arc<dyn SkimItem>)
+                .collect(),
+        )
+    }
+
+    fn selection_with_items(num: usize) -> Selection {
+        let mut selection = Selection::new();
+        selection.append_sorted_items(matched_items(num));
+        selection
+    }
+
+    /// an item of the text, with the other properties of `SkimItem` set as needed
+    #[derive(Default)]
+    struct TestItem {
+        text: &'static str,
+        icon: Option<char>,
+        row_style: Option<Attr>,
+        rows: usize, // 1 if not set
+        pinned: bool,
+    }
+
+    fn test_item(text: &'static str) -> TestItem {
+        TestItem {
+            text,
+            ..TestItem::default()
+        }
+    }
+
+    impl SkimItem for TestItem {
+        fn display(&self) -> Cow<'_, AnsiString<'_>> {
+            Cow::Owned(self.text.into())
+        }
+
+        fn text(&self) -> Cow<'_, str> {
+            Cow::Borrowed(self.text)
+        }
+
+        fn icon(&self) -> Option<char> {
+            self.icon
+        }
+
+        fn row_style(&self) -> Option<Attr> {
+            self.row_style
+        }
+
+        fn rows(&self) -> usize {
+            max(self.rows, 1)
+        }
+
+        fn pinned(&self) -> bool {
+            self.pinned
+        }
+    }
+
+    struct BadItem {
+        panic: bool,
+    }
+
+    static BAD_DISPLAYS: AtomicUsize = AtomicUsize::new(0);
+
+    impl SkimItem for BadItem {
+        fn display(&self) -> Cow<'_, AnsiString<'_>> {
+            if self.panic {
+                BAD_DISPLAYS.fetch_add(1, Ordering::SeqCst);
+                panic!("bad display");
+            }
+            Cow::Owned("".into())
+        }
+
+        fn text(&self) -> Cow<'_, str> {
+            Cow::Borrowed("fallback")
+        }
+    }
+
+    #[test]
+    fn test_draw_bad_items() {
+        let mut selection = Selection::new();
+        selection.reverse = true;
+        let items = vec![
+            Arc::new(BadItem { panic: true }) as Arc<dyn SkimItem>,
+            Arc::new(BadItem { panic: false }),
+            Arc::new("good"),
+        ];
+        selection.append_sorted_items(matched_items_of(items));
+
+        // the panic goes to the diagnostics, not to stderr
+        let messages = Arc::new(SpinLock::new(Vec::new()));
+        let messages_clone = messages.clone();
+        let prev = diagnostics::set_sink(Some(Arc::new(move |_, msg: &str| {
+            messages_clone.lock().push(msg.to_string())
+        })));
+
+        let mut screen = Screen::new(20, 3);
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 1), "fallback");
+        assert_eq!(screen_row(&screen, 2), "good");
+
+        // the bad item is not drawn again
+        selection.draw(&mut screen).unwrap();
+        diagnostics::set_sink(prev);
+        assert_eq!(BAD_DISPLAYS.load(Ordering::SeqCst), 1);
+        assert!(messages.lock().iter().any(|msg| msg.contains("bad display")));
+    }
+
+    #[test]
+    fn test_trailing_whitespace_start() {
+        assert_eq!(trailing_whitespace_start(""), 0);
+        assert_eq!(trailing_whitespace_start("abc"), 3);
+        assert_eq!(trailing_whitespace_start("a b \t "), 3);
+        assert_eq!(trailing_whitespace_start("  "), 0);
+        assert_eq!(trailing_whitespace_start("中文 "), 2);
+    }
+
+    #[test]
+    fn test_relayout_keeps_current_item() {
+        let mut selection = selection_with_items(100);
+        selection.height.store(10, Ordering::Relaxed);
+        selection.act_move_line_cursor(28);
+        assert_eq!(selection.get_current_item_idx(), 28);
+
+        // shrink the screen, the cursor should be scrolled into view
+        selection.height.store(5, Ordering::Relaxed);
+        selection.act_relayout();
+        assert_eq!(selection.get_current_item_idx(), 28);
+        assert!(selection.line_cursor < 5);
+
+        // enlarge the screen, still the same item
+        selection.height.store(40, Ordering::Relaxed);
+        selection.act_relayout();
+        assert_eq!(selection.get_current_item_idx(), 28);
+        assert!(selection.line_cursor < 40);
+        assert_eq!(selection.get_current_item().unwrap().text(), "item 28");
+    }
+
+    #[test]
+    fn test_relayout_fills_the_screen() {
+        let mut selection = selection_with_items(20);
+        selection.height.store(5, Ordering::Relaxed);
+        selection.act_move_line_cursor(19);
+        assert_eq!(selection.get_current_item_idx(), 19);
+
+        // all items could be shown now, no need to scroll
+        selection.height.store(30, Ordering::Relaxed);
+        selection.act_relayout();
+        assert_eq!(selection.item_cursor, 0);
+        assert_eq!(selection.get_current_item_idx(), 19);
+    }
+
+    #[test]
+    fn test_remove_current_item() {
+        let mut selection = selection_with_items(3);
+        selection.height.store(10, Ordering::Relaxed);
+        selection.multi_selection = true;
+        selection.act_move_line_cursor(2);
+        selection.act_toggle();
+        assert_eq!(selection.get_current_item().unwrap().text(), "item 2");
+
+        // the last one, the cursor moves to the previous item
+        let removed = selection.remove_current_item().unwrap();
+        assert_eq!(removed.text(), "item 2");
+        assert_eq!(selection.get_num_selected(), 0);
+        assert_eq!(selection.get_current_item().unwrap().text(), "item 1");
+
+        // the cursor stays on the row, i.e. the next item
+        selection.act_move_line_cursor(-1);
+        assert_eq!(selection.remove_current_item().unwrap().text(), "item 0");
+        assert_eq!(selection.get_current_item().unwrap().text(), "item 1");
+
+        assert_eq!(selection.remove_current_item().unwrap().text(), "item 1");
+        assert!(selection.remove_current_item().is_none());
+        assert_eq!(selection.get_current_item_idx(), 0);
+    }
+
+    #[test]
+    fn test_goto_mark() {
+        let mut selection = selection_with_items(100);
+        selection.height.store(10, Ordering::Relaxed);
+        selection.act_move_line_cursor(42);
+        selection.act_mark(1);
+        assert!(!selection.act_goto_mark(2));
+
+        selection.act_move_line_cursor(-40);
+        assert_eq!(selection.get_current_item_idx(), 2);
+        assert!(selection.act_goto_mark(1));
+        assert_eq!(selection.get_current_item_idx(), 42);
+
+        // the marked item is no longer matched
+        selection.clear();
+        selection.append_sorted_items(matched_items(10));
+        let current = selection.get_current_item_idx();
+        assert!(!selection.act_goto_mark(1));
+        assert_eq!(selection.get_current_item_idx(), current);
+    }
+
+    #[test]
+    fn test_move_cursor_to_first() {
+        let mut selection = selection_with_items(100);
+        selection.height.store(10, Ordering::Relaxed);
+        assert!(selection.act_move_cursor_to_first(|item| item.text().ends_with('7')));
+        assert_eq!(selection.get_current_item_idx(), 7);
+        assert_eq!(selection.get_current_item().unwrap().text(), "item 7");
+
+        assert!(selection.act_move_cursor_to_first(|item| item.text() == "item 77"));
+        assert_eq!(selection.get_current_item_idx(), 77);
+
+        // nothing satisfies, the cursor stays
+        assert!(!selection.act_move_cursor_to_first(|item| item.text() == "item 100"));
+        assert_eq!(selection.get_current_item_idx(), 77);
+    }
+
+    #[test]
+    fn test_jump() {
+        let mut selection = selection_with_items(30);
+        selection.height.store(10, Ordering::Relaxed);
+        let has_7 = |item: &ItemWrapper| item.text().contains('7');
+
+        assert!(selection.act_jump(true, has_7));
+        assert_eq!(selection.get_current_item_idx(), 7);
+        assert!(selection.act_jump(true, has_7));
+        assert_eq!(selection.get_current_item_idx(), 17);
+        assert!(selection.act_jump(false, has_7));
+        assert_eq!(selection.get_current_item_idx(), 7);
+
+        // not wrapped around
+        assert!(!selection.act_jump(false, has_7));
+        selection.act_move_line_cursor(20);
+        assert!(!selection.act_jump(true, has_7));
+        assert_eq!(selection.get_current_item_idx(), 27);
+    }
+
+    #[test]
+    fn test_toggle_all_inverts_matched_only() {
+        let mut selection = selection_with_items(100);
+        selection.multi_selection = true;
+        selection.height.store(10, Ordering::Relaxed);
+        selection.act_move_line_cursor(50);
+        selection.act_toggle();
+
+        // only the first 10 items are matched now
+        selection.clear();
+        selection.append_sorted_items(matched_items(10));
+        selection.act_move_cursor_to_first(|item| item.text() == "item 1");
+        selection.act_toggle();
+
+        selection.act_toggle_all();
+        let mut selected: Vec<_> = selection.selected.keys().map(|&(_, idx)| idx).collect();
+        selected.sort();
+        assert_eq!(selected, vec![0, 2, 3, 4, 5, 6, 7, 8, 9, 50]);
+    }
+
+    #[test]
+    fn test_retain_filtered_selection() {
+        let select_and_filter = |retain: bool| {
+            let mut selection = selection_with_items(100);
+            selection.multi_selection = true;
+            selection.retain_filtered_selection = retain;
+            selection.height.store(10, Ordering::Relaxed);
+            selection.act_toggle();
+            selection.act_move_line_cursor(50);
+            selection.act_toggle();
+
+            // only the first 10 items are matched now
+            selection.clear();
+            selection.append_sorted_items(matched_items(10));
+            selection
+        };
+
+        let mut selection = select_and_filter(true);
+        assert_eq!(selection.get_num_hidden_selected(), 1);
+        selection.act_toggle();
+        assert_eq!(selection.get_num_hidden_selected(), 1);
+        selection.act_toggle();
+        selection.act_toggle_all();
+        assert_eq!(selection.get_num_hidden_selected(), 1);
+        assert_eq!(selection.get_num_selected(), 10);
+        selection.act_toggle_all();
+        selection.act_select_all();
+        assert_eq!(selection.get_num_hidden_selected(), 1);
+        selection.act_deselect_all();
+        assert_eq!(selection.get_num_hidden_selected(), 0);
+
+        let mut selection = select_and_filter(true);
+        selection.drop_filtered_selected();
+        assert_eq!(selection.get_num_selected(), 2);
+        assert_eq!(selection.get_selected_items().len(), 2);
+
+        let mut selection = select_and_filter(false);
+        selection.drop_filtered_selected();
+        assert_eq!(selection.get_num_selected(), 1);
+        assert_eq!(selection.get_num_hidden_selected(), 0);
+        assert_eq!(&*selection.get_selected_items()[0].text(), "item 0");
+    }
+
+    #[test]
+    fn test_select_order() {
+        let mut selection = selection_with_items(10);
+        selection.multi_selection = true;
+        selection.height.store(10, Ordering::Relaxed);
+        let toggle = |selection: &mut Selection, text: &str| {
+            selection.act_move_cursor_to_first(|item| item.text() == text);
+            selection.act_toggle();
+        };
+        toggle(&mut selection, "item 5");
+        toggle(&mut selection, "item 2");
+        toggle(&mut selection, "item 7");
+        toggle(&mut selection, "item 5");
+        toggle(&mut selection, "item 5");
+
+        let texts = |selection: &Selection| -> Vec<String> {
+            selection
+                .get_selected_items()
+                .iter()
+                .map(|item| item.text().into_owned())
+                .collect()
+        };
+        assert_eq!(texts(&selection), vec!["item 2", "item 5", "item 7"]);
+
+        // reselected: moved to the end
+        selection.select_order = true;
+        assert_eq!(texts(&selection), vec!["item 2", "item 7", "item 5"]);
+    }
+
+    #[test]
+    fn test_mouse_scroll_lines() {
+        let mut selection = selection_with_items(10);
+        selection.scroll_lines = 3;
+        let rect = Rectangle {
+            top: 0,
+            left: 0,
+            width: 20,
+            height: 10,
+        };
+        let wheel_down = TermEvent::Key(Key::MousePress(MouseButton::WheelDown, 0, 0));
+        assert_eq!(selection.on_event(wheel_down, rect), vec![Event::EvActDown(3)]);
+    }
+
+    #[test]
+    fn test_get_matched_items_ignores_selection() {
+        let mut selection = selection_with_items(5);
+        selection.multi_selection = true;
+        selection.act_toggle();
+
+        let texts: Vec<_> = selection
+            .get_matched_items()
+            .iter()
+            .map(|item| item.text().into_owned())
+            .collect();
+        assert_eq!(texts, vec!["item 0", "item 1", "item 2", "item 3", "item 4"]);
+        assert_eq!(selection.get_matched_scores().len(), 5);
+        assert_eq!(selection.get_selected_items().len(), 1);
+    }
+
+    #[test]
+    fn test_gutter_width() {
+        let mut selection = Selection::new();
+        selection.line_numbers = true;
+        assert_eq!(selection.gutter_width(), 2);
+
+        selection.append_sorted_items(matched_items(9));
+        assert_eq!(selection.gutter_width(), 2);
+        selection.append_sorted_items(matched_items(100));
+        assert_eq!(selection.gutter_width(), 4);
+
+        selection.clear();
+        selection.append_sorted_items(matched_items(10));
+        assert_eq!(selection.gutter_width(), 3);
+
+        selection.grid = true;
+        assert_eq!(selection.gutter_width(), 0);
+    }
+
+    #[test]
+    fn test_icons() {
+        let mut selection = Selection::new();
+        selection.reverse = true;
+        let items: Vec<Arc<dyn SkimItem>> = vec![
+            Arc::new(TestItem {
+                icon: Some('R'),
+                ..test_item("lib.rs")
+            }),
+            Arc::new(test_item("doc")),
+            Arc::new(TestItem {
+                icon: Some('文'),
+                ..test_item("中")
+            }),
+        ];
+        assert_eq!(selection.icon_width(), 0);
+
+        selection.append_sorted_items(matched_items_of(items));
+        assert_eq!(selection.icon_width(), 3);
+
+        let mut screen = Screen::new(20, 3);
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 0), "> R  lib.rs");
+        assert_eq!(screen_row(&screen, 1), "doc");
+        // the wide icon takes two cells, the second one is a blank
+        assert_eq!(screen_row(&screen, 2), "文  中");
+
+        selection.clear();
+        assert_eq!(selection.icon_width(), 0);
+    }
+
+    #[test]
+    fn test_row_style() {
+        let mut selection = Selection::new();
+        selection.reverse = true;
+        let red = Attr::default().bg(Color::RED);
+        let items: Vec<Arc<dyn SkimItem>> = vec![
+            Arc::new(TestItem {
+                row_style: Some(red),
+                ..test_item("current")
+            }),
+            Arc::new(TestItem {
+                row_style: Some(red),
+                ..test_item("failing")
+            }),
+            Arc::new(test_item("plain")),
+        ];
+        selection.append_sorted_items(matched_items_of(items));
+
+        let mut screen = Screen::new(20, 3);
+        selection.draw(&mut screen).unwrap();
+        let bg_of = |row: usize, col: usize| {
+            screen
+                .iter_cell()
+                .find(|&(r, c, _)| r == row && c == col)
+                .map(|(_, _, cell)| cell.attr.bg)
+                .unwrap()
+        };
+
+        // the whole row is tinted, but the cursor line is drawn over it
+        assert_eq!(screen_row(&screen, 1), "failing");
+        assert_eq!(bg_of(1, 0), Color::RED);
+        assert_eq!(bg_of(1, 3), Color::RED);
+        assert_eq!(bg_of(1, 19), Color::RED);
+        assert_eq!(bg_of(0, 3), selection.theme.current().bg);
+        assert_eq!(bg_of(2, 19), Color::Default);
+    }
+
+    #[test]
+    fn test_multi_row_items() {
+        let commits = vec!["fix a\nthe body of a", "fix b\nthe body of b", "fix c\nthe body of c"];
+        let items = commits
+            .into_iter()
+            .map(|commit| {
+                Arc::new(TestItem {
+                    rows: 2,
+                    ..test_item(commit)
+                }) as Arc<dyn SkimItem>
+            })
+            .collect();
+        let mut items = matched_items_of(items);
+        for matched in items.iter_mut() {
+            matched.matched_range = Some(MatchedRange::ByteRange(4, 5));
+        }
+        let mut selection = Selection::new();
+        selection.reverse = true;
+        selection.append_sorted_items(items);
+
+        let mut screen = Screen::new(20, 5);
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 0), "> fix a");
+        assert_eq!(screen_row(&screen, 1), "the body of a");
+        assert_eq!(screen_row(&screen, 2), "fix b");
+        assert_eq!(screen_row(&screen, 3), "the body of b");
+        // cut by the bottom
+        assert_eq!(screen_row(&screen, 4), "fix c");
+
+        // the rows are moved over as a whole
+        selection.act_move_line_cursor(-1);
+        assert_eq!(selection.get_current_item_idx(), 1);
+        selection.act_move_line_cursor(-1);
+        assert_eq!((selection.item_cursor, selection.line_cursor), (1, 1));
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 2), "> fix c");
+        assert_eq!(screen_row(&screen, 3), "the body of c");
+
+        // any row of the item selects it
+        selection.act_select_screen_row(1);
+        assert_eq!(selection.get_current_item_idx(), 1);
+        selection.act_select_screen_row(4);
+        assert_eq!(selection.get_current_item_idx(), 1);
+    }
+
+    #[test]
+    fn test_pin_top() {
+        let mut selection = selection_with_items(100);
+        selection.pin_top = true;
+        selection.height.store(10, Ordering::Relaxed);
+
+        selection.act_move_line_cursor(9);
+        assert_eq!((selection.item_cursor, selection.line_cursor), (0, 9));
+
+        // scrolled, the item on the first row is covered by the pinned one
+        selection.act_move_line_cursor(1);
+        assert_eq!(selection.get_current_item_idx(), 10);
+        assert_eq!(selection.num_covered_lines(), 1);
+
+        // moving up scrolls instead of landing on the covered row
+        selection.act_move_line_cursor(-8);
+        assert_eq!(selection.get_current_item_idx(), 2);
+        assert_eq!((selection.item_cursor, selection.line_cursor), (1, 1));
+        selection.act_move_line_cursor(-1);
+        assert_eq!((selection.item_cursor, selection.line_cursor), (0, 1));
+        selection.act_move_line_cursor(-1);
+        assert_eq!(selection.get_current_item_idx(), 0);
+
+        // the pinned item could be clicked
+        selection.act_move_line_cursor(20);
+        selection.act_select_screen_row(9);
+        assert_eq!(selection.get_current_item_idx(), 0);
+    }
+
+    #[test]
+    fn test_follow() {
+        let mut items = matched_items(20);
+        let mut selection = Selection::new();
+        selection.follow = true;
+        selection.height.store(5, Ordering::Relaxed);
+        selection.append_sorted_items(items.split_off(10));
+
+        // on the top, the best match is followed
+        selection.append_sorted_items(items.split_off(8));
+        assert_eq!(selection.get_current_item().unwrap().get_index(), 8);
+
+        // moved away, the cursor stays on its item
+        selection.act_move_line_cursor(2);
+        assert_eq!(selection.get_current_item().unwrap().get_index(), 10);
+        selection.append_sorted_items(items.split_off(4));
+        assert_eq!(selection.get_current_item().unwrap().get_index(), 10);
+        assert_eq!(selection.line_cursor, 2);
+
+        // followed again once back on the top
+        selection.act_move_line_cursor(-10);
+        selection.append_sorted_items(items);
+        assert_eq!(selection.get_current_item().unwrap().get_index(), 0);
+    }
+
+    #[test]
+    fn test_pinned_items() {
+        let files = vec![("a.rs", false), ("b.rs", false), ("c.rs", true), ("d.rs", true)];
+        let items = files
+            .into_iter()
+            .map(|(file, pinned)| {
+                Arc::new(TestItem {
+                    pinned,
+                    ..test_item(file)
+                }) as Arc<dyn SkimItem>
+            })
+            .collect();
+        let mut selection = Selection::new();
+        selection.reverse = true;
+        selection.append_sorted_items(matched_items_of(items));
+
+        // ranked among themselves above the others, the separator takes a row
+        let mut screen = Screen::new(20, 6);
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 0), "> c.rs");
+        assert_eq!(screen_row(&screen, 1), "d.rs");
+        assert_eq!(screen_row(&screen, 2), "─".repeat(20));
+        assert_eq!(screen_row(&screen, 3), "a.rs");
+        assert_eq!(screen_row(&screen, 4), "b.rs");
+
+        // the separator is skipped by the cursor
+        selection.act_move_line_cursor(-2);
+        assert_eq!(selection.get_current_item().unwrap().text(), "a.rs");
+
+        // bottom up, the separator is still between them
+        selection.reverse = false;
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(screen_row(&screen, 5), "c.rs");
+        assert_eq!(screen_row(&screen, 4), "d.rs");
+        assert_eq!(screen_row(&screen, 3), "─".repeat(20));
+        assert_eq!(screen_row(&screen, 2), "> a.rs");
+    }
+
+    #[test]
+    fn test_pinned_items_scrolled() {
+        let texts = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+        let items = texts
+            .iter()
+            .enumerate()
+            .map(|(idx, text)| {
+                Arc::new(TestItem {
+                    pinned: idx < 2,
+                    ..test_item(text)
+                }) as Arc<dyn SkimItem>
+            })
+            .collect();
+        let mut selection = Selection::new();
+        selection.reverse = true;
+        selection.append_sorted_items(matched_items_of(items));
+
+        // the pinned items and the separator stay on the top rows past the end of the screen
+        let mut screen = Screen::new(20, 6);
+        selection.draw(&mut screen).unwrap();
+        selection.act_move_line_cursor(-6);
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(selection.get_current_item().unwrap().text(), "6");
+        assert_eq!(screen_row(&screen, 0), "0");
+        assert_eq!(screen_row(&screen, 1), "1");
+        assert_eq!(screen_row(&screen, 2), "─".repeat(20));
+        assert_eq!(screen_row(&screen, 3), "4");
+        assert_eq!(screen_row(&screen, 4), "5");
+        assert_eq!(screen_row(&screen, 5), "> 6");
+
+        // the scrolled items go on below them
+        selection.act_move_line_cursor(-3);
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(selection.get_current_item().unwrap().text(), "9");
+        assert_eq!(screen_row(&screen, 3), "7");
+        assert_eq!(screen_row(&screen, 0), "0");
+
+        // moving up past the scrolled items shows the hidden ones again
+        selection.act_move_line_cursor(3);
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(selection.get_current_item().unwrap().text(), "6");
+        assert_eq!(screen_row(&screen, 3), "> 6");
+        assert_eq!(screen_row(&screen, 1), "1");
+        selection.act_move_line_cursor(5);
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(selection.get_current_item().unwrap().text(), "1");
+        assert_eq!(screen_row(&screen, 3), "2");
+    }
+
+    #[test]
+    fn test_scrollbar_heatmap() {
+        // the scores drop from 100 down to 1
+        let mut items = matched_items(100);
+        for matched in items.iter_mut() {
+            matched.rank.score = matched.rank.index - 100;
+        }
+        let mut selection = Selection::new();
+        selection.scrollbar_heatmap = true;
+        selection.reverse = true;
+        selection.append_sorted_items(items);
+
+        let mut screen = Screen::new(20, 5);
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(selection.heat_levels(5), vec![9, 6, 4, 2, 0]);
+
+        // the first row covers the items on the screen, the rows are in the colors of their heat
+        let track: Vec<Cell> = (0..5)
+            .map(|row| *screen.iter_cell().find(|&(r, c, _)| r == row && c == 19).unwrap().2)
+            .collect();
+        assert_eq!(track[0].ch, '┃');
+        assert_eq!(track[1].ch, ' ');
+        assert_eq!(track[0].attr.bg, Color::AnsiValue(HEAT_COLORS[9]));
+        assert_eq!(track[4].attr.bg, Color::AnsiValue(HEAT_COLORS[0]));
+        assert_eq!(screen_row(&screen, 1), "item 1");
+    }
+
+    #[test]
+    fn test_display_toward_prompt() {
+        let mut selection = selection_with_items(3);
+        selection.reverse = true;
+        selection.first_far = true;
+
+        let mut screen = Screen::new(20, 5);
+        selection.draw(&mut screen).unwrap();
+        // the items are next to the prompt (above the first row), the first one farthest
+        assert_eq!(screen_row(&screen, 0), "item 2");
+        assert_eq!(screen_row(&screen, 2), "> item 0");
+        assert_eq!(screen_row(&screen, 3), "");
+
+        // up moves to the item drawn above
+        selection.handle(&Event::EvActUp(1));
+        assert_eq!(selection.get_current_item_idx(), 1);
+
+        selection.act_select_screen_row(0);
+        assert_eq!(selection.get_current_item_idx(), 2);
+        selection.act_select_screen_row(4);
+        assert_eq!(selection.get_current_item_idx(), 2);
+    }
+
+    #[test]
+    fn test_inline_preview_rows() {
+        let mut selection = selection_with_items(5);
+        selection.reverse = true;
+        selection.set_inline_preview_rows(2);
+
+        let mut screen = Screen::new(20, 6);
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(selection.inline_preview_area(), Some((1, 2)));
+        assert_eq!(selection.get_height(), 4);
+        assert_eq!(screen_row(&screen, 0), "> item 0");
+        assert_eq!(screen_row(&screen, 1), "");
+        assert_eq!(screen_row(&screen, 3), "item 1");
+
+        // the rows follow the cursor
+        selection.handle(&Event::EvActDown(1));
+        selection.draw(&mut screen).unwrap();
+        assert_eq!(selection.inline_preview_area(), Some((2, 2)));
+        assert_eq!(screen_row(&screen, 1), "> item 1");
+        assert_eq!(screen_row(&screen, 4), "item 2");
+
+        // the clicks on the preview are ignored, the rows below it are shifted
+        selection.act_select_screen_row(3);
+        assert_eq!(selection.get_current_item_idx(), 1);
+        selection.act_select_screen_row(5);
+        assert_eq!(selection.get_current_item_idx(), 3);
+    }
+
+    #[test]
+    fn test_grid_navigation() {
+        let mut selection = Selection::new();
+        selection.grid = true;
+        selection.append_sorted_items(matched_items(20));
+        // "item 10" is 7 wide, with the markers and gap, 3 columns fit in 30 columns
+        selection.width.store(30, Ordering::Relaxed);
+        selection.height.store(2, Ordering::Relaxed);
+        assert_eq!(selection.columns(), 3);
+
+        selection.handle(&Event::EvActRight(1));
+        assert_eq!(selection.get_current_item_idx(), 1);
+        selection.handle(&Event::EvActUp(1));
+        assert_eq!(selection.get_current_item_idx(), 4);
+
+        // scroll a row when the cursor leaves the screen
+        selection.handle(&Event::EvActUp(1));
+        assert_eq!(selection.get_current_item_idx(), 7);
+        assert_eq!(selection.item_cursor, 3);
+
+        selection.handle(&Event::EvActLeft(2));
+        selection.handle(&Event::EvActDown(2));
+        assert_eq!(selection.get_current_item_idx(), 0);
+        assert_eq!(selection.item_cursor, 0);
+
+        // stop at the last item
+        selection.handle(&Event::EvActUp(100));
+        assert_eq!(selection.get_current_item_idx(), 19);
+        assert_eq!(selection.item_cursor, 15);
+    }
+
+    #[test]
+    fn test_external_rank_criteria() {
+        let item = |score, external| {
+            let rank = Rank {
+                score,
+                external,
+                ..Rank::default()
+            };
+            MatchedItem::builder(Arc::new(ItemWrapper::new(Arc::new("item"), (0, 0))))
+                .rank(rank)
+                .build()
+        };
+        let (a, b) = (item(1, 0), item(0, 1));
+
+        let external_first = build_compare_function(vec![RankCriteria::External, RankCriteria::Score]);
+        assert_eq!(external_first(&a, &b), std::cmp::Ordering::Less);
+
+        let score_first = build_compare_function(vec![RankCriteria::Score, RankCriteria::External]);
+        assert_eq!(score_first(&a, &b), std::cmp::Ordering::Greater);
+
+        let neg_external = build_compare_function(vec![RankCriteria::NegExternal]);
+        assert_eq!(neg_external(&a, &b), std::cmp::Ordering::Greater);
+    }
+
+    #[test]
+    fn test_begin_criteria() {
+        let item = |text: &'static str, score, begin| {
+            let rank = Rank {
+                score,
+                begin,
+                ..Rank::default()
+            };
+            MatchedItem::builder(Arc::new(ItemWrapper::new(Arc::new(text), (0, 0))))
+                .rank(rank)
+                .build()
+        };
+        let (config, my_config) = (item("config.rs", 0, 0), item("my_config.rs", 0, 3));
+
+        let begin = build_compare_function(vec![RankCriteria::Score, RankCriteria::Begin]);
+        assert_eq!(begin(&config, &my_config), std::cmp::Ordering::Less);
+
+        let neg_begin = build_compare_function(vec![RankCriteria::Score, RankCriteria::NegBegin]);
+        assert_eq!(neg_begin(&config, &my_config), std::cmp::Ordering::Greater);
+
+        // the ties are broken only after the score
+        let better_score = item("my_config.rs", -1, 3);
+        assert_eq!(begin(&config, &better_score), std::cmp::Ordering::Greater);
+    }
+
+    #[test]
+    fn test_compare_matched_items_by_options() {
+        let item = |score, index| {
+            let rank = Rank {
+                score,
+                index,
+                ..Rank::default()
+            };
+            MatchedItem::builder(Arc::new(ItemWrapper::new(Arc::new("item"), (0, 0))))
+                .rank(rank)
+                .build()
+        };
+        let (a, b) = (item(1, 0), item(0, 1));
+
+        let by_default = compare_matched_items(&SkimOptions::default());
+        assert_eq!(by_default(&a, &b), std::cmp::Ordering::Greater);
+
+        let options = SkimOptions {
+            tiebreak: Some("index".to_string()),
+            ..SkimOptions::default()
+        };
+        let by_index = compare_matched_items(&options);
+        assert_eq!(by_index(&a, &b), std::cmp::Ordering::Less);
+
+        let options = SkimOptions { tac: true, ..options };
+        let by_index_tac = compare_matched_items(&options);
+        assert_eq!(by_index_tac(&a, &b), std::cmp::Ordering::Greater);
+    }
+}
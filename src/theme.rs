@@ -7,6 +7,29 @@ lazy_static! {
     pub static ref DEFAULT_THEME:  ColorTheme = ColorTheme::dark256();
 }
 
+/// The base palette of the theme, chosen by the background of the terminal since it could not be
+/// detected reliably. The colors given by `SkimOptions::color` are applied on top of it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ColorScheme {
+    /// tuned for the dark backgrounds, the default
+    #[default]
+    Dark,
+    /// tuned for the light backgrounds, on which the dark one washes out
+    Light,
+    /// no built-in colors, i.e. the terminal's default unless given by `SkimOptions::color`
+    Custom,
+}
+
+impl ColorScheme {
+    pub fn of(scheme: &str) -> Self {
+        match scheme.to_ascii_lowercase().as_ref() {
+            "light" => ColorScheme::Light,
+            "custom" => ColorScheme::Custom,
+            _ => ColorScheme::Dark,
+        }
+    }
+}
+
 /// The color scheme of skim's UI
 ///
 /// <pre>
@@ -43,17 +66,29 @@ pub struct ColorTheme {
     selected:             Color,
     header:               Color,
     border:               Color,
+    whitespace_bg:        Color,
+    whitespace_effect:    Effect,
+    line_number:          Color,
 }
 
 #[rustfmt::skip]
 #[allow(dead_code)]
 impl ColorTheme {
     pub fn init_from_options(options: &SkimOptions) -> ColorTheme {
+        let base = ColorTheme::of_scheme(options.color_scheme);
         // register
         if let Some(color) = options.color {
-            ColorTheme::from_options(color)
+            ColorTheme::from_options(base, color)
         } else {
-            ColorTheme::dark256()
+            base
+        }
+    }
+
+    fn of_scheme(scheme: ColorScheme) -> Self {
+        match scheme {
+            ColorScheme::Dark   => ColorTheme::dark256(),
+            ColorScheme::Light  => ColorTheme::light256(),
+            ColorScheme::Custom => ColorTheme::empty(),
         }
     }
 
@@ -81,6 +116,9 @@ impl ColorTheme {
             selected:             Color::Default,
             header:               Color::Default,
             border:               Color::Default,
+            whitespace_bg:        Color::Default,
+            whitespace_effect:    Effect::empty(),
+            line_number:          Color::Default,
         }
     }
 
@@ -89,6 +127,7 @@ impl ColorTheme {
             matched_effect:       Effect::UNDERLINE,
             current_effect:       Effect::REVERSE,
             current_match_effect: Effect::UNDERLINE | Effect::REVERSE,
+            whitespace_effect:    Effect::REVERSE,
             ..ColorTheme::empty()
         }
     }
@@ -108,6 +147,8 @@ impl ColorTheme {
             selected:         Color::MAGENTA,
             header:           Color::CYAN,
             border:           Color::LIGHT_BLACK,
+            whitespace_bg:    Color::RED,
+            line_number:      Color::LIGHT_BLACK,
             ..ColorTheme::empty()
         }
     }
@@ -127,6 +168,8 @@ impl ColorTheme {
             selected:         Color::AnsiValue(168),
             header:           Color::AnsiValue(109),
             border:           Color::AnsiValue(59),
+            whitespace_bg:    Color::AnsiValue(52),
+            line_number:      Color::AnsiValue(243),
             ..ColorTheme::empty()
         }
     }
@@ -146,6 +189,8 @@ impl ColorTheme {
             selected:         Color::AnsiValue(168),
             header:           Color::AnsiValue(109),
             border:           Color::AnsiValue(59),
+            whitespace_bg:    Color::AnsiValue(52),
+            line_number:      Color::AnsiValue(243),
             ..ColorTheme::empty()
         }
     }
@@ -165,12 +210,15 @@ impl ColorTheme {
             selected:         Color::AnsiValue(168),
             header:           Color::AnsiValue(31),
             border:           Color::AnsiValue(145),
+            whitespace_bg:    Color::AnsiValue(217),
+            line_number:      Color::AnsiValue(246),
             ..ColorTheme::empty()
         }
     }
 
-    fn from_options(color: &str) -> Self {
-        let mut theme = ColorTheme::dark256();
+    /// apply the colors on top of the `base` theme, a base scheme in them (e.g. `light`) replaces it
+    fn from_options(base: ColorTheme, color: &str) -> Self {
+        let mut theme = base;
         for pair in color.split(',') {
             let color: Vec<&str> = pair.split(':').collect();
             if color.len() < 2 {
@@ -180,7 +228,8 @@ impl ColorTheme {
                     "16"       => ColorTheme::default16(),
                     "bw"       => ColorTheme::bw(),
                     "empty"    => ColorTheme::empty(),
-                    "dark" | "default" | _ => ColorTheme::dark256(),
+                    "dark" | "default" => ColorTheme::dark256(),
+                    _ => base,
                 };
                 continue;
             }
@@ -215,6 +264,8 @@ impl ColorTheme {
                 "selected" | "marker"   => theme.selected         = new_color,
                 "header"                => theme.header           = new_color,
                 "border"                => theme.border           = new_color,
+                "whitespace_bg"         => theme.whitespace_bg    = new_color,
+                "line_number"           => theme.line_number      = new_color,
                 _ => {}
             }
         }
@@ -285,6 +336,15 @@ impl ColorTheme {
         }
     }
 
+    /// the hint shown in place of the empty query, dimmed
+    pub fn placeholder(&self) -> Attr {
+        Attr {
+            fg: self.query_fg,
+            bg: self.query_bg,
+            effect: Effect::DIM,
+        }
+    }
+
     pub fn cursor(&self) -> Attr {
         Attr {
             fg: self.cursor,
@@ -316,4 +376,41 @@ impl ColorTheme {
             effect: Effect::empty(),
         }
     }
+
+    pub fn trailing_whitespace(&self) -> Attr {
+        Attr {
+            fg: Color::Default,
+            bg: self.whitespace_bg,
+            effect: self.whitespace_effect,
+        }
+    }
+
+    pub fn line_number(&self) -> Attr {
+        Attr {
+            fg: self.line_number,
+            bg: self.bg,
+            effect: Effect::empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_color_scheme() {
+        let light = ColorTheme::of_scheme(ColorScheme::Light);
+        let theme = ColorTheme::from_options(light, "prompt:1");
+        assert_eq!(theme.prompt().fg, Color::AnsiValue(1));
+        assert_eq!(theme.matched().bg, light.matched().bg);
+
+        // the base scheme in the colors replaces it
+        let theme = ColorTheme::from_options(light, "dark,info:2");
+        assert_eq!(theme.matched().bg, ColorTheme::dark256().matched().bg);
+        assert_eq!(theme.info().fg, Color::AnsiValue(2));
+
+        let custom = ColorTheme::of_scheme(ColorScheme::Custom);
+        assert_eq!(custom.matched().fg, Color::Default);
+    }
 }
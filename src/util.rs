@@ -1,6 +1,8 @@
 use std::borrow::Cow;
 use std::cmp::min;
+use std::env;
 use std::prelude::v1::*;
+use std::process::Command;
 
 use regex::{Captures, Regex};
 use tuikit::prelude::*;
@@ -35,6 +37,9 @@ pub fn escape_single_quote(text: &str) -> String {
 ///             |<-    shift    -> |
 /// |< hscroll >|
 /// ```
+///
+/// Given the `rows`, a `\n` moves to the start of the next row, the rows after the last
+/// one are not printed.
 
 pub struct LinePrinter {
     start: usize,
@@ -45,6 +50,8 @@ pub struct LinePrinter {
     // start position
     row: usize,
     col: usize,
+    rows: Option<usize>, // `None` to print a `\n` as is
+    line: usize,         // the row printing at, from `row`
 
     tabstop: usize,
     shift: usize,
@@ -63,6 +70,8 @@ impl LinePrinter {
 
             row: 0,
             col: 0,
+            rows: None,
+            line: 0,
 
             tabstop: 8,
             shift: 0,
@@ -82,6 +91,11 @@ impl LinePrinter {
         self
     }
 
+    pub fn rows(mut self, rows: Option<usize>) -> Self {
+        self.rows = rows;
+        self
+    }
+
     pub fn tabstop(mut self, tabstop: usize) -> Self {
         self.tabstop = tabstop;
         self
@@ -115,27 +129,28 @@ impl LinePrinter {
     pub fn reset(&mut self) {
         self.current_pos = 0;
         self.screen_col = self.col;
+        self.line = 0;
 
         self.start = self.shift + self.hscroll_offset;
         self.end = self.start + self.container_width;
     }
 
-    fn print_ch_to_canvas(&mut self, canvas: &mut dyn Canvas, ch: char, attr: Attr, skip: bool) {
-        let w = ch.width().unwrap_or(2);
-
-        if !skip {
-            let _ = canvas.put_cell(self.row, self.screen_col, Cell::default().ch(ch).attribute(attr));
+    fn print_ch_to_canvas(&mut self, canvas: &mut dyn Canvas, ch: char, w: usize, attr: Attr, skip: bool) {
+        if !skip && self.line < self.rows.unwrap_or(1) {
+            let _ = canvas.put_cell(
+                self.row + self.line,
+                self.screen_col,
+                Cell::default().ch(ch).attribute(attr),
+            );
         }
 
         self.screen_col += w;
     }
 
-    fn print_char_raw(&mut self, canvas: &mut dyn Canvas, ch: char, attr: Attr, skip: bool) {
+    fn print_char_raw(&mut self, canvas: &mut dyn Canvas, ch: char, w: usize, attr: Attr, skip: bool) {
         // hide the content that outside the screen, and show the hint(i.e. `..`) for overflow
         // the hidden character
 
-        let w = ch.width().unwrap_or(2);
-
         assert!(self.current_pos >= 0);
         let current = self.current_pos as usize;
 
@@ -144,15 +159,15 @@ impl LinePrinter {
         } else if current < self.start + 2 && (self.shift > 0 || self.hscroll_offset > 0) {
             // print left ".."
             for _ in 0..min(w, current - self.start + 1) {
-                self.print_ch_to_canvas(canvas, '.', attr, skip);
+                self.print_ch_to_canvas(canvas, '.', 1, attr, skip);
             }
         } else if self.end - current <= 2 && (self.text_width > self.end) {
             // print right ".."
             for _ in 0..min(w, self.end - current) {
-                self.print_ch_to_canvas(canvas, '.', attr, skip);
+                self.print_ch_to_canvas(canvas, '.', 1, attr, skip);
             }
         } else {
-            self.print_ch_to_canvas(canvas, ch, attr, skip);
+            self.print_ch_to_canvas(canvas, ch, w, attr, skip);
         }
 
         self.current_pos += w as i32;
@@ -163,6 +178,11 @@ impl LinePrinter {
             '\u{08}' => {
                 // ignore \b character
             }
+            '\n' if self.rows.is_some() => {
+                self.line += 1;
+                self.current_pos = 0;
+                self.screen_col = self.col;
+            }
             '\t' => {
                 // handle tabstop
                 let rest = if self.current_pos < 0 {
@@ -171,30 +191,56 @@ impl LinePrinter {
                     self.tabstop - (self.current_pos as usize) % self.tabstop
                 };
                 for _ in 0..rest {
-                    self.print_char_raw(canvas, ' ', attr, skip);
+                    self.print_char_raw(canvas, ' ', 1, attr, skip);
                 }
             }
-            ch => self.print_char_raw(canvas, ch, attr, skip),
+            ch => self.print_char_raw(canvas, ch, ch.width().unwrap_or(2), attr, skip),
+        }
+    }
+
+    /// print a char of the width given by `cluster_char_widths`, a char of width 0 (e.g. a combining
+    /// mark or a ZWJ) is put in the cell after the previous char without advancing.
+    pub fn print_char_of_width(&mut self, canvas: &mut dyn Canvas, ch: char, width: usize, attr: Attr, skip: bool) {
+        match ch {
+            '\u{08}' | '\t' => self.print_char(canvas, ch, attr, skip),
+            '\n' if self.rows.is_some() => self.print_char(canvas, ch, attr, skip),
+            ch => self.print_char_raw(canvas, ch, width, attr, skip),
         }
     }
 }
 
+/// The display width of each char of `text` as drawn: a cell holds a single char, thus a grapheme
+/// cluster takes the columns of its chars, e.g. a flag or a char with a combining mark still fits
+/// in its own columns, while the emoji joined by ZWJs (e.g. a family) are drawn side by side, the
+/// joiners taking none.
+pub fn cluster_char_widths(text: &str) -> Vec<usize> {
+    text.chars().map(|ch| ch.width().unwrap_or(2)).collect()
+}
+
+/// print the `display()` of the item, fall back to its `text()` if the display is empty
 pub fn print_item(canvas: &mut dyn Canvas, printer: &mut LinePrinter, item: &ItemWrapper, default_attr: Attr) {
-    for (ch, attr) in item.display().iter() {
-        printer.print_char(canvas, ch, default_attr.extend(attr), false);
+    let display = item.display();
+    if display.stripped().is_empty() {
+        let text = item.text();
+        for (ch, width) in text.chars().zip(cluster_char_widths(&text)) {
+            printer.print_char_of_width(canvas, ch, width, default_attr, false);
+        }
+        return;
+    }
+
+    let widths = cluster_char_widths(display.stripped());
+    for ((ch, attr), width) in display.iter().zip(widths) {
+        printer.print_char_of_width(canvas, ch, width, default_attr.extend(attr), false);
     }
 }
 
-/// return an array, arr[i] store the display width till char[i]
+/// return an array, arr[i] store the display width till char[i], by the grapheme clusters (see
+/// `cluster_char_widths`)
 pub fn accumulate_text_width(text: &str, tabstop: usize) -> Vec<usize> {
     let mut ret = Vec::new();
     let mut w = 0;
-    for ch in text.chars() {
-        w += if ch == '\t' {
-            tabstop - (w % tabstop)
-        } else {
-            ch.width().unwrap_or(2)
-        };
+    for (ch, width) in text.chars().zip(cluster_char_widths(text)) {
+        w += if ch == '\t' { tabstop - (w % tabstop) } else { width };
         ret.push(w);
     }
     ret
@@ -329,6 +375,9 @@ pub fn depends_on_items(cmd: &str) -> bool {
 /// * `{+}` for all selections
 /// * `{q}` for query
 /// * `{cq}` for command query
+///
+/// The fields are quoted for where they are in the command, e.g. `"{}"` and `'{}'` are the item
+/// too, so that the shell takes them literally whatever they contain.
 pub fn inject_command<'a>(cmd: &'a str, context: InjectContext<'a>) -> Cow<'a, str> {
     RE_FIELDS.replace_all(cmd, |caps: &Captures| {
         // \{...
@@ -336,6 +385,8 @@ pub fn inject_command<'a>(cmd: &'a str, context: InjectContext<'a>) -> Cow<'a, s
             return caps[0].to_string();
         }
 
+        let quoting = quoting_at(cmd, caps.get(0).unwrap().start());
+
         // {1..} and other variant
         let range = &caps[1];
         assert!(range.len() >= 2);
@@ -367,7 +418,7 @@ pub fn inject_command<'a>(cmd: &'a str, context: InjectContext<'a>) -> Cow<'a, s
                         "n" => &index_str,
                         _ => get_string_by_range(context.delimiter, s, rest).unwrap_or(""),
                     };
-                    format!("'{}'", escape_single_quote(replacement))
+                    quote(replacement, quoting)
                 })
                 .collect::<Vec<_>>()
                 .join(" ");
@@ -383,13 +434,188 @@ pub fn inject_command<'a>(cmd: &'a str, context: InjectContext<'a>) -> Cow<'a, s
             _ => get_string_by_range(context.delimiter, context.current_selection, range).unwrap_or(""),
         };
 
-        format!("'{}'", escape_single_quote(replacement))
+        quote(replacement, quoting)
+    })
+}
+
+/// where a text is in a shell command
+#[derive(Clone, Copy, PartialEq)]
+enum Quoting {
+    None,
+    Single,
+    Double,
+}
+
+/// the quoting at the byte `pos` of the shell command
+fn quoting_at(cmd: &str, pos: usize) -> Quoting {
+    let mut quoting = Quoting::None;
+    let mut escaped = false;
+    for ch in cmd[..pos].chars() {
+        quoting = match (ch, quoting) {
+            _ if escaped => {
+                escaped = false;
+                quoting
+            }
+            ('\\', Quoting::None) | ('\\', Quoting::Double) => {
+                escaped = true;
+                quoting
+            }
+            ('\'', Quoting::None) => Quoting::Single,
+            ('\'', Quoting::Single) => Quoting::None,
+            ('"', Quoting::None) => Quoting::Double,
+            ('"', Quoting::Double) => Quoting::None,
+            _ => quoting,
+        };
+    }
+    quoting
+}
+
+/// quote the value for the shell to take it literally where it is put
+fn quote(value: &str, quoting: Quoting) -> String {
+    match quoting {
+        Quoting::None => format!("'{}'", escape_single_quote(value)),
+        Quoting::Single => escape_single_quote(value),
+        Quoting::Double => value
+            .chars()
+            .flat_map(|c| match c {
+                '"' | '\\' | '$' | '`' => vec!['\\', c],
+                _ => vec![c],
+            })
+            .collect::<String>(),
+    }
+}
+
+/// Expand `~` and environment variables in a command before it is passed to the shell, so that
+/// the result does not depend on the user's `$SHELL`.
+///
+/// * `~` at the beginning of a word (followed by `/` or the end of the word) => `$HOME`
+/// * `$NAME` and `${NAME}` => the value of the environment variable `NAME`
+///
+/// Nothing is expanded inside single quotes or after a backslash, `~` is not expanded inside
+/// double quotes, and unset variables are left untouched. The expanded values are quoted so that
+/// the shell takes them literally, but split into words out of double quotes as the shell does
+/// (e.g. `$EDITOR` of `code -w` is still the command `code` and its argument `-w`).
+pub fn expand_command<'a>(cmd: &'a str) -> Cow<'a, str> {
+    expand_command_with(cmd, |name| env::var(name).ok())
+}
+
+/// `expand_command` with only the variables in `allowed` (all of them if `None`), see `restrict_env`
+pub fn expand_command_in<'a>(cmd: &'a str, allowed: Option<&[String]>) -> Cow<'a, str> {
+    expand_command_with(cmd, |name| match allowed {
+        Some(allowed) if !allowed.iter().any(|allowed| allowed == name) => None,
+        _ => env::var(name).ok(),
     })
 }
 
+/// Keep only the environment variables in `allowed` for the command (all of them if `None`), e.g.
+/// so that the preview of an untrusted file does not see the secrets in the environment.
+pub fn restrict_env<'a>(command: &'a mut Command, allowed: Option<&[String]>) -> &'a mut Command {
+    if let Some(allowed) = allowed {
+        command.env_clear();
+        for name in allowed {
+            if let Some(value) = env::var_os(name) {
+                command.env(name, value);
+            }
+        }
+    }
+    command
+}
+
+fn expand_command_with<'a>(cmd: &'a str, lookup: impl Fn(&str) -> Option<String>) -> Cow<'a, str> {
+    if !cmd.contains(&['~', '$'][..]) {
+        return Cow::Borrowed(cmd);
+    }
+
+    let quote_value = |value: &str, in_double_quote: bool| {
+        if in_double_quote {
+            return quote(value, Quoting::Double);
+        }
+
+        let mut words = value
+            .split_whitespace()
+            .map(|word| quote(word, Quoting::None))
+            .collect::<Vec<_>>()
+            .join(" ");
+        // the spaces around still separate the value from the text next to it
+        if value.starts_with(char::is_whitespace) {
+            words.insert(0, ' ');
+        }
+        if value.ends_with(char::is_whitespace) && !words.trim().is_empty() {
+            words.push(' ');
+        }
+        words
+    };
+
+    let chars: Vec<char> = cmd.chars().collect();
+    let mut ret = String::with_capacity(cmd.len());
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut idx = 0;
+    while idx < chars.len() {
+        let ch = chars[idx];
+        idx += 1;
+        match ch {
+            '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+            _ if in_single_quote => {}
+            '"' => in_double_quote = !in_double_quote,
+            '\\' => {
+                ret.push(ch);
+                if idx < chars.len() {
+                    ret.push(chars[idx]);
+                    idx += 1;
+                }
+                continue;
+            }
+            '~' if !in_double_quote
+                && (idx == 1 || chars[idx - 2].is_whitespace() || "=:;|&(".contains(chars[idx - 2]))
+                && (idx == chars.len() || chars[idx] == '/' || chars[idx].is_whitespace()) =>
+            {
+                if let Some(home) = lookup("HOME") {
+                    ret.push_str(&quote(&home, Quoting::None));
+                    continue;
+                }
+            }
+            '$' => {
+                let braced = idx < chars.len() && chars[idx] == '{';
+                let start = if braced { idx + 1 } else { idx };
+                let mut end = start;
+                while end < chars.len() && (chars[end] == '_' || chars[end].is_ascii_alphanumeric()) {
+                    end += 1;
+                }
+                let name: String = chars[start..end].iter().collect();
+                let valid = !name.is_empty()
+                    && !name.starts_with(|c: char| c.is_ascii_digit())
+                    && (!braced || (end < chars.len() && chars[end] == '}'));
+                if let Some(value) = if valid { lookup(&name) } else { None } {
+                    ret.push_str(&quote_value(&value, in_double_quote));
+                    idx = if braced { end + 1 } else { end };
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        ret.push(ch);
+    }
+
+    Cow::Owned(ret)
+}
+
+/// the text drawn on the row of the screen, without the blanks around it
+#[cfg(test)]
+pub fn screen_row(screen: &tuikit::screen::Screen, row: usize) -> String {
+    screen
+        .iter_cell()
+        .filter(|&(r, _, _)| r == row)
+        .map(|(_, _, cell)| cell.ch)
+        .collect::<String>()
+        .trim_matches(|ch| ch == ' ' || ch == '\0')
+        .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tuikit::screen::Screen;
 
     #[test]
     fn test_accumulate_text_width() {
@@ -397,6 +623,42 @@ mod tests {
         assert_eq!(accumulate_text_width("ab中de国g", 8), vec![1, 2, 4, 5, 6, 8, 9]);
         assert_eq!(accumulate_text_width("ab\tdefg", 8), vec![1, 2, 8, 9, 10, 11, 12]);
         assert_eq!(accumulate_text_width("ab中\te国g", 8), vec![1, 2, 4, 8, 9, 11, 12]);
+        // the family (man, ZWJ, woman, ZWJ, girl) takes the columns of its emoji, the flag two
+        assert_eq!(accumulate_text_width("a👨‍👩‍👧b", 8), vec![1, 3, 3, 5, 5, 7, 8]);
+        assert_eq!(accumulate_text_width("🇺🇸b", 8), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_cluster_char_widths() {
+        assert_eq!(cluster_char_widths("ab"), vec![1, 1]);
+        assert_eq!(cluster_char_widths("👨‍👩‍👧"), vec![2, 0, 2, 0, 2]);
+        assert_eq!(cluster_char_widths("🇺🇸"), vec![1, 1]);
+        assert_eq!(cluster_char_widths("e\u{301}"), vec![1, 0]);
+        assert_eq!(cluster_char_widths("\u{915}\u{93f}"), vec![1, 1]);
+    }
+
+    fn print_line(text: &str) -> String {
+        let mut screen = Screen::new(10, 1);
+        let mut printer = LinePrinter::builder().container_width(10).text_width(10).build();
+        for (ch, width) in text.chars().zip(cluster_char_widths(text)) {
+            printer.print_char_of_width(&mut screen, ch, width, Attr::default(), false);
+        }
+        screen_row(&screen, 0)
+    }
+
+    #[test]
+    fn test_print_combining_mark() {
+        // the mark is kept after its base char
+        assert_eq!(print_line("ae\u{301}"), "ae\u{301}");
+        assert_eq!(print_line("\u{915}\u{93f}b"), "\u{915}\u{93f}b");
+        assert_eq!(print_line("🇺🇸b"), "🇺🇸b");
+    }
+
+    #[test]
+    fn test_print_zwj_sequence() {
+        // every emoji of the family (man, ZWJ, woman, ZWJ, girl) is drawn, the joiners take no cell
+        assert_eq!(print_line("👨‍👩‍👧"), "👨 👩 👧");
+        assert_eq!(print_line("👨‍👩‍👧b"), "👨 👩 👧 b");
     }
 
     #[test]
@@ -448,10 +710,72 @@ mod tests {
         assert_eq!("'a' 'x'", inject_command("{+1}", default_context));
         assert_eq!("'b' 'y'", inject_command("{+2}", default_context));
         assert_eq!("'0' '1'", inject_command("{+n}", default_context));
+
+        // quoted for where they are, the shell takes them literally
+        let nasty_context = InjectContext {
+            current_selection: "$(x) 'y'",
+            selections: &[],
+            ..default_context
+        };
+        assert_eq!("cat '$(x) '\\''y'\\'''", inject_command("cat {}", nasty_context));
+        assert_eq!("cat \"\\$(x) 'y'\"", inject_command("cat \"{}\"", nasty_context));
+        assert_eq!("cat '$(x) '\\''y'\\'''", inject_command("cat '{}'", nasty_context));
+        assert_eq!(
+            "echo '\"' '$(x) '\\''y'\\'''",
+            inject_command("echo '\"' {}", nasty_context)
+        );
+        assert_eq!(
+            "echo \\\" '$(x) '\\''y'\\'''",
+            inject_command("echo \\\" {}", nasty_context)
+        );
+    }
+
+    #[test]
+    fn test_restrict_env() {
+        let output = |allowed: Option<&[String]>| {
+            let mut command = Command::new("sh");
+            let output = restrict_env(&mut command, allowed)
+                .env("SKIM_TEST_ADDED", "added")
+                .arg("-c")
+                .arg("echo \"$HOME $SKIM_TEST_ADDED\"")
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).trim_end().to_string()
+        };
+
+        let home = env::var("HOME").unwrap_or_default();
+        assert_eq!(output(None), format!("{} added", home));
+        assert_eq!(output(Some(&["HOME".to_string()])), format!("{} added", home));
+        assert_eq!(output(Some(&[])), " added");
     }
 
     #[test]
     fn test_escape_single_quote() {
         assert_eq!("'\\''a'\\''\\0", escape_single_quote("'a'\0"));
     }
+
+    #[test]
+    fn test_expand_command() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/user".to_string()),
+            "FOO" => Some("foo bar".to_string()),
+            "QUOTE" => Some("it's".to_string()),
+            "EDITOR" => Some("code -w".to_string()),
+            "SPACED" => Some(" b ".to_string()),
+            _ => None,
+        };
+        let expand = |cmd| expand_command_with(cmd, lookup).to_string();
+
+        assert_eq!("cat {}", expand("cat {}"));
+        assert_eq!("cat '/home/user'/file", expand("cat ~/file"));
+        assert_eq!("cd '/home/user'", expand("cd ~"));
+        assert_eq!("echo a~b ~user", expand("echo a~b ~user"));
+        assert_eq!("echo 'foo' 'bar' 'foo' 'bar'x", expand("echo $FOO ${FOO}x"));
+        assert_eq!("'code' '-w' {}", expand("$EDITOR {}"));
+        assert_eq!("echo a 'b' ", expand("echo a$SPACED"));
+        assert_eq!("echo \"foo bar/~\"", expand("echo \"$FOO/~\""));
+        assert_eq!("echo 'it'\\''s'", expand("echo $QUOTE"));
+        assert_eq!("echo '$FOO ~' \\$FOO", expand("echo '$FOO ~' \\$FOO"));
+        assert_eq!("echo $UNSET ${FOO $1 $", expand("echo $UNSET ${FOO $1 $"));
+    }
 }